@@ -0,0 +1,410 @@
+//! Bech32m encoding of chunks and messages for handwritten backups.
+//!
+//! The regular encoding (see [crate::conv]) has no checksum, so a chunk copied by
+//! hand onto paper or steel can't be verified until the restoration. This module
+//! encodes chunks and single-part messages using the `bech32m` alphabet and
+//! `BCH` checksum (BIP-350), grouped into short words:
+//!
+//! ```text
+//! icodc1 qpzr y9x8 gf2t ...
+//! ```
+//!
+//! Chunks use `icodc` and messages `icodm` human readable part. When decoding,
+//! up to [MAX_CORRECTED_ERRORS] substituted characters are corrected. Note the
+//! checksum only guarantees the correction of errors for strings up to 89
+//! characters, hence chunks are transcribed using the compact encoding (see
+//! [icod_crypto::shamir::Chunk::encode_compact]), which `CRC-32` checksum
+//! verifies every correction. The message parts are authenticated
+//! once decrypted.
+
+use std::collections::HashMap;
+
+use icod_crypto::{
+  encryption::EncryptedMessagePart,
+  shamir::{Chunk, CHUNK_ENCODING_MAGIC_SEQUENCE},
+};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::{encryption::MSG_PREFIX, shamir::CHUNK_PREFIX, JsValueOrString};
+
+/// Human readable part of transcribed chunks.
+pub const CHUNK_HRP: &str = "icodc";
+/// Human readable part of transcribed message parts.
+pub const MESSAGE_HRP: &str = "icodm";
+/// Maximal number of substituted characters corrected when decoding.
+pub const MAX_CORRECTED_ERRORS: usize = 2;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+const GENERATOR: [u32; 5] = [
+  0x3b6a_57b2,
+  0x2650_8e6d,
+  0x1ea1_19fa,
+  0x3d42_33dd,
+  0x2a14_62b3,
+];
+const CHECKSUM_LEN: usize = 6;
+const WORD_LEN: usize = 4;
+
+/// An error occuring when transcribing or decoding the transcription.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The value is not a chunk or a message part.
+  UnknownValue,
+  /// Only single-part messages can be transcribed.
+  MultipartMessage,
+  /// The value could not be decoded.
+  DecodingError(String),
+  /// There are too many errors to be corrected.
+  InvalidChecksum,
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+/// Encode given chunk or single-part message for handwritten backup.
+///
+/// Accepts the strings produced by other functions (i.e. `icod-chunk:...`).
+/// Note the name of the chunk is not preserved (see [crate::shamir::set_chunk_metadata])
+/// and the chunk is converted to the compact encoding (see [crate::shamir::compact_chunk]).
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn encode_for_transcription(item: String) -> Result<String, Error> {
+  if let Some((_name, chunk)) = crate::identify::identify_chunk(&item) {
+    let bytes = crate::conv::decode(chunk).map_err(|_| decoding_error("chunk"))?;
+    let compact = Chunk::decode(&bytes)
+      .map_err(decoding_error)?
+      .encode_compact();
+    let bytes = compact
+      .strip_prefix(CHUNK_ENCODING_MAGIC_SEQUENCE)
+      .ok_or(Error::UnknownValue)?;
+    return Ok(encode(CHUNK_HRP, bytes));
+  }
+
+  if let Some(msg) = crate::conv::strip_prefix(&item, MSG_PREFIX) {
+    let bytes = crate::conv::decode(msg).map_err(|_| decoding_error("message part"))?;
+    let part = EncryptedMessagePart::decode(&bytes).map_err(decoding_error)?;
+    if part.parts_total() != 1 {
+      return Err(Error::MultipartMessage);
+    }
+    return Ok(encode(MESSAGE_HRP, &bytes));
+  }
+
+  Err(Error::UnknownValue)
+}
+
+/// Decode the transcription produced by [encode_for_transcription].
+///
+/// Returns the regular encoding of the chunk or message part, which can be
+/// passed to other functions. See [crate::identify::identify] to learn about
+/// the corrected errors.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn decode_transcription(words: String) -> Result<JsValueOrString, Error> {
+  let (item, _corrected) = decode_item(&words)?;
+  Ok(crate::conv::js_value_or_string(item))
+}
+
+/// Check if given string looks like a transcription.
+pub(crate) fn is_transcription(item: &str) -> bool {
+  let normalized = normalize(item);
+  [CHUNK_HRP, MESSAGE_HRP]
+    .iter()
+    .any(|hrp| normalized.starts_with(&format!("{}1", hrp)))
+}
+
+/// Decode the transcription into the regular encoding and the positions of corrected characters.
+pub(crate) fn decode_item(words: &str) -> Result<(String, Vec<usize>), Error> {
+  let (hrp, bytes, corrected) = decode(words)?;
+  let item = match hrp.as_str() {
+    CHUNK_HRP => {
+      let bytes = [CHUNK_ENCODING_MAGIC_SEQUENCE, &bytes].concat();
+      Chunk::decode(&bytes).map_err(decoding_error)?;
+      crate::conv::bytes_to_prefixed_str(CHUNK_PREFIX, &bytes)
+    }
+    MESSAGE_HRP => {
+      EncryptedMessagePart::decode(&bytes).map_err(decoding_error)?;
+      crate::conv::bytes_to_prefixed_str(MSG_PREFIX, &bytes)
+    }
+    _ => return Err(Error::UnknownValue),
+  };
+  Ok((item, corrected))
+}
+
+fn decoding_error(e: impl std::fmt::Debug) -> Error {
+  Error::DecodingError(format!("{:?}", e))
+}
+
+/// Encode the bytes as `bech32m` string grouped into words.
+fn encode(hrp: &str, bytes: &[u8]) -> String {
+  let mut data = to_base32(bytes);
+  let residue = polymod(
+    hrp_expand(hrp)
+      .into_iter()
+      .chain(data.clone())
+      .chain([0; CHECKSUM_LEN]),
+  ) ^ BECH32M_CONST;
+  data.extend((0..CHECKSUM_LEN).map(|i| ((residue >> (5 * (CHECKSUM_LEN - 1 - i))) & 31) as u8));
+
+  let chars: Vec<u8> = data.iter().map(|v| CHARSET[*v as usize]).collect();
+  let words: Vec<&str> = chars
+    .chunks(WORD_LEN)
+    .map(|w| std::str::from_utf8(w).expect("CHARSET is ASCII; qed"))
+    .collect();
+  format!("{}1 {}", hrp, words.join(" "))
+}
+
+/// Decode the `bech32m` string correcting up to [MAX_CORRECTED_ERRORS] substitutions.
+///
+/// Characters outside of the alphabet are treated as substitutions.
+fn decode(words: &str) -> Result<(String, Vec<u8>, Vec<usize>), Error> {
+  let normalized = normalize(words);
+  let (hrp, data) = normalized.rsplit_once('1').ok_or(Error::UnknownValue)?;
+  if data.len() < CHECKSUM_LEN {
+    return Err(decoding_error("Not enough data"));
+  }
+  let mut data: Vec<u8> = data
+    .bytes()
+    .map(|c| CHARSET.iter().position(|x| *x == c).unwrap_or(0) as u8)
+    .collect();
+
+  let residue = polymod(hrp_expand(hrp).into_iter().chain(data.iter().copied())) ^ BECH32M_CONST;
+  let corrections = correct(residue, data.len())?;
+  for (position, error) in &corrections {
+    data[*position] ^= error;
+  }
+  data.truncate(data.len() - CHECKSUM_LEN);
+
+  let bytes = from_base32(&data).ok_or_else(|| decoding_error("Invalid padding"))?;
+  let mut corrected: Vec<usize> = corrections.into_iter().map(|(p, _)| p).collect();
+  corrected.sort_unstable();
+  Ok((hrp.to_owned(), bytes, corrected))
+}
+
+/// Find the positions and values of errors matching the non-zero `residue`.
+fn correct(residue: u32, len: usize) -> Result<Vec<(usize, u8)>, Error> {
+  if residue == 0 {
+    return Ok(vec![]);
+  }
+
+  // The checksum is linear, so the residue of erroneous data is a sum of
+  // residues caused by every substitution alone.
+  let mut basis = vec![[0u32; 5]; len];
+  let mut states = [1, 2, 4, 8, 16];
+  for row in basis.iter_mut().rev() {
+    *row = states;
+    states = states.map(|state| polymod_step(state, 0));
+  }
+  let mut singles = HashMap::<u32, Vec<(usize, u8)>>::with_capacity(len * 31);
+  for (position, basis) in basis.iter().enumerate() {
+    for error in 1u8..32 {
+      let residue = (0..5)
+        .filter(|bit| error & (1 << bit) != 0)
+        .fold(0, |acc, bit| acc ^ basis[bit]);
+      singles.entry(residue).or_default().push((position, error));
+    }
+  }
+
+  // Long strings are beyond the guarantees of the code, so the fewest
+  // substitutions matching the residue may not be unique.
+  if let Some(matching) = singles.get(&residue) {
+    return match matching.as_slice() {
+      [single] => Ok(vec![*single]),
+      _ => Err(Error::InvalidChecksum),
+    };
+  }
+  let mut pairs = vec![];
+  for (first_residue, firsts) in &singles {
+    let Some(seconds) = singles.get(&(residue ^ first_residue)) else {
+      continue;
+    };
+    for first in firsts {
+      pairs.extend(
+        seconds
+          .iter()
+          .filter(|second| first.0 < second.0)
+          .map(|second| vec![*first, *second]),
+      );
+    }
+  }
+  match pairs.len() {
+    1 => Ok(pairs.remove(0)),
+    _ => Err(Error::InvalidChecksum),
+  }
+}
+
+/// Strip whitespace and separators, since the words may be written down in many ways.
+fn normalize(words: &str) -> String {
+  words
+    .chars()
+    .filter(|c| !c.is_whitespace() && *c != '-')
+    .flat_map(char::to_lowercase)
+    .collect()
+}
+
+fn polymod_step(state: u32, value: u8) -> u32 {
+  let top = state >> 25;
+  let mut state = ((state & 0x1ff_ffff) << 5) ^ value as u32;
+  for (i, generator) in GENERATOR.iter().enumerate() {
+    if (top >> i) & 1 == 1 {
+      state ^= generator;
+    }
+  }
+  state
+}
+
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+  values.into_iter().fold(1, polymod_step)
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+  let hrp = hrp.as_bytes();
+  hrp
+    .iter()
+    .map(|c| c >> 5)
+    .chain([0])
+    .chain(hrp.iter().map(|c| c & 31))
+    .collect()
+}
+
+fn to_base32(bytes: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity((bytes.len() * 8).div_ceil(5));
+  let (mut acc, mut bits) = (0u32, 0);
+  for byte in bytes {
+    acc = (acc << 8) | *byte as u32;
+    bits += 8;
+    while bits >= 5 {
+      bits -= 5;
+      out.push(((acc >> bits) & 31) as u8);
+    }
+  }
+  if bits > 0 {
+    out.push(((acc << (5 - bits)) & 31) as u8);
+  }
+  out
+}
+
+fn from_base32(data: &[u8]) -> Option<Vec<u8>> {
+  let mut out = Vec::with_capacity(data.len() * 5 / 8);
+  let (mut acc, mut bits) = (0u32, 0);
+  for value in data {
+    acc = (acc << 5) | *value as u32;
+    bits += 5;
+    if bits >= 8 {
+      bits -= 8;
+      out.push(((acc >> bits) & 0xff) as u8);
+    }
+  }
+  // The padding has to be shorter than 5 bits and zeroed.
+  if bits >= 5 || (acc & ((1 << bits) - 1)) != 0 {
+    return None;
+  }
+  Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use icod_crypto::encryption::KEY_SIZE;
+  use pretty_assertions::assert_eq;
+
+  fn chunk() -> String {
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 1,
+      spare: 1,
+    };
+    crate::shamir::split_into_chunks([1u8; KEY_SIZE].to_vec(), conf)
+      .unwrap()
+      .remove(1)
+  }
+
+  fn typo(words: &str, at: usize, c: char) -> String {
+    let mut chars: Vec<char> = words.chars().collect();
+    chars[at] = if chars[at] == c { 'q' } else { c };
+    chars.into_iter().collect()
+  }
+
+  #[test]
+  fn should_match_bip350_test_vector() {
+    assert_eq!(
+      decode("a1lqfn3a").unwrap(),
+      ("a".to_owned(), vec![], vec![])
+    );
+    assert_eq!(encode("a", &[]), "a1 lqfn 3a");
+  }
+
+  #[test]
+  fn should_transcribe_chunk_and_correct_errors() {
+    // given
+    let chunk = chunk();
+    let words = encode_for_transcription(chunk.clone()).unwrap();
+
+    // when
+    let decoded = decode_item(&words.to_uppercase().replace(' ', "-")).unwrap();
+    let one_error = decode_item(&typo(&words, 20, 'p')).unwrap();
+    let two_errors = decode_item(&typo(&typo(&words, 12, 'p'), 60, 'z')).unwrap();
+    let three_errors = decode_item(&typo(&typo(&typo(&words, 12, 'p'), 60, 'z'), 70, 'x'));
+    // `b` is not part of the alphabet.
+    let unknown_char = decode_item(&typo(&words, 9, 'b')).unwrap();
+    let key = crate::shamir::recover_key(vec![decoded.0.clone()]).unwrap();
+
+    // then
+    assert!(words.starts_with("icodc1 "));
+    assert!(words.split(' ').skip(1).all(|w| w.len() <= WORD_LEN));
+    assert_eq!(decoded.1, vec![]);
+    assert_eq!(key, crate::shamir::recover_key(vec![chunk]).unwrap());
+    assert_eq!(one_error, (decoded.0.clone(), vec![11]));
+    assert_eq!(two_errors, (decoded.0.clone(), vec![4, 43]));
+    assert!(three_errors.is_err());
+    assert_eq!(unknown_char.0, decoded.0);
+  }
+
+  #[test]
+  fn should_not_accept_miscorrected_chunk() {
+    // given
+    let words = encode_for_transcription(chunk()).unwrap();
+    let (hrp, data) = words
+      .replace(' ', "")
+      .split_once('1')
+      .map(|(h, d)| (h.to_owned(), d.to_owned()))
+      .unwrap();
+    let mut data: Vec<u8> = data
+      .bytes()
+      .map(|c| CHARSET.iter().position(|x| *x == c).unwrap() as u8)
+      .collect();
+    // alter the data and fix the `bech32m` checksum, so only `CRC-32` catches it.
+    data[4] ^= 1;
+    data.truncate(data.len() - CHECKSUM_LEN);
+    let bytes = from_base32(&data).unwrap();
+    let tampered = encode(&hrp, &bytes);
+
+    // when
+    let result = decode_item(&tampered);
+
+    // then
+    assert!(matches!(result, Err(Error::DecodingError(_))));
+  }
+
+  #[test]
+  fn should_transcribe_single_part_messages_only() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let single = crate::encryption::encrypt_message(key.clone(), "Hi!".into(), None).unwrap();
+    let multi = crate::encryption::encrypt_message(key, "Hello World!".into(), Some(5)).unwrap();
+
+    // when
+    let words = encode_for_transcription(single[0].clone()).unwrap();
+
+    // then
+    assert!(words.starts_with("icodm1 "));
+    assert_eq!(decode_item(&words).unwrap(), (single[0].clone(), vec![]));
+    assert_eq!(
+      encode_for_transcription(multi[0].clone()),
+      Err(Error::MultipartMessage)
+    );
+  }
+}
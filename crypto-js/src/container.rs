@@ -0,0 +1,219 @@
+//! Plaintext container functions exposed to JS.
+
+use icod_crypto::encryption::{Container, ContainerError, Entry, EntryKind};
+use serde_bytes::ByteBuf;
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// An error occuring during container encoding or decoding.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The JS value does not represent a list of entries.
+  ValueError(String),
+  /// The entry has both text and binary content or none of them.
+  InvalidEntry(String),
+  /// The container could not be encoded or decoded.
+  ContainerError(ContainerError),
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<ContainerError> for Error {
+  fn from(value: ContainerError) -> Self {
+    Self::ContainerError(value)
+  }
+}
+
+/// A single entry of the container.
+///
+/// Text notes have only `text` set, while files have `data` (`Uint8Array`),
+/// `filename` and `mime_type`.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ContainerEntry {
+  /// Name of the entry.
+  pub name: String,
+  /// Content of the text note.
+  #[serde(default)]
+  pub text: Option<String>,
+  /// Original name of the file.
+  #[serde(default)]
+  pub filename: Option<String>,
+  /// MIME type of the file.
+  #[serde(default)]
+  pub mime_type: Option<String>,
+  /// File content.
+  #[serde(default)]
+  pub data: Option<ByteBuf>,
+  /// Byte size of the content. Ignored during encoding.
+  #[serde(default)]
+  pub size: usize,
+}
+
+impl ContainerEntry {
+  fn into_icod(self) -> Result<Entry, Error> {
+    match (self.text, self.data) {
+      (Some(text), None) => Ok(Entry::text(self.name, &text)),
+      (None, Some(data)) => Ok(Entry::file(
+        self.name,
+        self.filename.unwrap_or_default(),
+        self
+          .mime_type
+          .unwrap_or_else(|| "application/octet-stream".into()),
+        data.into_vec(),
+      )),
+      _ => Err(Error::InvalidEntry(self.name)),
+    }
+  }
+
+  fn from_icod(entry: Entry) -> Self {
+    let size = entry.size();
+    let text = entry.as_text().map(String::from);
+    match entry.kind() {
+      EntryKind::Text => Self {
+        name: entry.name().into(),
+        text,
+        filename: None,
+        mime_type: None,
+        data: None,
+        size,
+      },
+      EntryKind::File {
+        filename,
+        mime_type,
+      } => Self {
+        name: entry.name().into(),
+        text: None,
+        filename: Some(filename.clone()),
+        mime_type: Some(mime_type.clone()),
+        data: Some(ByteBuf::from(entry.data().to_vec())),
+        size,
+      },
+    }
+  }
+}
+
+/// A list of [ContainerEntry] for tests.
+#[cfg(test)]
+pub type EntriesOrJsValue = Vec<ContainerEntry>;
+/// A serde-encoded list of [ContainerEntry] objects.
+#[cfg(not(test))]
+pub type EntriesOrJsValue = JsValue;
+
+/// Encode given list of entries into a container.
+///
+/// The result is meant to be passed to [crate::secure_restore::secure_bytes].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn encode_container(entries: EntriesOrJsValue) -> Result<Vec<u8>, Error> {
+  #[cfg(not(test))]
+  let entries: Vec<ContainerEntry> =
+    serde_wasm_bindgen::from_value(entries).map_err(|e| Error::ValueError(format!("{}", e)))?;
+  let entries = entries
+    .into_iter()
+    .map(ContainerEntry::into_icod)
+    .collect::<Result<_, _>>()?;
+  let container = Container::new(entries);
+
+  Ok(container.try_encode()?.into())
+}
+
+/// Decode the data restored using [crate::secure_restore::restore_bytes] into a list of entries.
+///
+/// Messages that were secured as plain text are returned as a single unnamed text entry.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn decode_container(data: Vec<u8>) -> Result<EntriesOrJsValue, Error> {
+  let container = Container::from_plaintext(&data);
+  let entries: Vec<_> = container
+    .into_entries()
+    .into_iter()
+    .map(ContainerEntry::from_icod)
+    .collect();
+
+  #[cfg(not(test))]
+  return Ok(
+    serde_wasm_bindgen::to_value(&entries).expect("ContainerEntry serialization is infallible"),
+  );
+  #[cfg(test)]
+  Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secure_restore::{restore_bytes, secure_bytes, secure_message};
+  use crate::shamir::ChunksConfiguration;
+  use pretty_assertions::assert_eq;
+
+  fn text(name: &str, text: &str) -> ContainerEntry {
+    ContainerEntry {
+      name: name.into(),
+      text: Some(text.into()),
+      filename: None,
+      mime_type: None,
+      data: None,
+      size: text.len(),
+    }
+  }
+
+  #[test]
+  fn should_secure_and_restore_container() {
+    // given
+    let photo = vec![0xff, 0xd8, 0xff, 0xe0, 0, 0x10];
+    let entries = vec![
+      text("note", "Passwords are in the safe."),
+      ContainerEntry {
+        name: "photo".into(),
+        text: None,
+        filename: Some("safe.jpg".into()),
+        mime_type: Some("image/jpeg".into()),
+        data: Some(ByteBuf::from(photo.clone())),
+        size: 0,
+      },
+    ];
+    let configuration = ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+
+    // when
+    let data = encode_container(entries).unwrap();
+    let secured = secure_bytes(data, None, configuration, None).unwrap();
+    let restored = restore_bytes(secured.encrypted_message, secured.chunks).unwrap();
+    let entries = decode_container(restored).unwrap();
+
+    // then
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0], text("note", "Passwords are in the safe."));
+    assert_eq!(entries[1].filename.as_deref(), Some("safe.jpg"));
+    assert_eq!(entries[1].data.as_deref(), Some(&photo));
+    assert_eq!(entries[1].size, 6);
+  }
+
+  #[test]
+  fn should_decode_legacy_text_message() {
+    let configuration = ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+    let secured = secure_message("Hello!".into(), None, configuration, None).unwrap();
+    let restored = restore_bytes(secured.encrypted_message, secured.chunks).unwrap();
+
+    assert_eq!(decode_container(restored), Ok(vec![text("", "Hello!")]));
+  }
+
+  #[test]
+  fn should_reject_invalid_entries() {
+    let mut entry = text("both", "text");
+    entry.data = Some(ByteBuf::from(vec![1]));
+
+    assert_eq!(
+      encode_container(vec![entry]),
+      Err(Error::InvalidEntry("both".into()))
+    );
+  }
+}
@@ -13,12 +13,19 @@ pub(crate) fn encode(b: &[u8]) -> String {
   data_encoding::BASE32_DNSSEC.encode(b)
 }
 
+/// Note the uppercase strings (see [crate::qr::to_qr_alphanumeric]) are accepted as well.
 pub(crate) fn decode(v: &str) -> Result<Vec<u8>, ()> {
   data_encoding::BASE32_DNSSEC
-    .decode(v.as_bytes())
+    .decode(v.to_ascii_lowercase().as_bytes())
     .map_err(|_| ())
 }
 
+/// Strip the lowercase `prefix` or it's uppercase variant used by the QR alphanumeric profile.
+pub(crate) fn strip_prefix<'a>(v: &'a str, prefix: &str) -> Option<&'a str> {
+  v.strip_prefix(prefix)
+    .or_else(|| v.strip_prefix(prefix.to_ascii_uppercase().as_str()))
+}
+
 pub fn bytes_to_prefixed_str(prefix: &str, b: &[u8]) -> String {
   format!("{}{}", prefix, encode(b))
 }
@@ -35,7 +42,7 @@ pub fn prefixed_str_js_to_bytes(
 ) -> Result<Vec<u8>, Error> {
   #[cfg(not(test))]
   let v = v.as_string().ok_or(Error::ValueError)?;
-  let s = v.strip_prefix(prefix).ok_or(Error::PrefixError)?;
+  let s = strip_prefix(&v, prefix).ok_or(Error::PrefixError)?;
   let s = if strip_to_next_colon {
     if let Some(idx) = s.find(":") {
       s.split_at(idx + 1).1
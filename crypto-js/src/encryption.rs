@@ -11,6 +11,9 @@ use wasm_bindgen::prelude::*;
 /// Typically this will be obtained by scanning a QR code.
 pub const MSG_PREFIX: &'static str = "icod-msg:";
 
+/// A prefix of every fragment of the dispersed encrypted message.
+pub const FRAGMENT_PREFIX: &str = "icod-fragment:";
+
 /// An error that happened during encryption or decryption.
 #[derive(Debug)]
 pub enum Error {
@@ -37,6 +40,7 @@ impl From<encryption::Error> for Error {
     match value {
       encryption::Error::UnsupportedVersion => Self::VersionError,
       encryption::Error::EncryptionError => Self::CryptoError,
+      encryption::Error::Io(_) | encryption::Error::Compression => Self::CryptoError,
     }
   }
 }
@@ -46,9 +50,8 @@ impl From<encryption::EncryptedMessageError> for Error {
     use encryption::EncryptedMessageError::*;
     match value {
       DataTooBig => Self::DataTooBig,
-      MissingParts | MalformedData(_) | InvalidVersion => {
-        Self::MessageDecodingError(format!("{:?}", value))
-      }
+      MissingParts | MalformedData(_) | InvalidVersion | ChecksumMismatch | CorruptedPart(_)
+      | ForeignPart(_) => Self::MessageDecodingError(format!("{:?}", value)),
     }
   }
 }
@@ -85,6 +88,28 @@ pub fn encrypt_message(
   Ok(conv::msg_parts_to_js(encoded))
 }
 
+/// Encrypt given `message` exactly like [encrypt_message], but using
+/// the encryption scheme of given `version`.
+///
+/// For instance version `3` (`XChaCha20-Poly1305`) is faster on devices
+/// without AES hardware support. The decryption picks the scheme automatically.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn encrypt_message_with_version(
+  key: Vec<u8>,
+  message: String,
+  split: Option<usize>,
+  version: u8,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let key = crate::parse_key(key).map_err(|_| Error::InvalidKeySize)?;
+  let version = encryption::EncryptionKeyVersion::from_byte(version).ok_or(Error::VersionError)?;
+  let key = MessageEncryptionKey::new(key).with_version(version);
+  let message = Message::from_str(&message);
+
+  let encrypted = encryption::encrypt_message(&key, &message)?;
+  let encoded = encrypted.split_and_encode(split);
+  Ok(conv::msg_parts_to_js(encoded))
+}
+
 /// Decrypt given message using provided `key`.
 ///
 /// - `key` must be exactly [KEY_SIZE] bytes (32-bytes for V0).
@@ -149,4 +174,21 @@ mod tests {
     let original = decrypt_message(key, encrypted).unwrap();
     assert_eq!(original, message);
   }
+
+  #[test]
+  fn should_encrypt_and_decrypt_with_xchacha() {
+    let key = [1u8; KEY_SIZE].to_vec();
+    let message = "This is a secret message.";
+
+    let encrypted = encrypt_message_with_version(key.clone(), message.to_owned(), None, 3).unwrap();
+    let original = decrypt_message(key.clone(), encrypted.clone()).unwrap();
+
+    assert_eq!(encrypted.len(), 1);
+    assert!(encrypted[0].starts_with("icod-msg:0c"));
+    assert_eq!(original, message);
+    assert!(matches!(
+      encrypt_message_with_version(key, message.to_owned(), None, 100),
+      Err(Error::VersionError)
+    ));
+  }
 }
@@ -0,0 +1,144 @@
+//! Fountain-coded frames for animated QR codes exposed to JS.
+
+use icod_crypto::encryption::{EncryptedMessage, FountainDecoder, FountainEncoder};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::encryption::conv::{js_to_msg_parts, msg_parts_to_js};
+use crate::JsValueOrString;
+
+/// A prefix of every fountain-coded frame of the encrypted message.
+pub const FRAME_PREFIX: &str = "icod-frame:";
+
+/// An error occuring when encoding or decoding the frames.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The message parts could not be decoded.
+  MessageDecodingError(String),
+  /// The frame could not be decoded or belongs to a different message.
+  FrameError(String),
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+/// Encode the encrypted message as a stream of fountain-coded frames.
+///
+/// The `message_parts` are collated into a single message, which is then encoded
+/// into frames carrying `fragment_len` bytes of data each. The function returns
+/// `count` frames starting at sequence number `start`, so the stream can be
+/// generated lazily while looping the animated QR code.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn encode_message_frames(
+  message_parts: Vec<JsValueOrString>,
+  fragment_len: usize,
+  start: u32,
+  count: u32,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let parts =
+    js_to_msg_parts(message_parts).map_err(|e| Error::MessageDecodingError(format!("{:?}", e)))?;
+  let message = EncryptedMessage::collate_from_parts(parts)
+    .map_err(|e| Error::MessageDecodingError(format!("{:?}", e)))?;
+  let encoder = FountainEncoder::new(message, fragment_len);
+  Ok(
+    (start..start.saturating_add(count))
+      .map(|seq| crate::conv::bytes_to_prefixed_str_js(FRAME_PREFIX, &encoder.frame(seq)))
+      .collect(),
+  )
+}
+
+/// Incremental decoder of the frames produced by [encode_message_frames].
+///
+/// The frames can be fed as they are scanned by the camera, in any order.
+#[cfg_attr(not(test), wasm_bindgen)]
+#[derive(Default)]
+pub struct FrameDecoder {
+  decoder: FountainDecoder,
+}
+
+#[cfg_attr(not(test), wasm_bindgen)]
+impl FrameDecoder {
+  /// Create a new decoder.
+  #[cfg_attr(not(test), wasm_bindgen(constructor))]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feed the decoder with a scanned frame.
+  ///
+  /// Returns `true` if the message is fully recovered.
+  pub fn receive(&mut self, frame: JsValueOrString) -> Result<bool, Error> {
+    let frame = crate::conv::prefixed_str_js_to_bytes(FRAME_PREFIX, frame, false)
+      .map_err(|e| Error::FrameError(format!("{:?}", e)))?;
+    self
+      .decoder
+      .receive(&frame)
+      .map_err(|e| Error::FrameError(format!("{:?}", e)))
+  }
+
+  /// Fraction of the message recovered so far (`0.0` to `1.0`).
+  pub fn progress(&self) -> f64 {
+    match self.decoder.fragments_count() {
+      0 => 0.0,
+      count => self.decoder.decoded_fragments() as f64 / count as f64,
+    }
+  }
+
+  /// Return the recovered message as regular message parts.
+  ///
+  /// The parts can be passed to restoration functions. Returns `None` until
+  /// the message is fully recovered.
+  pub fn message_parts(&self) -> Result<Option<Vec<JsValueOrString>>, Error> {
+    match self.decoder.message() {
+      None => Ok(None),
+      Some(Ok(message)) => Ok(Some(msg_parts_to_js(message.split_and_encode(None)))),
+      Some(Err(e)) => Err(Error::MessageDecodingError(format!("{:?}", e))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_restore_message_from_frames() {
+    // given
+    let secured = crate::secure_restore::secure_message(
+      "Hello World!".into(),
+      Some(10),
+      crate::shamir::ChunksConfiguration {
+        required: 1,
+        spare: 0,
+      },
+      None,
+    )
+    .unwrap();
+    // the stream is long enough for the missing fragment to be mixed into some frames.
+    let frames = encode_message_frames(secured.encrypted_message, 8, 0, 200).unwrap();
+    let mut decoder = FrameDecoder::new();
+
+    // when
+    // skip the first frame, it's going to be recovered from the next ones
+    let mut complete = false;
+    for frame in frames.into_iter().skip(1) {
+      if decoder.receive(frame).unwrap() {
+        complete = true;
+        break;
+      }
+    }
+    let parts = decoder.message_parts().unwrap().unwrap();
+    let restored = crate::secure_restore::restore_message(parts, secured.chunks).unwrap();
+
+    // then
+    assert!(complete);
+    assert_eq!(decoder.progress(), 1.0);
+    assert_eq!(restored, "Hello World!");
+  }
+}
@@ -1,11 +1,21 @@
 //! Functions used to identify & decode icod-produced strings.
 
-use icod_crypto::{encryption::EncryptedMessagePart, shamir::Chunk};
+use icod_crypto::{
+  encryption::{EncryptedMessagePart, FrameHeader, MessageFragment},
+  mnemonic::Mnemonic,
+  shamir::{Chunk, EncryptedChunk},
+};
 #[cfg(not(test))]
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 
-use crate::{conv, encryption::MSG_PREFIX, shamir::CHUNK_PREFIX, JsValueOrString};
+use crate::{
+  conv,
+  encryption::{FRAGMENT_PREFIX, MSG_PREFIX},
+  fountain::FRAME_PREFIX,
+  shamir::{CHUNK_PREFIX, ENCRYPTED_CHUNK_PREFIX},
+  JsValueOrString,
+};
 
 /// Error occuring during identification.
 #[derive(Debug)]
@@ -38,6 +48,10 @@ pub enum Identification {
     parts_total: u32,
     /// Nonce, only part of `part_index = 0`.
     nonce: Option<String>,
+    /// Fingerprint of the owner who signed the part (the signature is verified).
+    signer: Option<String>,
+    /// Identifier of the message the part belongs to (only in tagged parts).
+    message_id: Option<String>,
     /// Data piece of that message part.
     data: String,
   },
@@ -47,7 +61,7 @@ pub enum Identification {
     name: String,
     /// Version byte.
     version: u8,
-    /// Hash of the key the chunk is for.
+    /// Hash of the key the chunk is for (or the set identifier of compact chunks).
     key_hash: String,
     /// Number of required chunks.
     required_chunks: u8,
@@ -55,9 +69,96 @@ pub enum Identification {
     spare_chunks: u8,
     /// Index of the chunk.
     chunk_index: u8,
+    /// Number of share points carried by the chunk.
+    weight: u8,
+    /// Whether the chunk data is protected with a passphrase.
+    passphrase_protected: bool,
+    /// Fingerprint of the owner who signed the chunk (the signature is verified).
+    signer: Option<String>,
+    /// Metadata stored inside the chunk.
+    metadata: Option<Metadata>,
+    /// Whether the name in front of the chunk differs from the name in it's metadata.
+    name_mismatch: bool,
     /// Data piece of the chunk.
     data: String,
   },
+  /// The string is a handwritten chunk or message part (see [crate::bech32])
+  /// or a chunk encoded as a list of words (see [crate::mnemonic]).
+  Transcribed {
+    /// Regular encoding of the (corrected) value.
+    item: String,
+    /// Positions of the corrected characters (not counting the prefix and spaces).
+    corrected: Vec<usize>,
+    /// Identification of the value.
+    identification: Box<Identification>,
+  },
+  /// The string is a part of a Uniform Resource (see [crate::ur]).
+  Ur {
+    /// Type of the resource (e.g. `icod-chunk`).
+    ur_type: String,
+    /// Sequence number of the part (only multi-part resources).
+    sequence: Option<u32>,
+    /// Number of fragments (only multi-part resources).
+    parts_total: Option<u32>,
+    /// Identification of the carried values (only single-part resources).
+    items: Vec<Identification>,
+  },
+  /// The string is a key encoded as a list of words (see [crate::mnemonic]).
+  Key {
+    /// Version byte.
+    version: u8,
+  },
+  /// The string is a fragment (or it's part) of the dispersed message
+  /// (see [crate::secure_restore::secure_dispersed_message]).
+  Fragment {
+    /// Index of the fragment.
+    index: u8,
+    /// Number of fragments required to restore the message.
+    required: u8,
+    /// Total number of fragments.
+    total: u8,
+    /// Index of the part of the fragment.
+    part_index: u16,
+    /// Number of parts of the fragment.
+    parts_total: u16,
+    /// Identifier of the dispersed message.
+    message_id: String,
+  },
+  /// The string is a fountain-coded frame of the message (see [crate::fountain]).
+  Frame {
+    /// Sequence number of the frame.
+    seq: u32,
+    /// Number of fragments the message is split into.
+    fragments_count: u32,
+    /// Identifier of the message.
+    message_id: String,
+  },
+  /// The string is a SSS chunk encrypted to it's recipient.
+  EncryptedChunk {
+    /// Name of the chunk.
+    name: Option<String>,
+    /// Fingerprint of the recipient's public key.
+    recipient: String,
+    /// Encrypted data of the chunk.
+    data: String,
+  },
+}
+
+/// Metadata stored inside the SSS chunk.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Metadata {
+  /// Display name of the chunk.
+  name: Option<String>,
+  /// Hint who the chunk is intended for.
+  recipient: Option<String>,
+  /// Contact to the owner.
+  owner_contact: Option<String>,
+  /// Short restoration instructions.
+  instructions: Option<String>,
+  /// Creation date (UNIX timestamp in seconds).
+  created_at: Option<u64>,
+  /// Whether the metadata is covered by a valid owner's signature.
+  authenticated: bool,
 }
 
 fn serialize(id: Identification) -> IdentificationOrJsValue {
@@ -74,8 +175,16 @@ pub type IdentificationOrJsValue = Identification;
 #[cfg(not(test))]
 pub type IdentificationOrJsValue = JsValue;
 
-fn identify_chunk(item: &str) -> Option<(Option<&str>, &str)> {
-  if let Some(chunk) = item.strip_prefix(CHUNK_PREFIX) {
+pub(crate) fn identify_chunk(item: &str) -> Option<(Option<&str>, &str)> {
+  identify_named(CHUNK_PREFIX, item)
+}
+
+pub(crate) fn identify_encrypted_chunk(item: &str) -> Option<(Option<&str>, &str)> {
+  identify_named(ENCRYPTED_CHUNK_PREFIX, item)
+}
+
+fn identify_named<'a>(prefix: &str, item: &'a str) -> Option<(Option<&'a str>, &'a str)> {
+  if let Some(chunk) = conv::strip_prefix(item, prefix) {
     Some(if let Some(index) = chunk.find(':') {
       let (name, rest) = chunk.split_at(index);
       (Some(name), &rest[1..])
@@ -91,34 +200,144 @@ fn identify_chunk(item: &str) -> Option<(Option<&str>, &str)> {
 /// of encoded value.
 #[cfg_attr(not(test), wasm_bindgen)]
 pub fn identify(item: String) -> Result<IdentificationOrJsValue, Error> {
+  if crate::bech32::is_transcription(&item) {
+    let (item, corrected) =
+      crate::bech32::decode_item(&item).map_err(|e| Error::Other(format!("{:?}", e)))?;
+    return Ok(serialize(Identification::Transcribed {
+      identification: Box::new(identify_decoded(item.clone())?),
+      item,
+      corrected,
+    }));
+  }
+
+  if icod_crypto::ur::is_ur(&item) {
+    return identify_ur(item).map(serialize);
+  }
+
+  if crate::mnemonic::is_mnemonic(&item) {
+    let decoded =
+      icod_crypto::mnemonic::decode_mnemonic(&item).map_err(|e| Error::Other(format!("{}", e)))?;
+    return Ok(serialize(match decoded {
+      Mnemonic::Chunk(chunk) => {
+        let item = conv::bytes_to_prefixed_str(CHUNK_PREFIX, &chunk.encode());
+        Identification::Transcribed {
+          identification: Box::new(identify_decoded(item.clone())?),
+          item,
+          corrected: vec![],
+        }
+      }
+      Mnemonic::Key(key) => Identification::Key {
+        version: key.version().to_byte(),
+      },
+    }));
+  }
+
+  identify_decoded(item).map(serialize)
+}
+
+fn identify_ur(item: String) -> Result<Identification, Error> {
+  let item = item.to_ascii_lowercase();
+  let mut decoder = icod_crypto::ur::UrDecoder::new();
+  decoder
+    .receive(&item)
+    .map_err(|e| Error::Other(format!("{}", e)))?;
+  let items = crate::ur::decoded_items(&decoder)
+    .map_err(|e| Error::Other(format!("{:?}", e)))?
+    .unwrap_or_default();
+  let sequence = crate::ur::sequence(&item);
+  Ok(Identification::Ur {
+    ur_type: item[3..].split('/').next().unwrap_or_default().into(),
+    sequence: sequence.map(|(seq, _)| seq),
+    parts_total: sequence.map(|(_, count)| count),
+    items: items
+      .into_iter()
+      .map(identify_decoded)
+      .collect::<Result<_, _>>()?,
+  })
+}
+
+fn identify_decoded(item: String) -> Result<Identification, Error> {
   if let Some((name, chunk)) = identify_chunk(&item) {
     let bytes = crate::conv::decode(&chunk).map_err(|_| Error::DecodingError)?;
     let chunk = Chunk::decode(&bytes).map_err(|e| Error::Other(format!("{}", e)))?;
     let total_chunks = chunk.configuration().total();
+    let signer = chunk.signer().map_err(|e| Error::Other(format!("{}", e)))?;
+    let metadata = chunk.metadata().map(|metadata| Metadata {
+      name: metadata.name.clone(),
+      recipient: metadata.recipient.clone(),
+      owner_contact: metadata.owner_contact.clone(),
+      instructions: metadata.instructions.clone(),
+      // JS numbers can't represent every `u64`.
+      created_at: metadata.created_at.filter(|t| *t < 1 << 53),
+      authenticated: signer.is_some(),
+    });
+    let authenticated_name = metadata.as_ref().and_then(|m| m.name.as_deref());
+    let name_mismatch = matches!((name, authenticated_name), (Some(a), Some(b)) if a != b);
 
-    return Ok(serialize(Identification::Chunk {
+    return Ok(Identification::Chunk {
       name: name
+        .or(authenticated_name)
         .map(Into::into)
         .unwrap_or_else(|| format!("Restoration Piece {}/{}", chunk.index() + 1, total_chunks)),
       version: chunk.version(),
-      key_hash: crate::conv::encode(&chunk.key_hash().to_bytes()),
+      key_hash: crate::conv::encode(chunk.key_id().as_bytes()),
       required_chunks: chunk.configuration().required() as u8,
       spare_chunks: chunk.configuration().spare() as u8,
       chunk_index: chunk.index(),
+      weight: chunk.weight(),
+      passphrase_protected: chunk.is_protected(),
+      signer: signer.map(|s| s.fingerprint()),
+      metadata,
+      name_mismatch,
       data: crate::conv::encode(&chunk.data()),
-    }));
+    });
+  }
+
+  if let Some((name, chunk)) = identify_encrypted_chunk(&item) {
+    let bytes = crate::conv::decode(chunk).map_err(|_| Error::DecodingError)?;
+    let chunk = EncryptedChunk::decode(&bytes).map_err(|e| Error::Other(format!("{}", e)))?;
+    return Ok(Identification::EncryptedChunk {
+      name: name.map(Into::into),
+      recipient: chunk.recipient().fingerprint(),
+      data: crate::conv::encode(&bytes),
+    });
   }
 
-  if let Some(msg) = item.strip_prefix(MSG_PREFIX) {
+  if let Some(msg) = conv::strip_prefix(&item, MSG_PREFIX) {
     let bytes = crate::conv::decode(&msg).map_err(|_| Error::DecodingError)?;
     let part = EncryptedMessagePart::decode(&bytes).map_err(|e| Error::Other(format!("{}", e)))?;
-    return Ok(serialize(Identification::MessagePart {
+    return Ok(Identification::MessagePart {
       version: part.version(),
       part_index: part.part_index(),
       parts_total: part.parts_total(),
       nonce: part.nonce().map(|n| crate::conv::encode(n)),
+      signer: part.signer().map(|s| s.fingerprint()),
+      message_id: part.message_id().map(|id| crate::conv::encode(id)),
       data: crate::conv::encode(part.data()),
-    }));
+    });
+  }
+
+  if let Some(fragment) = conv::strip_prefix(&item, FRAGMENT_PREFIX) {
+    let bytes = crate::conv::decode(fragment).map_err(|_| Error::DecodingError)?;
+    let fragment = MessageFragment::decode(&bytes).map_err(|e| Error::Other(format!("{}", e)))?;
+    return Ok(Identification::Fragment {
+      index: fragment.index(),
+      required: fragment.required() as u8,
+      total: fragment.total() as u8,
+      part_index: fragment.part_index(),
+      parts_total: fragment.parts_total(),
+      message_id: crate::conv::encode(fragment.message_id()),
+    });
+  }
+
+  if let Some(frame) = conv::strip_prefix(&item, FRAME_PREFIX) {
+    let bytes = crate::conv::decode(frame).map_err(|_| Error::DecodingError)?;
+    let (header, _) = FrameHeader::decode(&bytes).map_err(|e| Error::Other(format!("{}", e)))?;
+    return Ok(Identification::Frame {
+      seq: header.seq(),
+      fragments_count: header.fragments_count(),
+      message_id: crate::conv::encode(header.message_id()),
+    });
   }
 
   return Err(Error::MissingPrefix);
@@ -211,6 +430,11 @@ mod tests {
       required_chunks: 2,
       spare_chunks: 3,
       chunk_index: 1,
+      weight: 1,
+      passphrase_protected: false,
+      signer: None,
+      metadata: None,
+      name_mismatch: false,
       data,
     });
   }
@@ -235,11 +459,118 @@ mod tests {
         part_index: 0,
         parts_total: 1,
         nonce: Some("nf47ekohh9ci20bdnrjg".into()),
+        signer: None,
+        message_id: None,
         data: "0r7rf3m80rr69ic6ktsbkr3k4bcdo86618411joljt9kbvp7f4qst8kpkc3vjl9s1tfdph0".into(),
       }
     );
   }
 
+  #[test]
+  fn should_report_signer_of_chunks_and_parts() {
+    // given
+    let identity_key = crate::identity::generate_identity_key();
+    let fingerprint = crate::identity::identity_fingerprint(identity_key.clone()).unwrap();
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+    let secured =
+      crate::secure_restore::secure_signed_message("Hi!".into(), None, conf, identity_key, None)
+        .unwrap();
+
+    // when
+    let chunk = identify(secured.chunks[0].clone()).unwrap();
+    let part = identify(secured.encrypted_message[0].clone()).unwrap();
+
+    // then
+    let signer = |id| match id {
+      Identification::Chunk { signer, .. } => signer,
+      Identification::MessagePart { signer, .. } => signer,
+      _ => None,
+    };
+    assert_eq!(signer(chunk), Some(fingerprint.clone()));
+    assert_eq!(signer(part), Some(fingerprint));
+  }
+
+  #[test]
+  fn should_identify_encrypted_chunk() {
+    // given
+    let key = crate::recipient::generate_recipient_key();
+    let recipient = crate::recipient::recipient_public_key(key.clone()).unwrap();
+    let secured = crate::secure_restore::secure_message_for_recipients(
+      "Hi!".into(),
+      None,
+      1,
+      vec![recipient],
+      None,
+    )
+    .unwrap();
+    let fingerprint = icod_crypto::recipient::RecipientKey::from_bytes(&key.try_into().unwrap())
+      .public_key()
+      .fingerprint();
+
+    // when
+    let result = identify(secured.chunks[0].replace("icod-echunk:", "icod-echunk:mom:")).unwrap();
+
+    // then
+    assert!(matches!(
+      result,
+      Identification::EncryptedChunk { name: Some(ref name), ref recipient, .. }
+        if name == "mom" && recipient == &fingerprint
+    ));
+  }
+
+  #[test]
+  fn should_report_message_id_of_tagged_parts() {
+    // given
+    let message =
+      icod_crypto::encryption::EncryptedMessage::new(b"Test Data".to_vec(), *b"test nonce x")
+        .unwrap();
+    let message_id = crate::conv::encode(&message.message_id());
+    let parts = message.split_and_encode_tagged(Some(16), None);
+
+    // when
+    let result = identify(crate::conv::bytes_to_prefixed_str(MSG_PREFIX, &parts[1])).unwrap();
+
+    // then
+    assert!(matches!(
+      result,
+      Identification::MessagePart { part_index: 1, message_id: Some(ref id), .. }
+        if id == &message_id
+    ));
+  }
+
+  #[test]
+  fn should_identify_fragments_and_frames() {
+    // given
+    let conf = || crate::shamir::ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+    let dispersed =
+      crate::secure_restore::secure_dispersed_message("Only together".into(), Some(8), conf())
+        .unwrap();
+    let secured =
+      crate::secure_restore::secure_message("Test Data".into(), None, conf(), None).unwrap();
+    let frames =
+      crate::fountain::encode_message_frames(secured.encrypted_message, 8, 3, 1).unwrap();
+
+    // when
+    let fragment = identify(dispersed.encrypted_message[1].clone()).unwrap();
+    let frame = identify(frames[0].clone()).unwrap();
+
+    // then
+    assert!(matches!(
+      fragment,
+      Identification::Fragment { index: 0, required: 2, total: 3, part_index: 1, parts_total, .. }
+        if parts_total > 1
+    ));
+    assert!(
+      matches!(frame, Identification::Frame { seq: 3, fragments_count, .. } if fragments_count > 1)
+    );
+  }
+
   #[test]
   fn should_support_named_chunks() {
     // given
@@ -258,11 +589,129 @@ mod tests {
         required_chunks: 1,
         spare_chunks: 1,
         chunk_index: 1,
+        weight: 1,
+        passphrase_protected: false,
+        signer: None,
+        metadata: None,
+        name_mismatch: false,
         data: "09km6rr4dc002081040g2081040g2081040g2081040g2081040g2081040g208".into(),
       }
     );
   }
 
+  #[test]
+  fn should_report_authenticated_metadata() {
+    // given
+    let identity_key = crate::identity::generate_identity_key();
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+    let chunks = crate::shamir::split_into_chunks([1u8; KEY_SIZE].to_vec(), conf).unwrap();
+    let chunk = crate::shamir::set_chunk_metadata(
+      chunks[0].clone(),
+      Some("For Alice".into()),
+      Some("Alice".into()),
+      Some("bob@example.com".into()),
+      None,
+      Some(1_700_000_000.0),
+      identity_key,
+    )
+    .unwrap();
+    let renamed = alter_chunks_name(chunk.clone(), "For Eve".into()).unwrap();
+
+    // when
+    let result = identify(chunk).unwrap();
+    let renamed = identify(renamed).unwrap();
+
+    // then
+    let expected = Metadata {
+      name: Some("For Alice".into()),
+      recipient: Some("Alice".into()),
+      owner_contact: Some("bob@example.com".into()),
+      instructions: None,
+      created_at: Some(1_700_000_000),
+      authenticated: true,
+    };
+    assert!(matches!(
+      result,
+      Identification::Chunk { ref name, metadata: Some(ref m), name_mismatch: false, .. }
+        if name == "For Alice" && m == &expected
+    ));
+    assert!(matches!(
+      renamed,
+      Identification::Chunk { ref name, metadata: Some(ref m), name_mismatch: true, .. }
+        if name == "For Eve" && m == &expected
+    ));
+  }
+
+  #[test]
+  fn should_identify_and_correct_transcription() {
+    // given
+    let chunk = "icod-chunk:d5hmup3301bt435o7vhlrc2poim1l1dcldpq3b010f8jg34bhm8co8h1rb89iml9htpfhcmtogogifj9ou8k4mve23a63s038ht0uvuafefqkr8l040g20j9cdnm8qo0040g2081040g2081040g2081040g2081040g2081040g2081040g";
+    let words = crate::bech32::encode_for_transcription(chunk.into()).unwrap();
+    let mut typo: Vec<char> = words.chars().collect();
+    typo[10] = if typo[10] == 'x' { 'y' } else { 'x' };
+
+    // when
+    let result = identify(typo.into_iter().collect()).unwrap();
+
+    // then
+    assert!(matches!(
+      result,
+      Identification::Transcribed { ref item, ref corrected, ref identification }
+        if item.starts_with("icod-chunk:")
+          && corrected == &[3]
+          && matches!(**identification, Identification::Chunk { chunk_index: 1, version: 2, .. })
+    ));
+  }
+
+  #[test]
+  fn should_identify_mnemonic() {
+    // given
+    let chunk = "icod-chunk:d5hmup3301bt435o7vhlrc2poim1l1dcldpq3b010f8jg34bhm8co8h1rb89iml9htpfhcmtogogifj9ou8k4mve23a63s038ht0uvuafefqkr8l040g20j9cdnm8qo0040g2081040g2081040g2081040g2081040g2081040g2081040g";
+    let words = crate::mnemonic::chunk_to_mnemonic(chunk.into()).unwrap();
+    let key = crate::shamir::recover_key(vec![chunk.into()]).unwrap();
+    let key_words = crate::mnemonic::key_to_mnemonic(key).unwrap();
+
+    // when
+    let chunk = identify(words).unwrap();
+    let key = identify(key_words).unwrap();
+
+    // then
+    assert!(matches!(
+      chunk,
+      Identification::Transcribed { ref identification, .. }
+        if matches!(**identification, Identification::Chunk { chunk_index: 1, version: 2, .. })
+    ));
+    assert_eq!(key, Identification::Key { version: 0 });
+  }
+
+  #[test]
+  fn should_identify_ur() {
+    // given
+    let chunk = "icod-chunk:d5hmup3301bt435o7vhlrc2poim1l1dcldpq3b010f8jg34bhm8co8h1rb89iml9htpfhcmtogogifj9ou8k4mve23a63s038ht0uvuafefqkr8l040g20j9cdnm8qo0040g2081040g2081040g2081040g2081040g2081040g2081040g";
+    let single = crate::ur::encode_ur(vec![chunk.into()], 1000, 1, 1).unwrap();
+    let multi = crate::ur::encode_ur(vec![chunk.into()], 20, 2, 1).unwrap();
+
+    // when
+    let single = identify(single[0].to_ascii_uppercase()).unwrap();
+    let multi = identify(multi[0].clone()).unwrap();
+
+    // then
+    assert!(matches!(
+      single,
+      Identification::Ur { ref ur_type, sequence: None, parts_total: None, ref items }
+        if ur_type == "icod-chunk"
+          && matches!(items[..], [Identification::Chunk { chunk_index: 1, version: 0, .. }])
+    ));
+    assert!(matches!(
+      multi,
+      Identification::Ur { ref ur_type, sequence: Some(2), parts_total: Some(_), ref items }
+        if ur_type == "icod-chunk" && items.is_empty()
+    ));
+  }
+
   #[test]
   fn should_alter_chunks_name() {
     // given
@@ -0,0 +1,60 @@
+//! Owner identity key functions exposed to JS.
+
+use icod_crypto::identity::{IdentityKey, IDENTITY_KEY_SIZE};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+/// An error occuring when the identity key is malformed.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// Provided key has invalid byte length.
+  InvalidKeySize,
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+pub(crate) fn parse_identity_key(key: Vec<u8>) -> Result<IdentityKey, Error> {
+  let key: [u8; IDENTITY_KEY_SIZE] = key.try_into().map_err(|_| Error::InvalidKeySize)?;
+  Ok(IdentityKey::from_bytes(&key))
+}
+
+/// Generate a new, random identity key of the owner.
+///
+/// The raw secret key (32 bytes) should be stored securely, since it's used
+/// to sign all of the chunks and message parts.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn generate_identity_key() -> Vec<u8> {
+  IdentityKey::generate().to_bytes().to_vec()
+}
+
+/// Return the fingerprint of given identity key.
+///
+/// The fingerprint is reported by [crate::identify::identify] for signed
+/// chunks and message parts.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn identity_fingerprint(identity_key: Vec<u8>) -> Result<String, Error> {
+  Ok(parse_identity_key(identity_key)?.public_key().fingerprint())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_compute_fingerprint() {
+    let key = generate_identity_key();
+
+    let fingerprint = identity_fingerprint(key.clone()).unwrap();
+
+    assert_eq!(fingerprint.len(), 16);
+    assert_eq!(identity_fingerprint(key), Ok(fingerprint));
+    assert_eq!(identity_fingerprint(vec![1, 2]), Err(Error::InvalidKeySize));
+  }
+}
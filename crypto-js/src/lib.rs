@@ -2,16 +2,37 @@
 
 #![warn(missing_docs)]
 
+pub mod bech32;
+pub mod container;
 pub mod encryption;
+pub mod fountain;
 pub mod identify;
+pub mod identity;
+pub mod mnemonic;
+pub mod qr;
+pub mod recipient;
 pub mod secure_restore;
 pub mod shamir;
+pub mod slip39;
+pub mod ur;
 
 pub(crate) mod conv;
 
+pub use bech32::{decode_transcription, encode_for_transcription};
+pub use fountain::{encode_message_frames, FrameDecoder};
 use icod_crypto::encryption::KEY_SIZE;
 pub use identify::{alter_chunks_name, identify};
-pub use secure_restore::{restore_message, secure_message};
+pub use identity::{generate_identity_key, identity_fingerprint};
+pub use mnemonic::{chunk_to_mnemonic, key_to_mnemonic, mnemonic_to_chunk, mnemonic_to_key};
+pub use qr::{qr_message_split, render_qr_png, render_qr_svg, to_qr_alphanumeric};
+pub use recipient::{decrypt_chunk, generate_recipient_key, recipient_public_key};
+pub use secure_restore::{
+  restore_bytes, restore_dispersed_message, restore_message, restore_signed_message, secure_bytes,
+  secure_dispersed_message, secure_message, secure_message_for_recipients, secure_signed_bytes,
+  secure_signed_message,
+};
+pub use slip39::{chunks_to_slip39, key_to_slip39, slip39_to_chunks, slip39_to_key};
+pub use ur::{encode_ur, UrDecoder};
 
 pub(crate) fn parse_key(key: Vec<u8>) -> Result<[u8; KEY_SIZE], ()> {
   let mut out = [0u8; KEY_SIZE];
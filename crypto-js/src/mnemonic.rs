@@ -0,0 +1,132 @@
+//! Mnemonic word encoding of chunks and keys exposed to JS.
+
+use icod_crypto::encryption::MessageEncryptionKey;
+use icod_crypto::mnemonic::{self, Mnemonic};
+use icod_crypto::shamir::Chunk;
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::{shamir::CHUNK_PREFIX, JsValueOrString};
+
+/// Minimal number of words in a mnemonic.
+const MIN_WORDS: usize = 12;
+
+/// An error occuring when encoding or decoding the mnemonic.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The value is not a chunk or a key.
+  DecodingError(String),
+  /// The words could not be decoded.
+  MnemonicError(mnemonic::MnemonicError),
+  /// The words encode a key instead of a chunk or vice versa.
+  UnexpectedValue,
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<mnemonic::MnemonicError> for Error {
+  fn from(value: mnemonic::MnemonicError) -> Self {
+    Self::MnemonicError(value)
+  }
+}
+
+/// Encode given chunk as a list of words from the BIP39 English wordlist.
+///
+/// Note the name of the chunk is not preserved (see [crate::shamir::set_chunk_metadata]).
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn chunk_to_mnemonic(chunk: String) -> Result<String, Error> {
+  let (_name, chunk) = crate::identify::identify_chunk(&chunk)
+    .ok_or_else(|| Error::DecodingError("Not a chunk".into()))?;
+  let bytes = crate::conv::decode(chunk).map_err(|e| Error::DecodingError(format!("{:?}", e)))?;
+  let chunk = Chunk::decode(&bytes).map_err(|e| Error::DecodingError(format!("{:?}", e)))?;
+  Ok(mnemonic::chunk_to_mnemonic(&chunk))
+}
+
+/// Decode the chunk from the list of words produced by [chunk_to_mnemonic].
+///
+/// The words can be abbreviated to their first 4 letters.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn mnemonic_to_chunk(words: String) -> Result<JsValueOrString, Error> {
+  match mnemonic::decode_mnemonic(&words)? {
+    Mnemonic::Chunk(chunk) => Ok(crate::conv::bytes_to_prefixed_str_js(
+      CHUNK_PREFIX,
+      &chunk.encode(),
+    )),
+    Mnemonic::Key(_) => Err(Error::UnexpectedValue),
+  }
+}
+
+/// Encode given key as a list of words from the BIP39 English wordlist.
+///
+/// The `key` is expected to be byte-encoded, i.e. as returned by
+/// [crate::shamir::recover_key]. Useful for `1-of-1` setups, where the key
+/// does not have to be split.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn key_to_mnemonic(key: Vec<u8>) -> Result<String, Error> {
+  let key =
+    MessageEncryptionKey::decode(&key).map_err(|e| Error::DecodingError(format!("{:?}", e)))?;
+  Ok(mnemonic::key_to_mnemonic(key))
+}
+
+/// Decode the byte-encoded key from the list of words produced by [key_to_mnemonic].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn mnemonic_to_key(words: String) -> Result<Vec<u8>, Error> {
+  match mnemonic::decode_mnemonic(&words)? {
+    Mnemonic::Key(key) => Ok(key.encode().into()),
+    Mnemonic::Chunk(_) => Err(Error::UnexpectedValue),
+  }
+}
+
+/// Check if given string looks like a list of words.
+pub(crate) fn is_mnemonic(item: &str) -> bool {
+  let words: Vec<&str> = item
+    .split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|w| !w.is_empty())
+    .collect();
+  let alphabetic = |w: &str| w.chars().all(|c| c.is_alphabetic());
+  let numbering = |w: &str| {
+    w.trim_end_matches(['.', ')'])
+      .chars()
+      .all(|c| c.is_ascii_digit())
+  };
+  words.iter().filter(|w| alphabetic(w)).count() >= MIN_WORDS
+    && words.iter().all(|w| alphabetic(w) || numbering(w))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use icod_crypto::encryption::KEY_SIZE;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_convert_chunks_and_keys_to_mnemonic() {
+    // given
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 2,
+      spare: 0,
+    };
+    let chunks = crate::shamir::split_into_chunks([1u8; KEY_SIZE].to_vec(), conf).unwrap();
+    let key = crate::shamir::recover_key(chunks.clone()).unwrap();
+
+    // when
+    let words = chunk_to_mnemonic(chunks[0].clone()).unwrap();
+    let chunk = mnemonic_to_chunk(words.clone()).unwrap();
+    let recovered = crate::shamir::recover_key(vec![chunk, chunks[1].clone()]).unwrap();
+    let key_words = key_to_mnemonic(key.clone()).unwrap();
+
+    // then
+    assert!(is_mnemonic(&words));
+    assert!(!is_mnemonic(&chunks[0]));
+    assert_eq!(recovered, key);
+    assert_eq!(key_words.split(' ').count(), 25);
+    assert_eq!(mnemonic_to_key(key_words), Ok(key));
+    assert_eq!(mnemonic_to_key(words), Err(Error::UnexpectedValue));
+  }
+}
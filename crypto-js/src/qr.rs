@@ -0,0 +1,238 @@
+//! QR code related encoding and rendering exposed to JS.
+
+use icod_crypto::encryption::{
+  BYTES_PER_ID_PART, MESSAGE_ID_SIZE, PART_CHECKSUM_SIZE, XNONCE_SIZE,
+};
+use icod_crypto::identity::SIGNED_BY_SIZE;
+use icod_crypto::qr::{EcLevel, QrError};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::{
+  encryption::{FRAGMENT_PREFIX, MSG_PREFIX},
+  fountain::FRAME_PREFIX,
+  recipient::RECIPIENT_PREFIX,
+  shamir::{CHUNK_PREFIX, ENCRYPTED_CHUNK_PREFIX},
+  JsValueOrString,
+};
+
+/// Prefixes of all values which can be converted to the QR alphanumeric profile.
+const PREFIXES: [&str; 6] = [
+  CHUNK_PREFIX,
+  ENCRYPTED_CHUNK_PREFIX,
+  MSG_PREFIX,
+  FRAGMENT_PREFIX,
+  RECIPIENT_PREFIX,
+  FRAME_PREFIX,
+];
+
+/// An error occuring when converting the value to the QR alphanumeric profile
+/// or rendering the QR code.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The string is missing a known prefix.
+  MissingPrefix,
+  /// The name of the chunk contains characters outside of the QR alphanumeric set.
+  UnsupportedName,
+  /// The error correction level is not one of `L`, `M`, `Q` or `H`.
+  InvalidErrorCorrection,
+  /// The QR code version is too small to hold a message part.
+  VersionTooSmall,
+  /// The QR code could not be rendered.
+  QrError(QrError),
+}
+
+impl From<QrError> for Error {
+  fn from(value: QrError) -> Self {
+    Self::QrError(value)
+  }
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+/// Convert the value produced by other functions (e.g. `icod-chunk:...`) to the
+/// QR alphanumeric profile.
+///
+/// The lowercase encoding forces QR byte mode, while the uppercase one (`ICOD-CHUNK:...`)
+/// uses only the QR alphanumeric character set, so the QR codes can hold roughly 40% more.
+/// The converted values are accepted everywhere the lowercase ones are.
+///
+/// Note the name of the chunk is uppercased as well, hence it may contain only letters,
+/// digits, spaces and `$%*+-./` characters.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn to_qr_alphanumeric(item: String) -> Result<JsValueOrString, Error> {
+  if !PREFIXES.iter().any(|p| item.starts_with(p)) {
+    return Err(Error::MissingPrefix);
+  }
+  let converted = item.to_ascii_uppercase();
+  if !is_qr_alphanumeric(&converted) {
+    return Err(Error::UnsupportedName);
+  }
+  Ok(crate::conv::js_value_or_string(converted))
+}
+
+/// Compute the `split` parameter of [crate::secure_restore::secure_message] (and
+/// other functions encrypting the message) so that every message part fits
+/// exactly into the QR code of given `version` (`1` to `40`).
+///
+/// - `ec_level` is the error correction level (`L`, `M`, `Q` or `H`) of the QR codes.
+/// - `alphanumeric` should be set if the parts are converted with [to_qr_alphanumeric].
+/// - `signed` should be set if the parts are signed with the owner's identity.
+/// - `tagged` should be set if the parts are tagged with the message identifier.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn qr_message_split(
+  version: u8,
+  ec_level: char,
+  alphanumeric: bool,
+  signed: bool,
+  tagged: Option<bool>,
+) -> Result<usize, Error> {
+  let ec_level = EcLevel::from_letter(ec_level).ok_or(Error::InvalidErrorCorrection)?;
+  let capacity = icod_crypto::qr::capacity(version, ec_level, alphanumeric)?;
+  // every 5 bytes are encoded as 8 characters.
+  let encoded_len = capacity.saturating_sub(MSG_PREFIX.len()) * 5 / 8;
+  // version byte, part id and total parts.
+  let mut overhead = 1 + 2 * BYTES_PER_ID_PART;
+  if signed {
+    overhead += SIGNED_BY_SIZE;
+  }
+  if tagged.unwrap_or_default() {
+    overhead += MESSAGE_ID_SIZE + PART_CHECKSUM_SIZE;
+  }
+  match encoded_len.saturating_sub(overhead) {
+    // the nonce is never split, so the first part would not fit.
+    split if split < XNONCE_SIZE => Err(Error::VersionTooSmall),
+    split => Ok(split),
+  }
+}
+
+/// Render given value (e.g. a chunk or a message part) as a QR code in SVG format.
+///
+/// The smallest QR code version fitting the value is used, with given error
+/// correction level (`L`, `M`, `Q` or `H`) and `module_size` units per module.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn render_qr_svg(item: String, ec_level: char, module_size: u32) -> Result<String, Error> {
+  let ec_level = EcLevel::from_letter(ec_level).ok_or(Error::InvalidErrorCorrection)?;
+  Ok(icod_crypto::qr::render_svg(&item, ec_level, module_size)?)
+}
+
+/// Render given value as a QR code in PNG format.
+///
+/// See [render_qr_svg] for the description of the parameters, `module_size`
+/// is given in pixels.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn render_qr_png(item: String, ec_level: char, module_size: u32) -> Result<Vec<u8>, Error> {
+  let ec_level = EcLevel::from_letter(ec_level).ok_or(Error::InvalidErrorCorrection)?;
+  Ok(icod_crypto::qr::render_png(&item, ec_level, module_size)?)
+}
+
+/// Check if given string can be encoded using QR alphanumeric mode.
+pub(crate) fn is_qr_alphanumeric(item: &str) -> bool {
+  item
+    .chars()
+    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || " $%*+-./:".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secure_restore::{restore_message, secure_message};
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_restore_message_from_alphanumeric_values() {
+    // given
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 2,
+      spare: 0,
+    };
+    let result = secure_message("Hello World!".into(), None, conf, None).unwrap();
+    let to_qr = |item: &String| to_qr_alphanumeric(item.clone()).unwrap();
+    let message: Vec<_> = result.encrypted_message.iter().map(to_qr).collect();
+    let chunks = vec![
+      to_qr(&result.chunks[0].replace(CHUNK_PREFIX, "icod-chunk:mom:")),
+      result.chunks[1].clone(),
+    ];
+
+    // when
+    let identified = crate::identify::identify(chunks[0].clone()).unwrap();
+    let recovered = crate::shamir::recover_key(chunks.clone());
+    let restored = restore_message(message.clone(), chunks.clone()).unwrap();
+
+    // then
+    assert!(message[0].starts_with("ICOD-MSG:"));
+    assert!(chunks[0].starts_with("ICOD-CHUNK:MOM:"));
+    assert!(message
+      .iter()
+      .chain(&chunks[..1])
+      .all(|i| is_qr_alphanumeric(i)));
+    assert!(
+      matches!(identified, crate::identify::Identification::Chunk { name, .. } if name == "MOM")
+    );
+    assert!(recovered.is_ok());
+    assert_eq!(restored, "Hello World!");
+    assert_eq!(
+      to_qr_alphanumeric("icod-chunk:mamá:abc".into()),
+      Err(Error::UnsupportedName)
+    );
+    assert_eq!(
+      to_qr_alphanumeric("hello".into()),
+      Err(Error::MissingPrefix)
+    );
+  }
+
+  #[test]
+  fn should_split_message_to_fit_qr_version() {
+    // given
+    let conf = || crate::shamir::ChunksConfiguration::new(2, 1);
+    let message = "Hello World! ".repeat(100);
+    let split = qr_message_split(10, 'm', false, false, None).unwrap();
+    let alphanumeric_split = qr_message_split(10, 'M', true, false, None).unwrap();
+    let tagged_split = qr_message_split(10, 'M', false, false, Some(true)).unwrap();
+
+    // when
+    let result = secure_message(message.clone(), Some(split), conf(), None).unwrap();
+    let alphanumeric =
+      secure_message(message.clone(), Some(alphanumeric_split), conf(), None).unwrap();
+    let tagged = secure_message(message.clone(), Some(tagged_split), conf(), Some(true)).unwrap();
+    let capacity = icod_crypto::qr::capacity(10, EcLevel::Medium, false).unwrap();
+    let alphanumeric_capacity = icod_crypto::qr::capacity(10, EcLevel::Medium, true).unwrap();
+    let svg = render_qr_svg(result.encrypted_message[0].clone(), 'M', 4).unwrap();
+    let png = render_qr_png(result.chunks[0].clone(), 'H', 2).unwrap();
+
+    // then
+    assert!(result.encrypted_message.len() > 1);
+    assert!(result.encrypted_message.iter().all(|p| p.len() <= capacity));
+    assert!(result
+      .encrypted_message
+      .iter()
+      .any(|p| p.len() + 8 > capacity));
+    assert!(alphanumeric
+      .encrypted_message
+      .iter()
+      .all(|p| p.len() <= alphanumeric_capacity));
+    assert!(alphanumeric_split > split);
+    assert!(tagged.encrypted_message.iter().all(|p| p.len() <= capacity));
+    assert!(tagged
+      .encrypted_message
+      .iter()
+      .any(|p| p.len() + 8 > capacity));
+    assert!(svg.contains("<svg"));
+    assert_eq!(&png[1..4], b"PNG");
+    assert!(qr_message_split(41, 'M', false, false, None).is_err());
+    assert_eq!(
+      qr_message_split(1, 'M', false, false, None),
+      Err(Error::VersionTooSmall)
+    );
+    assert_eq!(
+      qr_message_split(10, 'X', false, false, None),
+      Err(Error::InvalidErrorCorrection)
+    );
+  }
+}
@@ -0,0 +1,129 @@
+//! Recipient key functions exposed to JS.
+
+use icod_crypto::recipient::{RecipientKey, RecipientPublicKey, RECIPIENT_KEY_SIZE};
+use icod_crypto::shamir::EncryptedChunk;
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::shamir::{CHUNK_PREFIX, ENCRYPTED_CHUNK_PREFIX};
+use crate::JsValueOrString;
+
+/// Human readable prefix of recipient's public key.
+pub const RECIPIENT_PREFIX: &str = "icod-recipient:";
+
+/// An error occuring when handling recipient keys or encrypted chunks.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// Provided secret key has invalid byte length.
+  InvalidKeySize,
+  /// Provided public key is malformed.
+  InvalidRecipient,
+  /// The value is not an encrypted chunk.
+  NotAnEncryptedChunk,
+  /// Cannot decode the encrypted chunk.
+  ChunkDecodingError(String),
+  /// The chunk could not be decrypted with given key.
+  DecryptionError(String),
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+fn parse_recipient_key(key: Vec<u8>) -> Result<RecipientKey, Error> {
+  let key: [u8; RECIPIENT_KEY_SIZE] = key.try_into().map_err(|_| Error::InvalidKeySize)?;
+  Ok(RecipientKey::from_bytes(&key))
+}
+
+pub(crate) fn parse_recipient(recipient: JsValueOrString) -> Result<RecipientPublicKey, Error> {
+  let bytes = crate::conv::prefixed_str_js_to_bytes(RECIPIENT_PREFIX, recipient, false)
+    .map_err(|_| Error::InvalidRecipient)?;
+  RecipientPublicKey::from_bytes(&bytes).map_err(|_| Error::InvalidRecipient)
+}
+
+/// Generate a new, random recipient key.
+///
+/// The raw secret key (32 bytes) should never leave the recipient's device,
+/// while the public key (see [recipient_public_key]) is registered with the owner.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn generate_recipient_key() -> Vec<u8> {
+  RecipientKey::generate().to_bytes().to_vec()
+}
+
+/// Return the public key of given recipient key, prefixed with [RECIPIENT_PREFIX].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn recipient_public_key(recipient_key: Vec<u8>) -> Result<JsValueOrString, Error> {
+  let public_key = parse_recipient_key(recipient_key)?.public_key();
+  Ok(crate::conv::bytes_to_prefixed_str_js(
+    RECIPIENT_PREFIX,
+    &public_key.to_bytes(),
+  ))
+}
+
+/// Decrypt given encrypted chunk with the recipient's secret key.
+///
+/// The returned chunk can be used for restoration. The name of the chunk (if any) is preserved.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn decrypt_chunk(chunk: String, recipient_key: Vec<u8>) -> Result<JsValueOrString, Error> {
+  let key = parse_recipient_key(recipient_key)?;
+  let (name, chunk) =
+    crate::identify::identify_encrypted_chunk(&chunk).ok_or(Error::NotAnEncryptedChunk)?;
+  let bytes =
+    crate::conv::decode(chunk).map_err(|e| Error::ChunkDecodingError(format!("{:?}", e)))?;
+  let encrypted =
+    EncryptedChunk::decode(&bytes).map_err(|e| Error::ChunkDecodingError(format!("{:?}", e)))?;
+  let chunk = encrypted
+    .decrypt(&key)
+    .map_err(|e| Error::DecryptionError(format!("{:?}", e)))?;
+  let encoded = crate::conv::encode(&chunk.encode());
+
+  Ok(crate::conv::js_value_or_string(match name {
+    Some(name) => format!("{}{}:{}", CHUNK_PREFIX, name, encoded),
+    None => format!("{}{}", CHUNK_PREFIX, encoded),
+  }))
+}
+
+pub(crate) fn encrypted_chunks_to_js(chunks: Vec<EncryptedChunk>) -> Vec<String> {
+  chunks
+    .into_iter()
+    .map(|chunk| crate::conv::bytes_to_prefixed_str(ENCRYPTED_CHUNK_PREFIX, &chunk.encode()))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_decrypt_chunk() {
+    // given
+    let key = generate_recipient_key();
+    let other = generate_recipient_key();
+    let recipient = recipient_public_key(key.clone()).unwrap();
+    let chunk = icod_crypto::shamir::split_into_chunks(
+      icod_crypto::encryption::MessageEncryptionKey::new([1u8; 32]),
+      icod_crypto::shamir::ChunksConfiguration::new(1, 0).unwrap(),
+    )
+    .remove(0);
+    let encrypted = chunk.encrypt_to(&parse_recipient(recipient.clone()).unwrap());
+    let encoded = encrypted_chunks_to_js(vec![encrypted]).remove(0);
+    let named = encoded.replace(ENCRYPTED_CHUNK_PREFIX, "icod-echunk:mom:");
+
+    // when
+    let decrypted = decrypt_chunk(named.clone(), key).unwrap();
+    let err = decrypt_chunk(named, other).unwrap_err();
+
+    // then
+    assert!(recipient.starts_with(RECIPIENT_PREFIX));
+    assert_eq!(
+      decrypted,
+      format!("icod-chunk:mom:{}", crate::conv::encode(&chunk.encode()))
+    );
+    assert!(matches!(err, Error::DecryptionError(_)));
+  }
+}
@@ -1,8 +1,11 @@
 //! High-level secure & restore message functions.
 
-use crate::encryption::{self, MSG_PREFIX};
+use crate::encryption::{self, FRAGMENT_PREFIX, MSG_PREFIX};
+use crate::identity;
+use crate::recipient;
 use crate::shamir::{self, CHUNK_PREFIX};
 use crate::JsValueOrString;
+use icod_crypto::identity::IdentityKey;
 #[cfg(not(test))]
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
@@ -27,6 +30,10 @@ pub enum SecuringError {
   InvalidChunksConfiguration,
   /// The encryption was unsuccessful.
   Encryption(encryption::Error),
+  /// The identity key is malformed.
+  Identity(identity::Error),
+  /// One of the recipients' public keys is malformed.
+  Recipient(recipient::Error),
 }
 
 impl From<encryption::Error> for SecuringError {
@@ -53,19 +60,168 @@ pub type SecureMessageResult = MessageAndChunks;
 /// scheme with given configuration.
 ///
 /// The resulting encrypted message may also be split into multiple parts
-/// using `split` parameter to make sure it can fit into QR codes.
+/// using `split` parameter to make sure it can fit into QR codes
+/// (see [crate::qr::qr_message_split]).
+///
+/// With `tagged` set, every part carries the message identifier and a checksum,
+/// so that damaged parts and parts of other messages are reported during
+/// restoration (see [crate::identify::identify]).
 #[cfg_attr(not(test), wasm_bindgen)]
 pub fn secure_message(
   msg: String,
   split: Option<usize>,
   chunks_configuration: shamir::ChunksConfiguration,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
+  let msg = icod_crypto::encryption::Message::from_str(&msg);
+  secure(msg, split, chunks_configuration, None, tagged)
+}
+
+/// Secure given message exactly like [secure_message], but sign every
+/// chunk and message part with owner's identity key.
+///
+/// See [crate::identity::generate_identity_key].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn secure_signed_message(
+  msg: String,
+  split: Option<usize>,
+  chunks_configuration: shamir::ChunksConfiguration,
+  identity_key: Vec<u8>,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
+  let identity = identity::parse_identity_key(identity_key).map_err(SecuringError::Identity)?;
+  let msg = icod_crypto::encryption::Message::from_str(&msg);
+  secure(msg, split, chunks_configuration, Some(&identity), tagged)
+}
+
+/// Secure given binary data (i.e. an encoded container, see [crate::container]).
+///
+/// Works exactly the same as [secure_message], but accepts raw bytes (`Uint8Array`)
+/// instead of a string.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn secure_bytes(
+  data: Vec<u8>,
+  split: Option<usize>,
+  chunks_configuration: shamir::ChunksConfiguration,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
+  let msg = icod_crypto::encryption::Message::from_bytes(&data);
+  secure(msg, split, chunks_configuration, None, tagged)
+}
+
+/// Secure given binary data exactly like [secure_bytes], but sign every
+/// chunk and message part with owner's identity key.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn secure_signed_bytes(
+  data: Vec<u8>,
+  split: Option<usize>,
+  chunks_configuration: shamir::ChunksConfiguration,
+  identity_key: Vec<u8>,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
+  let identity = identity::parse_identity_key(identity_key).map_err(SecuringError::Identity)?;
+  let msg = icod_crypto::encryption::Message::from_bytes(&data);
+  secure(msg, split, chunks_configuration, Some(&identity), tagged)
+}
+
+/// Secure given message exactly like [secure_message], but encrypt every
+/// chunk to it's recipient.
+///
+/// The `recipients` are public keys obtained from [crate::recipient::recipient_public_key].
+/// Every recipient receives one chunk and any `required` of them are sufficient
+/// to restore the message. The chunks are prefixed with
+/// [crate::shamir::ENCRYPTED_CHUNK_PREFIX] and need to be decrypted using
+/// [crate::recipient::decrypt_chunk] before restoration.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn secure_message_for_recipients(
+  msg: String,
+  split: Option<usize>,
+  required: u8,
+  recipients: Vec<JsValueOrString>,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
+  let recipients = recipients
+    .into_iter()
+    .map(recipient::parse_recipient)
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(SecuringError::Recipient)?;
+  let chunks_configuration = recipients
+    .len()
+    .checked_sub(required as usize)
+    .and_then(|spare| u8::try_from(spare).ok())
+    .and_then(|spare| icod_crypto::shamir::ChunksConfiguration::new(required, spare).ok())
+    .ok_or(SecuringError::InvalidChunksConfiguration)?;
+  let msg = icod_crypto::encryption::Message::from_str(&msg);
+  let (encrypted_message, chunks) =
+    icod_crypto::secure_message_for_recipients(msg, chunks_configuration, &recipients).map_err(
+      |e| match e {
+        icod_crypto::RecipientsError::RecipientsCountMismatch => {
+          SecuringError::InvalidChunksConfiguration
+        }
+        icod_crypto::RecipientsError::Encryption(e) => encryption::Error::from(e).into(),
+      },
+    )?;
+
+  let chunks = recipient::encrypted_chunks_to_js(chunks);
+  let encrypted_message = if tagged.unwrap_or_default() {
+    encrypted_message.split_and_encode_tagged(split, None)
+  } else {
+    encrypted_message.split_and_encode(split)
+  };
+  Ok(to_result(encrypted_message, chunks))
+}
+
+/// Secure given message exactly like [secure_message], but instead of the complete
+/// encrypted message return it's fragments.
+///
+/// Every recipient should receive a fragment (prefixed with [FRAGMENT_PREFIX])
+/// along with a chunk. Any `required` recipients have enough fragments and chunks
+/// to restore the message using [restore_dispersed_message], so nobody has to
+/// keep the complete encrypted message.
+///
+/// The fragments may be split into parts carrying at most `split` bytes of data,
+/// in which case `n`-th recipient should receive all of the parts of `n`-th
+/// fragment (see [crate::identify::identify]).
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn secure_dispersed_message(
+  msg: String,
+  split: Option<usize>,
+  chunks_configuration: shamir::ChunksConfiguration,
 ) -> Result<SecureMessageResult, SecuringError> {
+  let chunks_configuration = chunks_configuration
+    .to_icod()
+    .map_err(|_| SecuringError::InvalidChunksConfiguration)?;
   let msg = icod_crypto::encryption::Message::from_str(&msg);
+  let (fragments, chunks) = icod_crypto::secure_dispersed_message(msg, chunks_configuration)
+    .map_err(encryption::Error::from)?;
+
+  let fragments = fragments
+    .into_iter()
+    .flat_map(|fragment| fragment.split(split))
+    .map(|fragment| crate::conv::bytes_to_prefixed_str(FRAGMENT_PREFIX, &fragment.encode()))
+    .collect();
+  let chunks = chunks
+    .into_iter()
+    .map(|chunk| crate::conv::bytes_to_prefixed_str(CHUNK_PREFIX, &chunk.encode()))
+    .collect();
+  Ok(to_result_strings(fragments, chunks))
+}
+
+fn secure(
+  msg: icod_crypto::encryption::Message,
+  split: Option<usize>,
+  chunks_configuration: shamir::ChunksConfiguration,
+  identity: Option<&IdentityKey>,
+  tagged: Option<bool>,
+) -> Result<SecureMessageResult, SecuringError> {
   let chunks_configuration = chunks_configuration
     .to_icod()
     .map_err(|_| SecuringError::InvalidChunksConfiguration)?;
-  let (encrypted_message, chunks) =
-    icod_crypto::secure_message(msg, chunks_configuration).map_err(encryption::Error::from)?;
+  let (encrypted_message, chunks) = match identity {
+    Some(identity) => icod_crypto::secure_signed_message(msg, chunks_configuration, identity),
+    None => icod_crypto::secure_message(msg, chunks_configuration),
+  }
+  .map_err(encryption::Error::from)?;
 
   let chunks = chunks
     .into_iter()
@@ -73,21 +229,35 @@ pub fn secure_message(
     .collect();
 
   println!("Original: {:?}", encrypted_message);
-  let encrypted_message = encrypted_message.split_and_encode(split);
+  let encrypted_message = match (identity, tagged.unwrap_or_default()) {
+    (identity, true) => encrypted_message.split_and_encode_tagged(split, identity),
+    (Some(identity), false) => encrypted_message.split_and_encode_signed(split, identity),
+    (None, false) => encrypted_message.split_and_encode(split),
+  };
+  Ok(to_result(encrypted_message, chunks))
+}
+
+fn to_result(
+  encrypted_message: Vec<icod_crypto::Bytes>,
+  chunks: Vec<String>,
+) -> SecureMessageResult {
   let encrypted_message = encrypted_message
     .into_iter()
     .map(|msg| crate::conv::bytes_to_prefixed_str(MSG_PREFIX, &msg))
     .collect();
+  to_result_strings(encrypted_message, chunks)
+}
+
+fn to_result_strings(encrypted_message: Vec<String>, chunks: Vec<String>) -> SecureMessageResult {
   let result = MessageAndChunks {
     encrypted_message,
     chunks,
   };
   #[cfg(not(test))]
-  return Ok(
-    serde_wasm_bindgen::to_value(&result).expect("MessageAndChunks serialization is infallible"),
-  );
+  return serde_wasm_bindgen::to_value(&result)
+    .expect("MessageAndChunks serialization is infallible");
   #[cfg(test)]
-  Ok(result)
+  result
 }
 
 /// An error occuring during restoration process.
@@ -97,6 +267,10 @@ pub enum RestorationError {
   Recovery(shamir::RecoveryError),
   /// Error during decryption process.
   Decryption(encryption::Error),
+  /// The message parts and chunks are signed by different owners.
+  SignerMismatch,
+  /// The dispersed message could not be reassembled from it's fragments.
+  Dispersal(String),
 }
 
 impl From<shamir::RecoveryError> for RestorationError {
@@ -110,6 +284,8 @@ impl From<icod_crypto::RestorationError> for RestorationError {
     match value {
       icod_crypto::RestorationError::Recovery(err) => Self::Recovery(err.into()),
       icod_crypto::RestorationError::Decryption(err) => Self::Decryption(err.into()),
+      icod_crypto::RestorationError::SignerMismatch => Self::SignerMismatch,
+      icod_crypto::RestorationError::Dispersal(err) => Self::Dispersal(format!("{:?}", err)),
     }
   }
 }
@@ -132,13 +308,101 @@ pub fn restore_message(
   message: Vec<JsValueOrString>,
   chunks: Vec<JsValueOrString>,
 ) -> Result<String, RestorationError> {
+  let (message, _) = restore(message, chunks)?;
+  Ok(String::from_utf8_lossy(&message).to_string())
+}
+
+/// A message restored using [restore_signed_message].
+#[derive(Debug, serde::Serialize)]
+pub struct RestoredMessage {
+  /// The original message.
+  pub message: String,
+  /// Fingerprint of the owner who signed the chunks or message parts.
+  pub signer: Option<String>,
+}
+
+/// `RestoredMessage` serde-encoded `JsValue` object.
+#[cfg(not(test))]
+pub type RestoredMessageResult = JsValue;
+/// Regular `RestoredMessage` type for tests.
+#[cfg(test)]
+pub type RestoredMessageResult = RestoredMessage;
+
+/// Restore the original message exactly like [restore_message], but also
+/// report the fingerprint of the owner who signed the pieces.
+///
+/// The signatures are always verified, however the `signer` should be compared
+/// with the fingerprint shared by the owner to detect spoofing.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn restore_signed_message(
+  message: Vec<JsValueOrString>,
+  chunks: Vec<JsValueOrString>,
+) -> Result<RestoredMessageResult, RestorationError> {
+  let (message, signer) = restore(message, chunks)?;
+  let result = RestoredMessage {
+    message: String::from_utf8_lossy(&message).to_string(),
+    signer,
+  };
+  #[cfg(not(test))]
+  return Ok(
+    serde_wasm_bindgen::to_value(&result).expect("RestoredMessage serialization is infallible"),
+  );
+  #[cfg(test)]
+  Ok(result)
+}
+
+/// Restore the original binary data given parts of the encrypted message and SSS chunks.
+///
+/// Contrary to [restore_message] the data is returned as-is (`Uint8Array`), so it's
+/// suitable for restoring binary payloads (see [crate::container::decode_container]).
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn restore_bytes(
+  message: Vec<JsValueOrString>,
+  chunks: Vec<JsValueOrString>,
+) -> Result<Vec<u8>, RestorationError> {
+  Ok(restore(message, chunks)?.0.into())
+}
+
+/// Restore the original message given fragments of the dispersed message and SSS chunks.
+///
+/// See [secure_dispersed_message].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn restore_dispersed_message(
+  fragments: Vec<JsValueOrString>,
+  chunks: Vec<JsValueOrString>,
+) -> Result<String, RestorationError> {
+  let fragments = fragments
+    .into_iter()
+    .map(|fragment| {
+      let bytes = crate::conv::prefixed_str_js_to_bytes(FRAGMENT_PREFIX, fragment, false)
+        .map_err(|e| RestorationError::Dispersal(format!("{:?}", e)))?;
+      icod_crypto::encryption::MessageFragment::decode(&bytes)
+        .map_err(|e| RestorationError::Dispersal(format!("{:?}", e)))
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let chunks = shamir::conv::js_to_chunks(chunks)?;
+  let message = icod_crypto::restore_dispersed_message(&fragments, chunks)?;
+  let (message, _) = message.into_tuple();
+  Ok(String::from_utf8_lossy(&message).to_string())
+}
+
+fn restore(
+  message: Vec<JsValueOrString>,
+  chunks: Vec<JsValueOrString>,
+) -> Result<(icod_crypto::Bytes, Option<String>), RestorationError> {
   let message = encryption::conv::js_to_msg_parts(message).map_err(encryption::Error::from)?;
   let encrypted_message = icod_crypto::encryption::EncryptedMessage::collate_from_parts(message)
     .map_err(encryption::Error::from)?;
   let chunks = shamir::conv::js_to_chunks(chunks)?;
+  // The signatures are verified when decoding message parts and recovering the key.
+  let signer = encrypted_message
+    .signer()
+    .copied()
+    .or_else(|| chunks.first().and_then(|c| c.signer().ok().flatten()))
+    .map(|signer| signer.fingerprint());
   let message = icod_crypto::restore_message(encrypted_message, chunks)?;
   let (message, _) = message.into_tuple();
-  Ok(String::from_utf8_lossy(&message).to_string())
+  Ok((message, signer))
 }
 
 #[cfg(test)]
@@ -155,7 +419,7 @@ mod tests {
       required: 1,
       spare: 1,
     };
-    let result = secure_message(msg.to_owned(), split, chunks_configuration).unwrap();
+    let result = secure_message(msg.to_owned(), split, chunks_configuration, None).unwrap();
 
     assert_eq!(result.encrypted_message.len(), 1);
     assert_eq!(
@@ -172,6 +436,176 @@ mod tests {
     assert_eq!(restored, msg);
   }
 
+  #[test]
+  fn should_secure_and_restore_binary_data() {
+    let data = vec![0u8, 159, 146, 150, 255];
+    let chunks_configuration = ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+    let result = secure_bytes(data.clone(), Some(20), chunks_configuration, None).unwrap();
+    assert_eq!(result.chunks.len(), 3);
+
+    let mut result = result;
+    result.chunks.remove(0);
+    let restored = restore_bytes(result.encrypted_message, result.chunks).unwrap();
+
+    assert_eq!(restored, data);
+  }
+
+  #[test]
+  fn should_secure_and_restore_signed_message() {
+    // given
+    let identity_key = crate::identity::generate_identity_key();
+    let fingerprint = crate::identity::identity_fingerprint(identity_key.clone()).unwrap();
+    let chunks_configuration = ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+
+    // when
+    let result = secure_signed_message(
+      "Signed message".into(),
+      Some(20),
+      chunks_configuration,
+      identity_key,
+      Some(true),
+    )
+    .unwrap();
+    let restored = restore_signed_message(result.encrypted_message, result.chunks).unwrap();
+
+    // then
+    assert_eq!(restored.message, "Signed message");
+    assert_eq!(restored.signer, Some(fingerprint));
+  }
+
+  #[test]
+  fn should_report_damaged_tagged_parts() {
+    // given
+    let identity_key = crate::identity::generate_identity_key();
+    let chunks_configuration = ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+    let result = secure_signed_message(
+      "Tagged message".into(),
+      Some(20),
+      chunks_configuration,
+      identity_key,
+      Some(true),
+    )
+    .unwrap();
+    let mut damaged = result.encrypted_message.clone();
+    // damage the data, the signature makes up the rest of the part.
+    let typo = MSG_PREFIX.len() + 20;
+    let replacement = if &damaged[1][typo..typo + 1] == "0" {
+      "1"
+    } else {
+      "0"
+    };
+    damaged[1].replace_range(typo..typo + 1, replacement);
+
+    // when
+    let err = restore_signed_message(damaged, result.chunks).unwrap_err();
+
+    // then
+    assert!(matches!(
+      err,
+      RestorationError::Decryption(encryption::Error::MessageDecodingError(e)) if e == "CorruptedPart(1)"
+    ));
+  }
+
+  #[test]
+  fn should_secure_message_for_recipients() {
+    // given
+    let keys: Vec<_> = (0..3)
+      .map(|_| crate::recipient::generate_recipient_key())
+      .collect();
+    let recipients = keys
+      .iter()
+      .map(|key| crate::recipient::recipient_public_key(key.clone()).unwrap())
+      .collect();
+
+    // when
+    let result =
+      secure_message_for_recipients("For your eyes only".into(), None, 2, recipients, None)
+        .unwrap();
+    let plain = restore_message(result.encrypted_message.clone(), result.chunks.clone());
+    let chunks = vec![
+      crate::recipient::decrypt_chunk(result.chunks[0].clone(), keys[0].clone()).unwrap(),
+      crate::recipient::decrypt_chunk(result.chunks[2].clone(), keys[2].clone()).unwrap(),
+    ];
+    let restored = restore_message(result.encrypted_message, chunks).unwrap();
+
+    // then
+    assert!(result.chunks[0].starts_with(crate::shamir::ENCRYPTED_CHUNK_PREFIX));
+    assert!(plain.is_err());
+    assert_eq!(restored, "For your eyes only");
+  }
+
+  #[test]
+  fn should_secure_and_restore_dispersed_message() {
+    // given
+    let chunks_configuration = ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+
+    // when
+    let result =
+      secure_dispersed_message("Only together".into(), None, chunks_configuration).unwrap();
+    let restored = restore_dispersed_message(
+      result.encrypted_message[1..3].to_vec(),
+      result.chunks[1..3].to_vec(),
+    )
+    .unwrap();
+    let not_enough = restore_dispersed_message(
+      result.encrypted_message[0..1].to_vec(),
+      result.chunks.clone(),
+    );
+
+    // then
+    assert_eq!(result.encrypted_message.len(), 3);
+    assert!(result.encrypted_message[0].starts_with(FRAGMENT_PREFIX));
+    assert_eq!(restored, "Only together");
+    assert!(matches!(not_enough, Err(RestorationError::Dispersal(_))));
+  }
+
+  #[test]
+  fn should_restore_dispersed_message_from_split_fragments() {
+    // given
+    let chunks_configuration = ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+
+    // when
+    let result =
+      secure_dispersed_message("Only together".into(), Some(8), chunks_configuration).unwrap();
+    let per_fragment = result.encrypted_message.len() / 3;
+    let restored = restore_dispersed_message(
+      result.encrypted_message[per_fragment..].to_vec(),
+      result.chunks[1..3].to_vec(),
+    )
+    .unwrap();
+    let missing_part = restore_dispersed_message(
+      result.encrypted_message[1..].to_vec(),
+      result.chunks[0..2].to_vec(),
+    )
+    .unwrap();
+    let not_enough = restore_dispersed_message(
+      result.encrypted_message[per_fragment + 1..].to_vec(),
+      result.chunks.clone(),
+    );
+
+    // then
+    assert!(per_fragment > 1);
+    assert_eq!(result.encrypted_message.len(), 3 * per_fragment);
+    assert_eq!(restored, "Only together");
+    assert_eq!(missing_part, "Only together");
+    assert!(matches!(not_enough, Err(RestorationError::Dispersal(_))));
+  }
+
   #[test]
   fn should_restore_with_named_chunks() {
     let chunk1 = "icod-chunk:moms chunk:d5hmup3303pbk9a68s73jd39675mlp143smkc97m6q65dcbj8co4rqbaqhhdspikfl9u4m8md3097gsegqeg8nm65pqm5qs0nhvftiedjkfbmlco080g00ephrv1pprbm97ma87quod19fn2vv4r0q7a7tco980uq2uf37oinnmkgjag5g70";
@@ -2,7 +2,9 @@
 
 use crate::JsValueOrString;
 use icod_crypto::encryption::MessageEncryptionKey;
-use icod_crypto::shamir::KeyRecoveryError;
+use icod_crypto::shamir::{
+  Chunk, ChunkMetadata, Commitments, KeyRecoveryError, MetadataError, PassphraseError, VssError,
+};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
 
@@ -36,6 +38,14 @@ pub enum RecoveryError {
   UnexpectedKey,
   /// The key could not be decoded.
   KeyDecodingError,
+  /// Some of the chunks are protected with a passphrase.
+  PassphraseProtected,
+  /// The chunk with given index does not match the VSS commitments.
+  InvalidChunk(u8),
+  /// The chunk with given index has invalid owner's signature.
+  InvalidSignature(u8),
+  /// The chunks are signed by different owners.
+  SignerMismatch,
 }
 
 impl From<RecoveryError> for JsValue {
@@ -52,6 +62,10 @@ impl From<KeyRecoveryError> for RecoveryError {
       KeyRecoveryError::NotEnoughChunks => Self::NotEnoughChunks,
       KeyRecoveryError::UnexpectedKey => Self::UnexpectedKey,
       KeyRecoveryError::KeyDecodingError => Self::KeyDecodingError,
+      KeyRecoveryError::PassphraseProtected => Self::PassphraseProtected,
+      KeyRecoveryError::InvalidChunk(index) => Self::InvalidChunk(index),
+      KeyRecoveryError::InvalidSignature(index) => Self::InvalidSignature(index),
+      KeyRecoveryError::SignerMismatch => Self::SignerMismatch,
     }
   }
 }
@@ -91,6 +105,32 @@ impl ChunksConfiguration {
 /// Used to identify the string typically obtained by scanning a QR code.
 pub const CHUNK_PREFIX: &'static str = "icod-chunk:";
 
+/// Human readable prefix of every chunk encrypted to it's recipient.
+///
+/// See [crate::recipient::decrypt_chunk].
+pub const ENCRYPTED_CHUNK_PREFIX: &str = "icod-echunk:";
+
+/// Human readable prefix of the VSS commitments.
+///
+/// See [split_into_verifiable_chunks].
+pub const COMMITMENTS_PREFIX: &str = "icod-vss:";
+
+/// Verifiable chunks along with the encoded commitments to check them.
+#[derive(serde::Serialize)]
+pub struct ChunksAndCommitments {
+  /// The verifiable SSS chunks.
+  pub chunks: Vec<String>,
+  /// The commitments prefixed with [COMMITMENTS_PREFIX].
+  pub commitments: String,
+}
+
+/// `ChunksAndCommitments` serde-encoded `JsValue` object.
+#[cfg(not(test))]
+pub type VerifiableChunksResult = JsValue;
+/// Regular `ChunksAndCommitments` type for tests.
+#[cfg(test)]
+pub type VerifiableChunksResult = ChunksAndCommitments;
+
 /// Split given `key` into SSS chunks according to `configuration`.
 ///
 /// The `key` should be raw, 32-bytes key. The magic sequence and version
@@ -110,6 +150,123 @@ pub fn split_into_chunks(
   Ok(conv::chunks_to_js(chunks))
 }
 
+/// Split given `key` into weighted SSS chunks.
+///
+/// Every recipient receives a single chunk carrying as many share points as
+/// their weight, and any chunks with total weight of at least `required`
+/// are sufficient to recover the key.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn split_into_weighted_chunks(
+  key: Vec<u8>,
+  required: u8,
+  weights: Vec<u8>,
+) -> Result<Vec<JsValueOrString>, SplittingError> {
+  let key = crate::parse_key(key).map_err(|_| SplittingError::InvalidKeySize)?;
+  let key = MessageEncryptionKey::new(key);
+  let configuration = icod_crypto::shamir::WeightedConfiguration::new(required, weights)
+    .map_err(|_| SplittingError::ConfigurationError)?;
+  let chunks = icod_crypto::shamir::split_into_weighted_chunks(key, &configuration);
+
+  Ok(conv::chunks_to_js(chunks))
+}
+
+/// Split given `key` into verifiable SSS chunks according to `configuration`.
+///
+/// Along with the chunks, the commitments (prefixed with [COMMITMENTS_PREFIX])
+/// are returned. They should be published or handed out together with every
+/// chunk, so that the recipients can check their chunks using [verify_chunk].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn split_into_verifiable_chunks(
+  key: Vec<u8>,
+  configuration: ChunksConfiguration,
+) -> Result<VerifiableChunksResult, SplittingError> {
+  let key = crate::parse_key(key).map_err(|_| SplittingError::InvalidKeySize)?;
+  let key = MessageEncryptionKey::new(key);
+  let chunks_configuration = configuration
+    .to_icod()
+    .map_err(|_| SplittingError::ConfigurationError)?;
+  let (chunks, commitments) =
+    icod_crypto::shamir::split_into_verifiable_chunks(key, chunks_configuration);
+
+  let result = ChunksAndCommitments {
+    chunks: chunks
+      .into_iter()
+      .map(|chunk| crate::conv::bytes_to_prefixed_str(CHUNK_PREFIX, &chunk.encode()))
+      .collect(),
+    commitments: crate::conv::bytes_to_prefixed_str(COMMITMENTS_PREFIX, &commitments.encode()),
+  };
+  #[cfg(not(test))]
+  return Ok(
+    serde_wasm_bindgen::to_value(&result)
+      .expect("ChunksAndCommitments serialization is infallible"),
+  );
+  #[cfg(test)]
+  Ok(result)
+}
+
+/// An error occuring while verifying the chunk against VSS commitments.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum VerificationError {
+  /// The value is not a chunk.
+  NotAChunk,
+  /// Cannot decode the chunk.
+  ChunkDecodingError(String),
+  /// Cannot decode the commitments.
+  CommitmentsDecodingError(String),
+  /// The chunk does not match the commitments.
+  VssError(VssError),
+}
+
+impl From<VerificationError> for JsValue {
+  fn from(value: VerificationError) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+/// Verify that given encoded chunk was produced together with the `commitments`
+/// (see [split_into_verifiable_chunks]).
+///
+/// The chunk may be named. Passphrase-protected chunks need to be unprotected first.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn verify_chunk(chunk: String, commitments: String) -> Result<(), VerificationError> {
+  let commitments =
+    parse_commitments(&commitments).map_err(VerificationError::CommitmentsDecodingError)?;
+  let (_name, chunk) =
+    crate::identify::identify_chunk(&chunk).ok_or(VerificationError::NotAChunk)?;
+  let bytes = crate::conv::decode(chunk)
+    .map_err(|e| VerificationError::ChunkDecodingError(format!("{:?}", e)))?;
+  let chunk =
+    Chunk::decode(&bytes).map_err(|e| VerificationError::ChunkDecodingError(format!("{:?}", e)))?;
+
+  commitments
+    .verify(&chunk)
+    .map_err(VerificationError::VssError)
+}
+
+/// Recover key given enough verifiable SSS chunks and their `commitments`.
+///
+/// Contrary to [recover_key] every chunk is checked first and the invalid one is
+/// reported via [RecoveryError::InvalidChunk].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn recover_verifiable_key(
+  chunks: Vec<JsValueOrString>,
+  commitments: String,
+) -> Result<Vec<u8>, RecoveryError> {
+  let commitments = parse_commitments(&commitments).map_err(RecoveryError::ChunkDecodingError)?;
+  let chunks = conv::js_to_chunks(chunks)?;
+  let key = icod_crypto::shamir::vss::recover_key(&chunks, &commitments)?;
+
+  Ok(key.encode().into())
+}
+
+fn parse_commitments(commitments: &str) -> Result<Commitments, String> {
+  let encoded = crate::conv::strip_prefix(commitments, COMMITMENTS_PREFIX)
+    .ok_or_else(|| "MissingPrefix".to_owned())?;
+  let bytes = crate::conv::decode(encoded).map_err(|e| format!("{:?}", e))?;
+  Commitments::decode(&bytes).map_err(|e| format!("{:?}", e))
+}
+
 /// Recover key given enough SSS chunks.
 ///
 /// The recovered key will be byte-encoded, i.e. it will
@@ -122,6 +279,168 @@ pub fn recover_key(chunks: Vec<JsValueOrString>) -> Result<Vec<u8>, RecoveryErro
   Ok(key.encode().into())
 }
 
+/// An error occuring while issuing a new chunk.
+#[derive(Debug)]
+pub enum IssueError {
+  /// The provided chunks could not be used to recover the key.
+  RecoveryError(RecoveryError),
+  /// Only regular chunks can be used to issue a new one.
+  Unsupported,
+  /// The chunk index is already used or is out of range.
+  InvalidIndex,
+}
+
+impl From<IssueError> for JsValue {
+  fn from(value: IssueError) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<RecoveryError> for IssueError {
+  fn from(value: RecoveryError) -> Self {
+    Self::RecoveryError(value)
+  }
+}
+
+impl From<icod_crypto::shamir::IssueError> for IssueError {
+  fn from(value: icod_crypto::shamir::IssueError) -> Self {
+    use icod_crypto::shamir::IssueError as E;
+    match value {
+      E::Recovery(e) => Self::RecoveryError(e.into()),
+      E::Unsupported => Self::Unsupported,
+      E::InvalidIndex => Self::InvalidIndex,
+    }
+  }
+}
+
+/// Mint a new SSS chunk at given `index` from a threshold of existing `chunks`.
+///
+/// Can be used to replace a lost chunk (issuing it at the same index) or to add
+/// a new recipient (the returned chunk will have more `spare` chunks).
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn issue_chunk(chunks: Vec<JsValueOrString>, index: u8) -> Result<JsValueOrString, IssueError> {
+  let chunks = conv::js_to_chunks(chunks)?;
+  let chunk = icod_crypto::shamir::issue_chunk(&chunks, index)?;
+
+  Ok(crate::conv::bytes_to_prefixed_str_js(
+    CHUNK_PREFIX,
+    &chunk.encode(),
+  ))
+}
+
+/// An error occuring while adding or removing chunk's passphrase protection
+/// or changing it's encoding or metadata.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum ProtectionError {
+  /// The value is not a chunk.
+  NotAChunk,
+  /// Cannot decode the chunk.
+  ChunkDecodingError(String),
+  /// The passphrase could not be applied or removed.
+  PassphraseError(PassphraseError),
+  /// The metadata could not be attached.
+  MetadataError(MetadataError),
+  /// The identity key is malformed.
+  Identity(crate::identity::Error),
+}
+
+impl From<ProtectionError> for JsValue {
+  fn from(value: ProtectionError) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<PassphraseError> for ProtectionError {
+  fn from(value: PassphraseError) -> Self {
+    Self::PassphraseError(value)
+  }
+}
+
+impl From<MetadataError> for ProtectionError {
+  fn from(value: MetadataError) -> Self {
+    Self::MetadataError(value)
+  }
+}
+
+/// Encrypt the data of given encoded chunk with a key derived from `passphrase`.
+///
+/// The name of the chunk (if any) is preserved.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn protect_chunk(
+  chunk: String,
+  passphrase: String,
+) -> Result<JsValueOrString, ProtectionError> {
+  map_chunk(&chunk, |chunk| Ok(chunk.protect(passphrase.as_bytes())?))
+}
+
+/// Remove the passphrase protection of given encoded chunk.
+///
+/// The name of the chunk (if any) is preserved.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn unprotect_chunk(
+  chunk: String,
+  passphrase: String,
+) -> Result<JsValueOrString, ProtectionError> {
+  map_chunk(&chunk, |chunk| Ok(chunk.unprotect(passphrase.as_bytes())?))
+}
+
+/// Convert given encoded chunk to the shorter encoding with a checksum.
+///
+/// The compact chunks can be used for recovery interchangeably with the regular ones.
+/// The name of the chunk (if any) is preserved.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn compact_chunk(chunk: String) -> Result<JsValueOrString, ProtectionError> {
+  map_chunk(&chunk, |chunk| Ok(chunk.into_compact()))
+}
+
+/// Attach the metadata to given encoded chunk and sign it with owner's identity key.
+///
+/// The metadata is stored inside the chunk and covered by the signature, so unlike
+/// the name in front of the encoded chunk (see [crate::identify::alter_chunks_name])
+/// it can't be altered. Empty metadata removes it from the chunk.
+/// The `created_at` is a UNIX timestamp in seconds.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn set_chunk_metadata(
+  chunk: String,
+  name: Option<String>,
+  recipient: Option<String>,
+  owner_contact: Option<String>,
+  instructions: Option<String>,
+  created_at: Option<f64>,
+  identity_key: Vec<u8>,
+) -> Result<JsValueOrString, ProtectionError> {
+  let identity =
+    crate::identity::parse_identity_key(identity_key).map_err(ProtectionError::Identity)?;
+  let metadata = ChunkMetadata {
+    name,
+    recipient,
+    owner_contact,
+    instructions,
+    created_at: created_at.map(|t| t as u64),
+  };
+  map_chunk(&chunk, |chunk| {
+    Ok(chunk.with_metadata(metadata)?.sign(&identity))
+  })
+}
+
+fn map_chunk(
+  item: &str,
+  f: impl FnOnce(Chunk) -> Result<Chunk, ProtectionError>,
+) -> Result<JsValueOrString, ProtectionError> {
+  let (name, chunk) = crate::identify::identify_chunk(item).ok_or(ProtectionError::NotAChunk)?;
+  let bytes = crate::conv::decode(chunk)
+    .map_err(|e| ProtectionError::ChunkDecodingError(format!("{:?}", e)))?;
+  let chunk =
+    Chunk::decode(&bytes).map_err(|e| ProtectionError::ChunkDecodingError(format!("{:?}", e)))?;
+  let encoded = crate::conv::encode(&f(chunk)?.encode());
+
+  Ok(crate::conv::js_value_or_string(match name {
+    Some(name) => format!("{}{}:{}", CHUNK_PREFIX, name, encoded),
+    None => format!("{}{}", CHUNK_PREFIX, encoded),
+  }))
+}
+
 pub(crate) mod conv {
   use super::{RecoveryError, CHUNK_PREFIX};
   use crate::JsValueOrString;
@@ -177,4 +496,134 @@ mod tests {
     let recovered_no_version = &recovered_no_prefix[1..];
     assert_eq!(recovered_no_version, &key);
   }
+
+  #[test]
+  fn should_split_key_into_weighted_chunks() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let chunks = split_into_weighted_chunks(key.clone(), 3, vec![2, 1, 1]).unwrap();
+
+    // when
+    let siblings = recover_key(chunks[1..].to_vec());
+    let recovered = recover_key(chunks[..2].to_vec()).unwrap();
+
+    // then
+    assert!(matches!(siblings, Err(RecoveryError::NotEnoughChunks)));
+    assert_eq!(&recovered[6..], &key);
+    assert!(split_into_weighted_chunks(key, 3, vec![1, 1]).is_err());
+  }
+
+  #[test]
+  fn should_split_key_into_verifiable_chunks() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let result = split_into_verifiable_chunks(key.clone(), ChunksConfiguration::new(2, 1)).unwrap();
+    let other = split_into_verifiable_chunks(key.clone(), ChunksConfiguration::new(2, 1)).unwrap();
+    let named = result.chunks[0].replace(CHUNK_PREFIX, "icod-chunk:my chunk:");
+
+    // when
+    let verified = verify_chunk(named, result.commitments.clone());
+    let mismatch = verify_chunk(result.chunks[0].clone(), other.commitments.clone());
+    let invalid = verify_chunk(result.chunks[0].clone(), "icod-vss:abc".into());
+    let recovered =
+      recover_verifiable_key(result.chunks[1..].to_vec(), result.commitments.clone()).unwrap();
+    let mixed = recover_verifiable_key(
+      vec![result.chunks[0].clone(), other.chunks[1].clone()],
+      result.commitments,
+    );
+
+    // then
+    assert!(other.commitments.starts_with(COMMITMENTS_PREFIX));
+    assert_eq!(verified, Ok(()));
+    assert_eq!(
+      mismatch,
+      Err(VerificationError::VssError(VssError::CommitmentsMismatch))
+    );
+    assert!(matches!(
+      invalid,
+      Err(VerificationError::CommitmentsDecodingError(_))
+    ));
+    assert_eq!(&recovered[6..], &key);
+    assert!(matches!(mixed, Err(RecoveryError::InvalidChunk(1))));
+  }
+
+  #[test]
+  fn should_issue_additional_chunk() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let configuration = ChunksConfiguration {
+      required: 2,
+      spare: 0,
+    };
+    let chunks = split_into_chunks(key.clone(), configuration).unwrap();
+
+    // when
+    let issued = issue_chunk(chunks.clone(), 2).unwrap();
+    let replaced = issue_chunk(vec![chunks[1].clone(), issued.clone()], 0).unwrap();
+    let recovered = recover_key(vec![chunks[0].clone(), issued.clone()]).unwrap();
+    let recovered_with_replaced = recover_key(vec![replaced, issued]).unwrap();
+
+    // then
+    assert_eq!(&recovered[6..], &key);
+    assert_eq!(recovered_with_replaced, recovered);
+    assert!(matches!(
+      issue_chunk(chunks, 1),
+      Err(IssueError::InvalidIndex)
+    ));
+  }
+
+  #[test]
+  fn should_protect_chunk_with_passphrase() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let configuration = ChunksConfiguration {
+      required: 1,
+      spare: 1,
+    };
+    let chunks = split_into_chunks(key.clone(), configuration).unwrap();
+    let named = chunks[0].replace(CHUNK_PREFIX, "icod-chunk:my chunk:");
+
+    // when
+    let protected = protect_chunk(named, "secret".into()).unwrap();
+    let err = recover_key(vec![protected.clone()]).unwrap_err();
+    let invalid = unprotect_chunk(protected.clone(), "Secret".into()).unwrap_err();
+    let unprotected = unprotect_chunk(protected.clone(), "secret".into()).unwrap();
+
+    // then
+    assert!(protected.starts_with("icod-chunk:my chunk:"));
+    assert!(matches!(err, RecoveryError::PassphraseProtected));
+    assert_eq!(
+      invalid,
+      ProtectionError::PassphraseError(PassphraseError::InvalidPassphrase)
+    );
+    assert_eq!(
+      unprotected,
+      chunks[0].replace(CHUNK_PREFIX, "icod-chunk:my chunk:")
+    );
+  }
+
+  #[test]
+  fn should_recover_key_from_compact_chunks() {
+    // given
+    let key = [1u8; KEY_SIZE].to_vec();
+    let configuration = ChunksConfiguration {
+      required: 2,
+      spare: 0,
+    };
+    let chunks = split_into_chunks(key.clone(), configuration).unwrap();
+
+    // when
+    let compact = compact_chunk(chunks[0].replace(CHUNK_PREFIX, "icod-chunk:my chunk:")).unwrap();
+    let recovered = recover_key(vec![compact.clone(), chunks[1].clone()]).unwrap();
+    let mut typo = compact.clone().into_bytes();
+    let middle = typo.len() - 20;
+    typo[middle] = if typo[middle] == b'0' { b'1' } else { b'0' };
+    let err = recover_key(vec![String::from_utf8(typo).unwrap(), chunks[1].clone()]).unwrap_err();
+
+    // then
+    assert!(compact.starts_with("icod-chunk:my chunk:"));
+    assert!(compact.len() < chunks[0].len());
+    assert_eq!(recovered[recovered.len() - KEY_SIZE..], key[..]);
+    assert!(matches!(err, RecoveryError::ChunkDecodingError(e) if e == "ChecksumMismatch"));
+  }
 }
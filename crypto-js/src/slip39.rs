@@ -0,0 +1,174 @@
+//! SLIP-39 import and export exposed to JS.
+
+use icod_crypto::encryption::MessageEncryptionKey;
+use icod_crypto::shamir::{slip39, Slip39Error};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::shamir::{conv, ChunksConfiguration, RecoveryError};
+use crate::JsValueOrString;
+
+/// An error occuring during SLIP-39 conversion.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// Provided `key` has invalid byte length.
+  InvalidKeySize,
+  /// The chunks or groups configuration is incorrect.
+  ConfigurationError,
+  /// The chunks could not be decoded or used to recover the key.
+  RecoveryError(String),
+  /// The SLIP-39 shares could not be converted.
+  Slip39Error(Slip39Error),
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<Slip39Error> for Error {
+  fn from(value: Slip39Error) -> Self {
+    match value {
+      Slip39Error::KeyRecovery(e) => Self::RecoveryError(format!("{:?}", RecoveryError::from(e))),
+      e => Self::Slip39Error(e),
+    }
+  }
+}
+
+impl From<RecoveryError> for Error {
+  fn from(value: RecoveryError) -> Self {
+    Self::RecoveryError(format!("{:?}", value))
+  }
+}
+
+/// Split given raw, 32-bytes `key` into SLIP-39 mnemonic shares.
+///
+/// The `n`-th group requires `members_required[n]` of
+/// `members_required[n] + members_spare[n]` shares, while `group_threshold`
+/// of the groups are required to recover the key.
+/// The shares of all groups are returned one after another.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn key_to_slip39(
+  key: Vec<u8>,
+  group_threshold: u8,
+  members_required: Vec<u8>,
+  members_spare: Vec<u8>,
+  passphrase: String,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let key = crate::parse_key(key).map_err(|_| Error::InvalidKeySize)?;
+  let groups = groups(members_required, members_spare)?;
+  let shares = slip39::split_into_slip39_shares(
+    MessageEncryptionKey::new(key),
+    group_threshold,
+    &groups,
+    &passphrase,
+  )?;
+  Ok(shares_to_js(shares))
+}
+
+/// Recover the key from SSS chunks and split it into SLIP-39 mnemonic shares.
+///
+/// See [key_to_slip39] for the description of the groups.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn chunks_to_slip39(
+  chunks: Vec<JsValueOrString>,
+  group_threshold: u8,
+  members_required: Vec<u8>,
+  members_spare: Vec<u8>,
+  passphrase: String,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let chunks = conv::js_to_chunks(chunks)?;
+  let groups = groups(members_required, members_spare)?;
+  let shares = slip39::chunks_to_slip39(&chunks, group_threshold, &groups, &passphrase)?;
+  Ok(shares_to_js(shares))
+}
+
+/// Recover the key from SLIP-39 mnemonic shares produced by any implementation.
+///
+/// The recovered key will be byte-encoded, like the one returned by
+/// [crate::shamir::recover_key].
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn slip39_to_key(mnemonics: Vec<String>, passphrase: String) -> Result<Vec<u8>, Error> {
+  let mnemonics: Vec<&str> = mnemonics.iter().map(String::as_str).collect();
+  let key = slip39::recover_key_from_slip39(&mnemonics, &passphrase)?;
+  Ok(key.encode().into())
+}
+
+/// Import SLIP-39 mnemonic shares as a new set of SSS chunks.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn slip39_to_chunks(
+  mnemonics: Vec<String>,
+  passphrase: String,
+  configuration: ChunksConfiguration,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let mnemonics: Vec<&str> = mnemonics.iter().map(String::as_str).collect();
+  let configuration = configuration
+    .to_icod()
+    .map_err(|_| Error::ConfigurationError)?;
+  let chunks = slip39::slip39_to_chunks(&mnemonics, &passphrase, configuration)?;
+  Ok(conv::chunks_to_js(chunks))
+}
+
+fn groups(
+  members_required: Vec<u8>,
+  members_spare: Vec<u8>,
+) -> Result<Vec<icod_crypto::shamir::ChunksConfiguration>, Error> {
+  if members_required.len() != members_spare.len() {
+    return Err(Error::ConfigurationError);
+  }
+  members_required
+    .into_iter()
+    .zip(members_spare)
+    .map(|(required, spare)| {
+      icod_crypto::shamir::ChunksConfiguration::new(required, spare)
+        .map_err(|_| Error::ConfigurationError)
+    })
+    .collect()
+}
+
+fn shares_to_js(shares: Vec<Vec<String>>) -> Vec<JsValueOrString> {
+  shares
+    .into_iter()
+    .flatten()
+    .map(crate::conv::js_value_or_string)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use icod_crypto::encryption::KEY_SIZE;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_convert_chunks_to_slip39_and_back() {
+    // given
+    let conf = ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+    let chunks = crate::shamir::split_into_chunks([5u8; KEY_SIZE].to_vec(), conf).unwrap();
+    let key = crate::shamir::recover_key(chunks.clone()).unwrap();
+
+    // when
+    let shares = chunks_to_slip39(chunks[..2].to_vec(), 1, vec![2], vec![1], "".into()).unwrap();
+    let recovered = slip39_to_key(shares[1..].to_vec(), "".into()).unwrap();
+    let conf = ChunksConfiguration {
+      required: 1,
+      spare: 0,
+    };
+    let imported = slip39_to_chunks(shares[..2].to_vec(), "".into(), conf).unwrap();
+
+    // then
+    assert_eq!(shares.len(), 3);
+    assert_eq!(recovered, key);
+    assert_eq!(crate::shamir::recover_key(imported).unwrap(), key);
+    assert_eq!(
+      key_to_slip39([5u8; KEY_SIZE].to_vec(), 2, vec![2], vec![1], "".into()),
+      Err(Error::Slip39Error(Slip39Error::InvalidConfiguration))
+    );
+  }
+}
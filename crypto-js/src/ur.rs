@@ -0,0 +1,215 @@
+//! Uniform Resources (UR) encoding exposed to JS.
+
+use icod_crypto::shamir::Chunk;
+use icod_crypto::ur::{self, UrEncoder, UrPayload};
+#[cfg(not(test))]
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+use crate::encryption::MSG_PREFIX;
+use crate::shamir::CHUNK_PREFIX;
+use crate::JsValueOrString;
+
+/// An error occuring when encoding or decoding the UR.
+#[derive(Debug)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub enum Error {
+  /// The values are not chunks or message parts.
+  UnknownValue,
+  /// The chunk or message part could not be decoded.
+  DecodingError(String),
+  /// The UR could not be decoded.
+  UrError(ur::UrError),
+}
+
+impl From<Error> for JsValue {
+  fn from(value: Error) -> Self {
+    JsValue::from_str(&format!("{:?}", value))
+  }
+}
+
+impl From<ur::UrError> for Error {
+  fn from(value: ur::UrError) -> Self {
+    Self::UrError(value)
+  }
+}
+
+/// Encode given values as UR parts.
+///
+/// The `items` are the strings produced by other functions: a single chunk
+/// (`ur:icod-chunk`), the message parts (`ur:icod-msg`) or the message parts
+/// together with the chunks, i.e. the whole secure result (`ur:icod-secured`).
+///
+/// The value is split into fragments of at most `max_fragment_len` bytes and the
+/// function returns `count` parts starting at sequence number `start` (from `1`),
+/// so the stream can be generated lazily while looping the animated QR code.
+/// A value fitting into a single fragment is returned as a single part.
+#[cfg_attr(not(test), wasm_bindgen)]
+pub fn encode_ur(
+  items: Vec<JsValueOrString>,
+  max_fragment_len: usize,
+  start: u32,
+  count: u32,
+) -> Result<Vec<JsValueOrString>, Error> {
+  let payload = to_payload(items)?;
+  let encoder = UrEncoder::from_payload(&payload, max_fragment_len);
+  if encoder.is_single_part() {
+    return Ok(vec![crate::conv::js_value_or_string(encoder.part(1))]);
+  }
+  Ok(
+    (start.max(1)..start.max(1).saturating_add(count))
+      .map(|seq| crate::conv::js_value_or_string(encoder.part(seq)))
+      .collect(),
+  )
+}
+
+/// Incremental decoder of the parts produced by [encode_ur] or other UR-capable tools.
+///
+/// The parts can be fed as they are scanned by the camera, in any order.
+#[cfg_attr(not(test), wasm_bindgen)]
+#[derive(Default)]
+pub struct UrDecoder {
+  decoder: ur::UrDecoder,
+}
+
+#[cfg_attr(not(test), wasm_bindgen)]
+impl UrDecoder {
+  /// Create a new decoder.
+  #[cfg_attr(not(test), wasm_bindgen(constructor))]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feed the decoder with a scanned part.
+  ///
+  /// Returns `true` if the value is fully recovered.
+  pub fn receive(&mut self, part: String) -> Result<bool, Error> {
+    Ok(self.decoder.receive(&part)?)
+  }
+
+  /// Fraction of the value recovered so far (`0.0` to `1.0`).
+  pub fn progress(&self) -> f64 {
+    match self.decoder.fragments_count() {
+      0 => 0.0,
+      count => self.decoder.decoded_fragments() as f64 / count as f64,
+    }
+  }
+
+  /// Return the recovered message parts and chunks.
+  ///
+  /// The values can be passed to restoration functions. Returns `None` until
+  /// the value is fully recovered.
+  pub fn items(&self) -> Result<Option<Vec<JsValueOrString>>, Error> {
+    Ok(decoded_items(&self.decoder)?.map(|items| {
+      items
+        .into_iter()
+        .map(crate::conv::js_value_or_string)
+        .collect()
+    }))
+  }
+}
+
+/// Return the message parts and chunks carried by the complete UR.
+pub(crate) fn decoded_items(decoder: &ur::UrDecoder) -> Result<Option<Vec<String>>, Error> {
+  let Some(payload) = decoder.payload() else {
+    return Ok(None);
+  };
+  let (message, chunks) = match payload? {
+    UrPayload::Chunk(chunk) => (vec![], vec![chunk]),
+    UrPayload::Message(parts) => (parts, vec![]),
+    UrPayload::Secured { message, chunks } => (message, chunks),
+  };
+  Ok(Some(
+    message
+      .iter()
+      .map(|part| crate::conv::bytes_to_prefixed_str(MSG_PREFIX, part))
+      .chain(
+        chunks
+          .iter()
+          .map(|chunk| crate::conv::bytes_to_prefixed_str(CHUNK_PREFIX, &chunk.encode())),
+      )
+      .collect(),
+  ))
+}
+
+/// Return the sequence number and the number of parts of a multi-part UR.
+pub(crate) fn sequence(item: &str) -> Option<(u32, u32)> {
+  let mut components = item.split('/').skip(1);
+  let (seq, count) = components.next()?.split_once('-')?;
+  components.next()?;
+  Some((seq.parse().ok()?, count.parse().ok()?))
+}
+
+fn to_payload(items: Vec<JsValueOrString>) -> Result<UrPayload, Error> {
+  let mut message = vec![];
+  let mut chunks = vec![];
+  for item in items {
+    #[cfg(not(test))]
+    let item = item.as_string().ok_or(Error::UnknownValue)?;
+    if let Some((_name, chunk)) = crate::identify::identify_chunk(&item) {
+      let bytes = crate::conv::decode(chunk).map_err(|_| decoding_error("chunk"))?;
+      chunks.push(Chunk::decode(&bytes).map_err(decoding_error)?);
+    } else if let Some(part) = crate::conv::strip_prefix(&item, MSG_PREFIX) {
+      let bytes = crate::conv::decode(part).map_err(|_| decoding_error("message part"))?;
+      message.push(icod_crypto::Bytes::from_slice(&bytes));
+    } else {
+      return Err(Error::UnknownValue);
+    }
+  }
+
+  match (message.is_empty(), chunks.len()) {
+    (true, 1) => Ok(UrPayload::Chunk(chunks.remove(0))),
+    (false, 0) => Ok(UrPayload::Message(message)),
+    (false, _) => Ok(UrPayload::Secured { message, chunks }),
+    (true, _) => Err(Error::UnknownValue),
+  }
+}
+
+fn decoding_error(e: impl std::fmt::Debug) -> Error {
+  Error::DecodingError(format!("{:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::secure_restore::{restore_message, secure_message};
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_restore_message_from_ur_parts() {
+    // given
+    let conf = crate::shamir::ChunksConfiguration {
+      required: 2,
+      spare: 1,
+    };
+    let secured = secure_message("Hello World!".into(), Some(20), conf, None).unwrap();
+    let items = [secured.encrypted_message.clone(), secured.chunks.clone()].concat();
+    let parts = encode_ur(items, 60, 1, 30).unwrap();
+    let (_, count) = sequence(&parts[0]).unwrap();
+    let mut decoder = UrDecoder::new();
+
+    // when
+    let received: Vec<bool> = parts[..count as usize]
+      .iter()
+      .rev()
+      .map(|part| decoder.receive(part.clone()).unwrap())
+      .collect();
+    let mut items = decoder.items().unwrap().unwrap();
+    let chunks = items.split_off(secured.encrypted_message.len());
+    let restored = restore_message(items, chunks).unwrap();
+    let chunk = encode_ur(secured.chunks[..1].to_vec(), 1000, 1, 10).unwrap();
+
+    // then
+    assert_eq!(received.iter().filter(|complete| **complete).count(), 1);
+    assert_eq!(received.last(), Some(&true));
+    assert!(parts[0].starts_with("ur:icod-secured/1-"));
+    assert_eq!(decoder.progress(), 1.0);
+    assert_eq!(restored, "Hello World!");
+    assert_eq!(chunk.len(), 1);
+    assert_eq!(sequence(&chunk[0]), None);
+    assert_eq!(
+      encode_ur(secured.chunks[..2].to_vec(), 1000, 1, 10),
+      Err(Error::UnknownValue)
+    );
+  }
+}
@@ -0,0 +1,176 @@
+//! Plaintext compression.
+//!
+//! The [EncryptionKeyVersion::V2] scheme compresses the plaintext before
+//! encrypting it, which reduces the number of QR codes the encrypted message
+//! has to be split into.
+//!
+//! Since the compression ratio depends on the content, the compressed data is
+//! padded using the `Padmé` scheme, which leaks at most `O(log log L)` bits of
+//! information about the length `L` of the compressed data.
+
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+
+use super::{EncryptionKeyVersion, Error, AAD};
+
+/// Compression algorithm applied to the plaintext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  /// The data is stored as-is.
+  ///
+  /// Used in case compressing the data does not reduce it's size.
+  None,
+  /// Raw `DEFLATE` stream.
+  Deflate,
+}
+
+impl Compression {
+  /// Return the byte representation of the algorithm.
+  pub fn to_byte(self) -> u8 {
+    match self {
+      Compression::None => 0u8,
+      Compression::Deflate => 1u8,
+    }
+  }
+
+  /// Attempt to read the algorithm from it's byte representation.
+  pub fn from_byte(byte: u8) -> Option<Self> {
+    match byte {
+      0 => Some(Compression::None),
+      1 => Some(Compression::Deflate),
+      _ => None,
+    }
+  }
+}
+
+/// Byte size of the compressed data length prefix.
+const LENGTH_SIZE: usize = 4;
+
+/// Compress and pad given data.
+///
+/// The output is a concatenation of 4 bytes of compressed data length (Big Endian),
+/// the compressed data and zeroes padding it to [padded_size].
+pub(crate) fn compress_and_pad(data: &[u8]) -> Result<(Compression, Vec<u8>), Error> {
+  let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::best());
+  encoder.write_all(data)?;
+  let mut compressed = encoder.finish()?;
+
+  let (compression, payload) = if compressed.len() < data.len() {
+    (Compression::Deflate, &*compressed)
+  } else {
+    (Compression::None, data)
+  };
+  let len = u32::try_from(payload.len()).map_err(|_| Error::EncryptionError)?;
+  let mut out = Vec::with_capacity(padded_size(LENGTH_SIZE + payload.len()));
+  out.extend_from_slice(&len.to_be_bytes());
+  out.extend_from_slice(payload);
+  out.resize(padded_size(out.len()), 0);
+  crate::wipe(&mut compressed);
+
+  Ok((compression, out))
+}
+
+/// Strip the padding and decompress the data produced by [compress_and_pad].
+pub(crate) fn unpad_and_decompress(
+  compression: Compression,
+  padded: &[u8],
+) -> Result<Vec<u8>, Error> {
+  if padded.len() < LENGTH_SIZE {
+    return Err(Error::Compression);
+  }
+  let (len, rest) = padded.split_at(LENGTH_SIZE);
+  let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+  if rest.len() < len {
+    return Err(Error::Compression);
+  }
+  let payload = &rest[..len];
+
+  match compression {
+    Compression::None => Ok(payload.to_vec()),
+    Compression::Deflate => {
+      let mut out = vec![];
+      DeflateDecoder::new(payload)
+        .read_to_end(&mut out)
+        .map_err(|_| Error::Compression)?;
+      Ok(out)
+    }
+  }
+}
+
+/// Additional Authenticated Data binding the scheme version and compression algorithm.
+pub(crate) fn aad(compression: Compression) -> Vec<u8> {
+  let mut aad = AAD.to_vec();
+  aad.push(EncryptionKeyVersion::V2.to_byte());
+  aad.push(compression.to_byte());
+  aad
+}
+
+/// Return the size of `len` bytes padded according to the `Padmé` scheme.
+///
+/// The padded size only keeps `floor(log2(floor(log2(len)))) + 1` most significant
+/// bits of `len`, rounding up.
+pub fn padded_size(len: usize) -> usize {
+  if len < 2 {
+    return len;
+  }
+  let e = usize::BITS - 1 - len.leading_zeros();
+  let s = u32::BITS - e.leading_zeros();
+  let last_bits = e - s;
+  let mask = (1usize << last_bits) - 1;
+  (len + mask) & !mask
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_compute_padded_size() {
+    assert_eq!(padded_size(0), 0);
+    assert_eq!(padded_size(1), 1);
+    assert_eq!(padded_size(9), 10);
+    assert_eq!(padded_size(100), 104);
+    assert_eq!(padded_size(101), 104);
+    assert_eq!(padded_size(1000), 1024);
+    assert_eq!(padded_size(1_000_000), 1_015_808);
+  }
+
+  #[test]
+  fn should_compress_and_pad() {
+    let data = "All work and no play makes Jack a dull boy. ".repeat(20);
+
+    let (compression, padded) = compress_and_pad(data.as_bytes()).unwrap();
+    let decompressed = unpad_and_decompress(compression, &padded).unwrap();
+
+    assert_eq!(compression, Compression::Deflate);
+    assert!(padded.len() < data.len() / 5, "Len: {}", padded.len());
+    assert_eq!(padded.len(), padded_size(padded.len()));
+    assert_eq!(decompressed, data.as_bytes());
+  }
+
+  #[test]
+  fn should_not_compress_incompressible_data() {
+    let data = [1u8, 2, 3];
+
+    let (compression, padded) = compress_and_pad(&data).unwrap();
+    let decompressed = unpad_and_decompress(compression, &padded).unwrap();
+
+    assert_eq!(compression, Compression::None);
+    assert_eq!(padded, vec![0, 0, 0, 3, 1, 2, 3]);
+    assert_eq!(decompressed, data);
+  }
+
+  #[test]
+  fn should_reject_invalid_length() {
+    assert_eq!(
+      unpad_and_decompress(Compression::None, &[0, 0, 0, 5, 1]),
+      Err(Error::Compression)
+    );
+    assert_eq!(
+      unpad_and_decompress(Compression::Deflate, &[0, 0]),
+      Err(Error::Compression)
+    );
+  }
+}
@@ -0,0 +1,441 @@
+//! Plaintext container.
+//!
+//! The encryption functions treat the message as opaque bytes. To store
+//! more than a single text (i.e. scanned documents, key files or photos)
+//! the entries are first wrapped into a versioned [Container] which is then
+//! encrypted as a regular [super::Message].
+//!
+//! Messages created before the container was introduced are plain UTF-8
+//! strings. [Container::from_plaintext] handles both formats, so such
+//! messages are read as a container with a single text entry.
+
+use crate::Bytes;
+
+/// A specific byte sequence used to identify the encoding of [Container].
+///
+/// The sequence is used to tell apart the container from legacy plain text messages.
+pub const CONTAINER_ENCODING_MAGIC_SEQUENCE: &[u8] = b"icodm";
+
+/// Container version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerVersion {
+  /// A list of text & file entries.
+  V0,
+}
+
+/// An error which may occur during decoding of the [Container].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ContainerError {
+  /// The byte input is missing [CONTAINER_ENCODING_MAGIC_SEQUENCE] prefix.
+  #[error("Given data does not look like a container.")]
+  MissingMagicBytes,
+  /// The byte input has invalid version identifier.
+  #[error("The version of the container is invalid.")]
+  InvalidVersion,
+  /// The container is truncated or has some trailing data.
+  #[error("The container is malformed: {0}")]
+  MalformedData(&'static str),
+  /// One of the strings (names or text content) is not valid UTF-8.
+  #[error("The container contains invalid UTF-8 string.")]
+  InvalidUtf8,
+  /// Some of the values is too long to be encoded.
+  #[error("The container has too many entries or some of them are too big.")]
+  DataTooBig,
+}
+
+/// Type of the entry stored in the [Container].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+  /// UTF-8 text note.
+  Text,
+  /// A binary file.
+  File {
+    /// Original name of the file.
+    filename: String,
+    /// MIME type of the file content.
+    mime_type: String,
+  },
+}
+
+/// A single named entry of the [Container].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  name: String,
+  kind: EntryKind,
+  data: Bytes,
+}
+
+impl Entry {
+  /// Create a new text entry.
+  pub fn text(name: impl Into<String>, text: &str) -> Self {
+    Self {
+      name: name.into(),
+      kind: EntryKind::Text,
+      data: Bytes::from_slice(text.as_bytes()),
+    }
+  }
+
+  /// Create a new file entry.
+  pub fn file(
+    name: impl Into<String>,
+    filename: impl Into<String>,
+    mime_type: impl Into<String>,
+    data: impl Into<Bytes>,
+  ) -> Self {
+    Self {
+      name: name.into(),
+      kind: EntryKind::File {
+        filename: filename.into(),
+        mime_type: mime_type.into(),
+      },
+      data: data.into(),
+    }
+  }
+
+  /// Name of the entry.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Type of the entry.
+  pub fn kind(&self) -> &EntryKind {
+    &self.kind
+  }
+
+  /// Raw content of the entry.
+  pub fn data(&self) -> &Bytes {
+    &self.data
+  }
+
+  /// Content of the text entry.
+  ///
+  /// Returns `None` for file entries.
+  pub fn as_text(&self) -> Option<&str> {
+    match self.kind {
+      // text entries are validated during decoding
+      EntryKind::Text => std::str::from_utf8(&self.data).ok(),
+      EntryKind::File { .. } => None,
+    }
+  }
+
+  /// Byte size of the entry content.
+  pub fn size(&self) -> usize {
+    self.data.len()
+  }
+}
+
+/// A versioned collection of named entries stored inside the encrypted message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+  version: ContainerVersion,
+  entries: Vec<Entry>,
+}
+
+impl Container {
+  /// Create a new container with given entries.
+  pub fn new(entries: Vec<Entry>) -> Self {
+    Self {
+      version: ContainerVersion::V0,
+      entries,
+    }
+  }
+
+  /// Return the entries of the container.
+  pub fn entries(&self) -> &[Entry] {
+    &self.entries
+  }
+
+  /// Convert the container into the list of it's entries.
+  pub fn into_entries(self) -> Vec<Entry> {
+    self.entries
+  }
+
+  /// Clear out the content of all entries.
+  pub fn wipe(&mut self) {
+    for entry in &mut self.entries {
+      entry.data.wipe();
+    }
+  }
+
+  /// Interpret decrypted message data as a container.
+  ///
+  /// Data that can't be decoded as a container is considered a legacy message
+  /// and is returned as a single, unnamed entry (text if it's a valid UTF-8 string,
+  /// file otherwise). This includes legacy text which merely starts with
+  /// the [CONTAINER_ENCODING_MAGIC_SEQUENCE].
+  pub fn from_plaintext(data: &[u8]) -> Self {
+    Self::decode(data).unwrap_or_else(|_| {
+      let entry = match std::str::from_utf8(data) {
+        Ok(text) => Entry::text("", text),
+        Err(_) => Entry::file("", "", "application/octet-stream", Bytes::from_slice(data)),
+      };
+      Self::new(vec![entry])
+    })
+  }
+
+  /// Encode the container into a vector of bytes.
+  ///
+  /// All lengths are Big Endian. The container will fail to encode if it has more than
+  /// `u16::MAX` entries, any of the strings is longer than `u16::MAX` bytes
+  /// or any of the entries is larger than `u32::MAX` bytes.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | magic byte sequence (5 bytes)  |
+  /// | (b"icodm")                     |
+  /// +--------------------------------+
+  /// | version (1 byte)               |
+  /// +--------------------------------+
+  /// | number of entries (2 bytes)    |
+  /// +--------------------------------+
+  /// | entries (variable length)      |
+  /// +--------------------------------+
+  /// ```
+  ///
+  /// Every entry is encoded as:
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | kind (1 byte)                  | // 0 - text, 1 - file
+  /// +--------------------------------+
+  /// | name (2 bytes length + data)   |
+  /// +--------------------------------+
+  /// | filename (2 bytes length + data| // only for files
+  /// +--------------------------------+
+  /// | MIME type (2 bytes length+data)| // only for files
+  /// +--------------------------------+
+  /// | size (4 bytes)                 |
+  /// +--------------------------------+
+  /// | content (variable length)      |
+  /// +--------------------------------+
+  /// ```
+  pub fn try_encode(&self) -> Result<Bytes, ContainerError> {
+    fn push_str(out: &mut Vec<u8>, s: &str) -> Result<(), ContainerError> {
+      let len = u16::try_from(s.len()).map_err(|_| ContainerError::DataTooBig)?;
+      out.extend_from_slice(&len.to_be_bytes());
+      out.extend_from_slice(s.as_bytes());
+      Ok(())
+    }
+
+    let entries = u16::try_from(self.entries.len()).map_err(|_| ContainerError::DataTooBig)?;
+    let mut out = vec![];
+    out.extend_from_slice(CONTAINER_ENCODING_MAGIC_SEQUENCE);
+    out.push(match self.version {
+      ContainerVersion::V0 => 0u8,
+    });
+    out.extend_from_slice(&entries.to_be_bytes());
+    for entry in &self.entries {
+      match entry.kind {
+        EntryKind::Text => {
+          out.push(0);
+          push_str(&mut out, &entry.name)?;
+        }
+        EntryKind::File {
+          ref filename,
+          ref mime_type,
+        } => {
+          out.push(1);
+          push_str(&mut out, &entry.name)?;
+          push_str(&mut out, filename)?;
+          push_str(&mut out, mime_type)?;
+        }
+      }
+      let size = u32::try_from(entry.data.len()).map_err(|_| ContainerError::DataTooBig)?;
+      out.extend_from_slice(&size.to_be_bytes());
+      out.extend_from_slice(&entry.data);
+    }
+    Ok(Bytes::from(out))
+  }
+
+  /// Encode the container into a vector of bytes.
+  ///
+  /// See [Container::try_encode] for details.
+  ///
+  /// Panics in case the container exceeds encoding limits.
+  pub fn encode(&self) -> Bytes {
+    self
+      .try_encode()
+      .expect("The container exceeds maximal encoding size.")
+  }
+
+  /// Attempt to decode the [Container] from given bytes.
+  pub fn decode(data: &[u8]) -> Result<Self, ContainerError> {
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+      fn bytes(&mut self, len: usize) -> Result<&'a [u8], ContainerError> {
+        if self.0.len() < len {
+          return Err(ContainerError::MalformedData("Unexpected end of data."));
+        }
+        let (read, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(read)
+      }
+
+      fn u8(&mut self) -> Result<u8, ContainerError> {
+        Ok(self.bytes(1)?[0])
+      }
+
+      fn u16(&mut self) -> Result<u16, ContainerError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+      }
+
+      fn u32(&mut self) -> Result<u32, ContainerError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+      }
+
+      fn string(&mut self) -> Result<String, ContainerError> {
+        let len = self.u16()? as usize;
+        let s = std::str::from_utf8(self.bytes(len)?).map_err(|_| ContainerError::InvalidUtf8)?;
+        Ok(s.into())
+      }
+    }
+
+    let data = data
+      .strip_prefix(CONTAINER_ENCODING_MAGIC_SEQUENCE)
+      .ok_or(ContainerError::MissingMagicBytes)?;
+    let mut reader = Reader(data);
+    let version = match reader.u8() {
+      Ok(0) => ContainerVersion::V0,
+      _ => return Err(ContainerError::InvalidVersion),
+    };
+
+    let len = reader.u16()? as usize;
+    let mut entries = Vec::with_capacity(len);
+    for _ in 0..len {
+      let kind = reader.u8()?;
+      let name = reader.string()?;
+      let kind = match kind {
+        0 => EntryKind::Text,
+        1 => EntryKind::File {
+          filename: reader.string()?,
+          mime_type: reader.string()?,
+        },
+        _ => return Err(ContainerError::MalformedData("Unknown entry kind.")),
+      };
+      let size = reader.u32()? as usize;
+      let content = reader.bytes(size)?;
+      if kind == EntryKind::Text && std::str::from_utf8(content).is_err() {
+        return Err(ContainerError::InvalidUtf8);
+      }
+      entries.push(Entry {
+        name,
+        kind,
+        data: Bytes::from_slice(content),
+      });
+    }
+
+    if !reader.0.is_empty() {
+      return Err(ContainerError::MalformedData("Unexpected trailing data."));
+    }
+
+    Ok(Self { version, entries })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{decrypt_message, encrypt_message, Message, MessageEncryptionKey};
+  use pretty_assertions::assert_eq;
+
+  fn container() -> Container {
+    Container::new(vec![
+      Entry::text("note", "Hello World!"),
+      Entry::file(
+        "key",
+        "id_ed25519",
+        "application/octet-stream",
+        vec![0u8, 159, 146, 150],
+      ),
+    ])
+  }
+
+  #[test]
+  fn should_encode_and_decode_container() {
+    // given
+    let container = container();
+
+    // when
+    let encoded = container.encode();
+    let decoded = Container::decode(&encoded).unwrap();
+
+    // then
+    assert_eq!(
+      format!("{:?}", encoded),
+      r#"Bytes("69636f646d0000020000046e6f74650000000c48656c6c6f20576f726c64210100036b6579000a69645f6564323535313900186170706c69636174696f6e2f6f637465742d73747265616d00000004009f9296")"#
+    );
+    assert_eq!(decoded, container);
+    assert_eq!(decoded.entries()[0].as_text(), Some("Hello World!"));
+    assert_eq!(decoded.entries()[1].as_text(), None);
+    assert_eq!(decoded.entries()[1].size(), 4);
+  }
+
+  #[test]
+  fn should_encrypt_binary_container() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; 32]);
+    let container = container();
+
+    // when
+    let encrypted = encrypt_message(&key, &Message::from_container(&container)).unwrap();
+    let decrypted = decrypt_message(&key, &encrypted).unwrap();
+
+    // then
+    let restored = Container::from_plaintext(decrypted.data());
+    assert_eq!(restored, container);
+  }
+
+  #[test]
+  fn should_read_legacy_messages() {
+    let text = Container::from_plaintext(b"Hello World!");
+    assert_eq!(text, Container::new(vec![Entry::text("", "Hello World!")]));
+
+    let magic = Container::from_plaintext(b"icodm is the magic");
+    assert_eq!(
+      magic,
+      Container::new(vec![Entry::text("", "icodm is the magic")])
+    );
+
+    let binary = Container::from_plaintext(&[0, 159, 146, 150]);
+    assert_eq!(binary.entries()[0].as_text(), None);
+    assert_eq!(&**binary.entries()[0].data(), &[0, 159, 146, 150]);
+  }
+
+  #[test]
+  fn should_reject_malformed_containers() {
+    let encoded = container().encode();
+
+    let mut wrong_version = encoded.to_vec();
+    wrong_version[5] = 1;
+    assert_eq!(
+      Container::decode(&wrong_version),
+      Err(ContainerError::InvalidVersion)
+    );
+
+    let truncated = &encoded[..encoded.len() - 1];
+    assert_eq!(
+      Container::decode(truncated),
+      Err(ContainerError::MalformedData("Unexpected end of data."))
+    );
+
+    let mut trailing = encoded.to_vec();
+    trailing.push(0);
+    assert_eq!(
+      Container::decode(&trailing),
+      Err(ContainerError::MalformedData("Unexpected trailing data."))
+    );
+
+    let invalid_text = Container::new(vec![Entry {
+      name: "".into(),
+      kind: EntryKind::Text,
+      data: Bytes::from_slice(&[159, 146]),
+    }]);
+    assert_eq!(
+      Container::decode(&invalid_text.encode()),
+      Err(ContainerError::InvalidUtf8)
+    );
+  }
+}
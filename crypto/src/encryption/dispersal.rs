@@ -0,0 +1,409 @@
+//! Information dispersal of the encrypted message.
+//!
+//! Regularly every recipient keeps a complete copy of the [EncryptedMessage]
+//! next to their chunk. Instead, the encrypted message can be dispersed
+//! (Rabin's IDA, a systematic Reed-Solomon erasure code over `GF(256)`) into
+//! as many [MessageFragment]s as there are chunks, so that any `required` fragments
+//! are sufficient to reassemble the message. Each fragment is roughly `1/required`
+//! of the message size and no full copy has to be stored anywhere.
+//!
+//! The message is encoded as a single tagged part (see
+//! [EncryptedMessage::split_and_encode_tagged]), split into `required` pieces
+//! and every fragment is a point of polynomials passing through the pieces.
+//!
+//! Large fragments can be further split into parts (see [MessageFragment::split]),
+//! e.g. to fit into QR codes. All of the parts of a fragment are needed to use it.
+
+use super::{EncryptedMessage, EncryptedMessageError, MESSAGE_ID_SIZE};
+use crate::shamir::{issue::interpolate_at, ChunksConfiguration};
+use crate::Bytes;
+
+/// A specific byte sequence used to identify the encoding of [MessageFragment].
+pub const FRAGMENT_ENCODING_MAGIC_SEQUENCE: &[u8] = b"icodf";
+
+/// Byte size of the fragment header following the magic sequence.
+const FRAGMENT_HEADER_SIZE: usize = 1 + MESSAGE_ID_SIZE + 3 + 4;
+
+/// Byte size of the part index and total number of parts of split fragments.
+const PART_HEADER_SIZE: usize = 4;
+
+/// An error which may occur when reassembling the dispersed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DispersalError {
+  /// The fragment could not be decoded.
+  #[error("The fragment is malformed.")]
+  MalformedFragment,
+  /// Less than `required` distinct fragments were given.
+  #[error("Not enough fragments to reassemble the message.")]
+  NotEnoughFragments,
+  /// The fragments belong to different messages or dispersal configurations.
+  #[error("The fragments belong to different messages.")]
+  InconsistentFragments,
+  /// The reassembled message is invalid.
+  #[error("{0}")]
+  Message(#[from] EncryptedMessageError),
+}
+
+/// A fragment of the dispersed [EncryptedMessage].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageFragment {
+  message_id: [u8; MESSAGE_ID_SIZE],
+  required: u8,
+  total: u8,
+  index: u8,
+  payload_len: u32,
+  part_index: u16,
+  parts_total: u16,
+  data: Bytes,
+}
+
+impl MessageFragment {
+  /// Index of the fragment (0-based).
+  pub fn index(&self) -> u8 {
+    self.index
+  }
+
+  /// Identifier of the dispersed message (see [EncryptedMessage::message_id]).
+  pub fn message_id(&self) -> &[u8; MESSAGE_ID_SIZE] {
+    &self.message_id
+  }
+
+  /// Number of fragments required to reassemble the message.
+  pub fn required(&self) -> usize {
+    self.required as usize
+  }
+
+  /// Total number of fragments.
+  pub fn total(&self) -> usize {
+    self.total as usize
+  }
+
+  /// Index of the part of split fragment (0-based).
+  pub fn part_index(&self) -> u16 {
+    self.part_index
+  }
+
+  /// Total number of parts of the fragment (`1` if not split).
+  pub fn parts_total(&self) -> u16 {
+    self.parts_total
+  }
+
+  /// Split the fragment into parts carrying at most `split` bytes of data each.
+  ///
+  /// The parts are joined back by [reassemble], but all of them are needed
+  /// to use the fragment. Note the parts are enlarged if the fragment would
+  /// be split into more than `u16::MAX` of them.
+  pub fn split(self, split: Option<usize>) -> Vec<MessageFragment> {
+    let part_len = match split {
+      Some(split) if self.parts_total == 1 && split < self.data.len() => split
+        .max(1)
+        .max(self.data.len().div_ceil(u16::MAX as usize)),
+      _ => return vec![self],
+    };
+    let parts_total = self.data.len().div_ceil(part_len) as u16;
+    self
+      .data
+      .chunks(part_len)
+      .enumerate()
+      .map(|(part_index, data)| MessageFragment {
+        part_index: part_index as u16,
+        parts_total,
+        data: Bytes::from_slice(data),
+        ..self.clone()
+      })
+      .collect()
+  }
+
+  /// Encode the fragment.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | magic byte sequence (5 bytes)  |
+  /// | (b"icodf")                     |
+  /// +--------------------------------+
+  /// | version (1 byte)               |
+  /// +--------------------------------+
+  /// | message id (4 bytes)           |
+  /// +--------------------------------+
+  /// | required, total, index         | // 1 byte each
+  /// +--------------------------------+
+  /// | payload length (4 bytes)       | // Big Endian
+  /// +--------------------------------+
+  /// | part index, parts total        | // 2 bytes each, Big Endian
+  /// +--------------------------------+ // (only version `1`, split fragments)
+  /// | data (variable length)         |
+  /// +--------------------------------+
+  /// ```
+  pub fn encode(&self) -> Bytes {
+    let mut out = Vec::with_capacity(
+      FRAGMENT_ENCODING_MAGIC_SEQUENCE.len()
+        + FRAGMENT_HEADER_SIZE
+        + PART_HEADER_SIZE
+        + self.data.len(),
+    );
+    let split = self.parts_total > 1;
+    out.extend_from_slice(FRAGMENT_ENCODING_MAGIC_SEQUENCE);
+    out.push(u8::from(split));
+    out.extend_from_slice(&self.message_id);
+    out.extend_from_slice(&[self.required, self.total, self.index]);
+    out.extend_from_slice(&self.payload_len.to_be_bytes());
+    if split {
+      out.extend_from_slice(&self.part_index.to_be_bytes());
+      out.extend_from_slice(&self.parts_total.to_be_bytes());
+    }
+    out.extend_from_slice(&self.data);
+    out.into()
+  }
+
+  /// Decode the fragment produced by [MessageFragment::encode].
+  pub fn decode(data: &[u8]) -> Result<Self, DispersalError> {
+    let data = data
+      .strip_prefix(FRAGMENT_ENCODING_MAGIC_SEQUENCE)
+      .ok_or(DispersalError::MalformedFragment)?;
+    let header_size = match data.first() {
+      Some(0) => FRAGMENT_HEADER_SIZE,
+      Some(1) => FRAGMENT_HEADER_SIZE + PART_HEADER_SIZE,
+      _ => return Err(DispersalError::MalformedFragment),
+    };
+    if data.len() <= header_size {
+      return Err(DispersalError::MalformedFragment);
+    }
+    let (header, data) = data.split_at(header_size);
+    let mut message_id = [0u8; MESSAGE_ID_SIZE];
+    message_id.copy_from_slice(&header[1..1 + MESSAGE_ID_SIZE]);
+    let header = &header[1 + MESSAGE_ID_SIZE..];
+    let (required, total, index) = (header[0], header[1], header[2]);
+    if required == 0 || total < required || index >= total {
+      return Err(DispersalError::MalformedFragment);
+    }
+    let (part_index, parts_total) = match header.get(7..) {
+      Some([a, b, c, d]) => (u16::from_be_bytes([*a, *b]), u16::from_be_bytes([*c, *d])),
+      _ => (0, 1),
+    };
+    if parts_total == 0 || part_index >= parts_total {
+      return Err(DispersalError::MalformedFragment);
+    }
+    Ok(Self {
+      message_id,
+      required,
+      total,
+      index,
+      payload_len: u32::from_be_bytes([header[3], header[4], header[5], header[6]]),
+      part_index,
+      parts_total,
+      data: Bytes::from_slice(data),
+    })
+  }
+}
+
+/// Disperse the encrypted message into `configuration.total()` fragments.
+///
+/// Any `configuration.required()` of the fragments are sufficient to reassemble it.
+/// Note the signer of the message (see [EncryptedMessage::split_and_encode_signed])
+/// is not carried over to the fragments.
+pub fn disperse(
+  message: EncryptedMessage,
+  configuration: ChunksConfiguration,
+) -> Vec<MessageFragment> {
+  let message_id = message.message_id();
+  let mut payload = message
+    .split_and_encode_tagged(None, None)
+    .into_iter()
+    .next()
+    .expect("A message without split is always a single part; qed")
+    .to_vec();
+  let payload_len = payload.len() as u32;
+  let required = configuration.required();
+  let piece_len = payload.len().div_ceil(required);
+  payload.resize(piece_len * required, 0);
+
+  // The pieces are points at x = 1..=required.
+  let pieces: Vec<Vec<u8>> = payload
+    .chunks(piece_len)
+    .enumerate()
+    .map(|(idx, piece)| [&[idx as u8 + 1], piece].concat())
+    .collect();
+  let pieces: Vec<&[u8]> = pieces.iter().map(|p| &**p).collect();
+
+  (0..configuration.total())
+    .map(|index| {
+      let point = interpolate_at(&pieces, index as u8 + 1);
+      MessageFragment {
+        message_id,
+        required: required as u8,
+        total: configuration.total() as u8,
+        index: index as u8,
+        payload_len,
+        part_index: 0,
+        parts_total: 1,
+        data: Bytes::from_slice(&point[1..]),
+      }
+    })
+    .collect()
+}
+
+/// Reassemble the encrypted message from at least `required` fragments.
+///
+/// Split fragments are only used if all of their parts are given.
+pub fn reassemble(fragments: &[MessageFragment]) -> Result<EncryptedMessage, DispersalError> {
+  let first = fragments
+    .first()
+    .ok_or(DispersalError::NotEnoughFragments)?;
+  let consistent = fragments.iter().all(|f| {
+    f.message_id == first.message_id
+      && f.required == first.required
+      && f.total == first.total
+      && f.payload_len == first.payload_len
+  });
+  if !consistent {
+    return Err(DispersalError::InconsistentFragments);
+  }
+  let fragments = join_parts(fragments);
+  let first = fragments
+    .first()
+    .ok_or(DispersalError::NotEnoughFragments)?;
+  if fragments.iter().any(|f| f.data.len() != first.data.len()) {
+    return Err(DispersalError::InconsistentFragments);
+  }
+
+  let mut unique = Vec::<&MessageFragment>::with_capacity(first.required());
+  for fragment in &fragments {
+    if unique.len() < first.required() && unique.iter().all(|f| f.index != fragment.index) {
+      unique.push(fragment);
+    }
+  }
+  if unique.len() < first.required() {
+    return Err(DispersalError::NotEnoughFragments);
+  }
+
+  let points: Vec<Vec<u8>> = unique
+    .iter()
+    .map(|f| [&[f.index + 1], &*f.data].concat())
+    .collect();
+  let points: Vec<&[u8]> = points.iter().map(|p| &**p).collect();
+  let mut payload = Vec::with_capacity(first.data.len() * first.required());
+  for x in 1..=first.required {
+    payload.extend_from_slice(&interpolate_at(&points, x)[1..]);
+  }
+  payload.truncate(first.payload_len as usize);
+
+  let message = EncryptedMessage::collate_from_parts(vec![payload.into()])?;
+  if &message.message_id() != first.message_id() {
+    return Err(DispersalError::InconsistentFragments);
+  }
+  Ok(message)
+}
+
+/// Join the parts of split fragments, skipping the fragments with missing parts.
+fn join_parts(fragments: &[MessageFragment]) -> Vec<MessageFragment> {
+  let mut indices: Vec<u8> = fragments.iter().map(|f| f.index).collect();
+  indices.sort_unstable();
+  indices.dedup();
+  indices
+    .into_iter()
+    .filter_map(|index| {
+      let parts: Vec<&MessageFragment> = fragments.iter().filter(|f| f.index == index).collect();
+      let parts_total = parts[0].parts_total;
+      let mut data = vec![];
+      for part_index in 0..parts_total {
+        let part = parts
+          .iter()
+          .find(|f| f.parts_total == parts_total && f.part_index == part_index)?;
+        data.extend_from_slice(&part.data);
+      }
+      Some(MessageFragment {
+        part_index: 0,
+        parts_total: 1,
+        data: data.into(),
+        ..parts[0].clone()
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn message() -> EncryptedMessage {
+    crate::encryption::tests::message(100)
+  }
+
+  #[test]
+  fn should_reassemble_from_any_required_fragments() {
+    // given
+    let fragments = disperse(message(), ChunksConfiguration::new(3, 2).unwrap());
+    let encoded: Vec<_> = fragments.iter().map(|f| f.encode()).collect();
+
+    // when
+    let decoded: Vec<_> = encoded
+      .iter()
+      .map(|f| MessageFragment::decode(f).unwrap())
+      .collect();
+    let from_last = reassemble(&decoded[2..5]).unwrap();
+    let from_mixed = reassemble(&[
+      decoded[4].clone(),
+      decoded[0].clone(),
+      decoded[0].clone(),
+      decoded[3].clone(),
+    ])
+    .unwrap();
+
+    // then
+    assert_eq!(decoded, fragments);
+    assert_eq!(fragments.len(), 5);
+    assert!(fragments[0].data.len() < message().data.len() / 2);
+    assert_eq!(from_last, message());
+    assert_eq!(from_mixed, message());
+  }
+
+  #[test]
+  fn should_reassemble_from_split_fragments() {
+    // given
+    let fragments = disperse(message(), ChunksConfiguration::new(2, 1).unwrap());
+    let parts: Vec<_> = fragments
+      .iter()
+      .flat_map(|f| f.clone().split(Some(16)))
+      .map(|p| MessageFragment::decode(&p.encode()).unwrap())
+      .collect();
+    let per_fragment = parts.len() / 3;
+
+    // when
+    let reassembled = reassemble(&parts[per_fragment..]).unwrap();
+    let mixed = reassemble(&[&parts[..per_fragment], &[fragments[2].clone()]].concat()).unwrap();
+    let incomplete = reassemble(&parts[1..2 * per_fragment]);
+
+    // then
+    assert!(per_fragment > 1);
+    assert_eq!(parts[1].part_index(), 1);
+    assert_eq!(parts[1].parts_total() as usize, per_fragment);
+    assert_eq!(parts[1].encode()[5], 1);
+    assert_eq!(reassembled, message());
+    assert_eq!(mixed, message());
+    assert_eq!(incomplete, Err(DispersalError::NotEnoughFragments));
+    assert_eq!(fragments[0].clone().split(None), vec![fragments[0].clone()]);
+  }
+
+  #[test]
+  fn should_reject_insufficient_or_inconsistent_fragments() {
+    let fragments = disperse(message(), ChunksConfiguration::new(2, 1).unwrap());
+    let other = disperse(
+      EncryptedMessage::new(b"other".to_vec(), *b"test nonce x").unwrap(),
+      ChunksConfiguration::new(2, 1).unwrap(),
+    );
+
+    assert_eq!(
+      reassemble(&[fragments[1].clone(), fragments[1].clone()]),
+      Err(DispersalError::NotEnoughFragments)
+    );
+    assert_eq!(
+      reassemble(&[fragments[0].clone(), other[1].clone()]),
+      Err(DispersalError::InconsistentFragments)
+    );
+    assert_eq!(
+      MessageFragment::decode(&fragments[0].encode()[..10]),
+      Err(DispersalError::MalformedFragment)
+    );
+  }
+}
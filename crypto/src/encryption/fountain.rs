@@ -0,0 +1,508 @@
+//! Fountain-coded message frames.
+//!
+//! [EncryptedMessage::split_and_encode] produces a fixed set of parts and all
+//! of them are required to restore the message. When the message is transferred
+//! using animated QR codes, it's more convenient to loop an endless stream of
+//! frames and let the receiver pick up any sufficient subset of them.
+//!
+//! The message (encoded as a single tagged part, see
+//! [EncryptedMessage::split_and_encode_tagged]) is split into `K` fragments of
+//! equal length. The first `K` frames carry the fragments as-is, every next
+//! frame is a XOR of a pseudo-random subset of fragments (an `LT` code with the
+//! ideal soliton degree distribution). The subset is derived from the message
+//! identifier and the frame sequence number, so it doesn't have to be transmitted.
+//!
+//! The [FountainDecoder] is incremental: frames can be fed as they are scanned
+//! and the message is available as soon as all fragments are recovered.
+//!
+//! Note the frames are not signed, so the signer of the message parts
+//! (see [EncryptedMessage::split_and_encode_signed]) is not carried over.
+
+use std::collections::BTreeSet;
+
+use super::{EncryptedMessage, EncryptedMessageError, MESSAGE_ID_SIZE};
+use crate::Bytes;
+
+/// Byte size of the frame header.
+///
+/// The header consists of the sequence number, the number of fragments, the payload
+/// length (4 bytes each, Big Endian) and the message identifier.
+pub const FRAME_HEADER_SIZE: usize = 12 + MESSAGE_ID_SIZE;
+
+/// Maximal number of fragments of a single message.
+///
+/// The decoders ([FountainDecoder] and [crate::ur::UrDecoder]) allocate and shuffle
+/// tables of this size, so the number of fragments taken from untrusted frames or parts
+/// has to be bounded. The encoders enlarge the fragments if needed to stay within
+/// the limit, while values longer than [MAX_PAYLOAD_LEN] are rejected when decoding.
+pub const MAX_FRAGMENTS_COUNT: u32 = 4096;
+
+/// Maximal byte length of the message transferred as frames.
+pub const MAX_PAYLOAD_LEN: usize = 1 << 24;
+
+/// An error which may occur when decoding fountain frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum FountainError {
+  /// The frame could not be decoded.
+  #[error("The frame is malformed.")]
+  MalformedFrame,
+  /// The frame belongs to a different message than the previously received ones.
+  #[error("The frame belongs to a different message.")]
+  ForeignFrame,
+  /// The recovered message is invalid.
+  #[error("{0}")]
+  Message(#[from] EncryptedMessageError),
+}
+
+/// Produces an endless stream of frames of the [EncryptedMessage].
+pub struct FountainEncoder {
+  message_id: [u8; MESSAGE_ID_SIZE],
+  payload: Bytes,
+  payload_len: usize,
+  fragment_len: usize,
+  fragments_count: u32,
+  next_seq: u32,
+}
+
+impl FountainEncoder {
+  /// Prepare the message to be sent as frames carrying `fragment_len` bytes of data.
+  ///
+  /// See [MAX_FRAGMENTS_COUNT] for the bounds of the fragments.
+  pub fn new(message: EncryptedMessage, fragment_len: usize) -> Self {
+    let message_id = message.message_id();
+    let mut payload = message
+      .split_and_encode_tagged(None, None)
+      .into_iter()
+      .next()
+      .expect("A message without split is always a single part; qed")
+      .to_vec();
+    let fragment_len = fragment_len
+      .max(payload.len().div_ceil(MAX_FRAGMENTS_COUNT as usize))
+      .max(1);
+    let fragments_count = payload.len().div_ceil(fragment_len);
+    let payload_len = payload.len();
+    payload.resize(fragments_count * fragment_len, 0);
+    Self {
+      message_id,
+      payload: payload.into(),
+      payload_len,
+      fragment_len,
+      fragments_count: fragments_count as u32,
+      next_seq: 0,
+    }
+  }
+
+  /// Number of fragments the message is split into.
+  ///
+  /// At least this many frames are required to decode the message.
+  pub fn fragments_count(&self) -> u32 {
+    self.fragments_count
+  }
+
+  /// Return the next frame of the stream.
+  pub fn next_frame(&mut self) -> Bytes {
+    let frame = self.frame(self.next_seq);
+    self.next_seq = self.next_seq.wrapping_add(1);
+    frame
+  }
+
+  /// Return the frame with given sequence number.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | sequence number (4 bytes)      | // Big Endian
+  /// +--------------------------------+
+  /// | fragments count (4 bytes)      | // Big Endian
+  /// +--------------------------------+
+  /// | payload length (4 bytes)       | // Big Endian
+  /// +--------------------------------+
+  /// | message id (4 bytes)           |
+  /// +--------------------------------+
+  /// | mixed fragments (variable)     |
+  /// +--------------------------------+
+  /// ```
+  pub fn frame(&self, seq: u32) -> Bytes {
+    let mut out = Vec::with_capacity(FRAME_HEADER_SIZE + self.fragment_len);
+    out.extend_from_slice(&seq.to_be_bytes());
+    out.extend_from_slice(&self.fragments_count.to_be_bytes());
+    out.extend_from_slice(&(self.payload_len as u32).to_be_bytes());
+    out.extend_from_slice(&self.message_id);
+    let mut data = vec![0u8; self.fragment_len];
+    for index in fragment_indices(&self.message_id, seq, self.fragments_count) {
+      let start = index * self.fragment_len;
+      xor(&mut data, &self.payload[start..start + self.fragment_len]);
+    }
+    out.extend(data);
+    out.into()
+  }
+}
+
+/// Incrementally decodes the [EncryptedMessage] from fountain frames.
+#[derive(Default)]
+pub struct FountainDecoder {
+  header: Option<FrameHeader>,
+  fragments: PeelingDecoder,
+}
+
+impl FountainDecoder {
+  /// Create a new decoder.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feed the decoder with a single frame.
+  ///
+  /// Returns `true` if the message is fully recovered.
+  /// Duplicated frames are ignored.
+  pub fn receive(&mut self, frame: &[u8]) -> Result<bool, FountainError> {
+    let (header, data) = FrameHeader::decode(frame)?;
+    match self.header {
+      None => {
+        self.fragments = PeelingDecoder::new(header.fragments_count);
+        self.header = Some(header);
+      }
+      Some(ref expected) if !expected.is_same_message(&header) => {
+        return Err(FountainError::ForeignFrame);
+      }
+      Some(_) => {}
+    }
+
+    self.fragments.receive(header.seq, data.to_vec(), || {
+      fragment_indices(&header.message_id, header.seq, header.fragments_count)
+    });
+    Ok(self.is_complete())
+  }
+
+  /// Return `true` if all fragments of the message are recovered.
+  pub fn is_complete(&self) -> bool {
+    self.header.is_some() && self.fragments.is_complete()
+  }
+
+  /// Number of recovered fragments.
+  pub fn decoded_fragments(&self) -> u32 {
+    self.fragments.decoded_fragments()
+  }
+
+  /// Number of fragments of the message (`0` before the first frame is received).
+  pub fn fragments_count(&self) -> u32 {
+    self.header.map(|h| h.fragments_count).unwrap_or_default()
+  }
+
+  /// Return the recovered message, if it's complete.
+  pub fn message(&self) -> Option<Result<EncryptedMessage, FountainError>> {
+    let header = self.header.as_ref().filter(|_| self.is_complete())?;
+    let payload = self.fragments.payload(header.payload_len as usize)?;
+    Some(
+      EncryptedMessage::collate_from_parts(vec![payload.into()])
+        .map_err(FountainError::from)
+        .and_then(|message| {
+          if message.message_id() == header.message_id {
+            Ok(message)
+          } else {
+            Err(FountainError::ForeignFrame)
+          }
+        }),
+    )
+  }
+}
+
+/// Recovers the fragments of a fountain-coded value from XORs of their subsets.
+///
+/// Shared by [FountainDecoder] and the UR decoder (see [crate::ur]), which only
+/// differ in the encoding of the parts and the way the subsets are chosen.
+#[derive(Default)]
+pub(crate) struct PeelingDecoder {
+  fragments: Vec<Option<Vec<u8>>>,
+  decoded: usize,
+  pending: Vec<(BTreeSet<usize>, Vec<u8>)>,
+  received: BTreeSet<u32>,
+}
+
+impl PeelingDecoder {
+  /// Create a decoder of `count` fragments.
+  ///
+  /// The `count` must be bounded by [MAX_FRAGMENTS_COUNT].
+  pub(crate) fn new(count: u32) -> Self {
+    debug_assert!(count <= MAX_FRAGMENTS_COUNT);
+    Self {
+      fragments: vec![None; count as usize],
+      ..Default::default()
+    }
+  }
+
+  /// Feed the decoder with the part of given sequence number carrying
+  /// the XOR of fragments returned by `indices`.
+  ///
+  /// Duplicated parts are ignored.
+  pub(crate) fn receive(&mut self, seq: u32, data: Vec<u8>, indices: impl FnOnce() -> Vec<usize>) {
+    if self.is_complete() || !self.received.insert(seq) {
+      return;
+    }
+    self.pending.push((indices().into_iter().collect(), data));
+    self.reduce();
+  }
+
+  /// Return `true` if all fragments are recovered.
+  pub(crate) fn is_complete(&self) -> bool {
+    self.decoded == self.fragments.len()
+  }
+
+  /// Number of recovered fragments.
+  pub(crate) fn decoded_fragments(&self) -> u32 {
+    self.decoded as u32
+  }
+
+  /// Return the concatenated fragments truncated to `len` bytes, if complete.
+  pub(crate) fn payload(&self, len: usize) -> Option<Vec<u8>> {
+    if !self.is_complete() {
+      return None;
+    }
+    let mut payload: Vec<u8> = self.fragments.iter().flatten().flatten().copied().collect();
+    payload.truncate(len);
+    Some(payload)
+  }
+
+  /// Substitute the recovered fragments into pending parts until no progress is made.
+  fn reduce(&mut self) {
+    loop {
+      let mut progress = false;
+      let pending = std::mem::take(&mut self.pending);
+      for (mut indices, mut data) in pending {
+        let known: Vec<usize> = indices
+          .iter()
+          .copied()
+          .filter(|idx| self.fragments[*idx].is_some())
+          .collect();
+        for idx in known {
+          xor(
+            &mut data,
+            self.fragments[idx].as_ref().expect("Filtered above; qed"),
+          );
+          indices.remove(&idx);
+        }
+        match indices.len() {
+          0 => {}
+          1 => {
+            let idx = *indices.iter().next().expect("Checked length; qed");
+            self.fragments[idx] = Some(data);
+            self.decoded += 1;
+            progress = true;
+          }
+          _ => self.pending.push((indices, data)),
+        }
+      }
+      if !progress {
+        break;
+      }
+    }
+  }
+}
+
+/// The header of a frame produced by [FountainEncoder].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+  seq: u32,
+  fragments_count: u32,
+  payload_len: u32,
+  message_id: [u8; MESSAGE_ID_SIZE],
+  fragment_len: usize,
+}
+
+impl FrameHeader {
+  /// Sequence number of the frame.
+  pub fn seq(&self) -> u32 {
+    self.seq
+  }
+
+  /// Number of fragments the message is split into.
+  pub fn fragments_count(&self) -> u32 {
+    self.fragments_count
+  }
+
+  /// Identifier of the message (see [EncryptedMessage::message_id]).
+  pub fn message_id(&self) -> &[u8; MESSAGE_ID_SIZE] {
+    &self.message_id
+  }
+
+  /// Decode the header of given frame, returning it along with the frame data.
+  pub fn decode(frame: &[u8]) -> Result<(Self, &[u8]), FountainError> {
+    if frame.len() <= FRAME_HEADER_SIZE {
+      return Err(FountainError::MalformedFrame);
+    }
+    let (header, data) = frame.split_at(FRAME_HEADER_SIZE);
+    let u32_at = |pos: usize| {
+      u32::from_be_bytes([
+        header[pos],
+        header[pos + 1],
+        header[pos + 2],
+        header[pos + 3],
+      ])
+    };
+    let mut message_id = [0u8; MESSAGE_ID_SIZE];
+    message_id.copy_from_slice(&header[12..]);
+    let header = Self {
+      seq: u32_at(0),
+      fragments_count: u32_at(4),
+      payload_len: u32_at(8),
+      message_id,
+      fragment_len: data.len(),
+    };
+    let fragments_count = header.fragments_count as usize;
+    if fragments_count == 0
+      || header.fragments_count > MAX_FRAGMENTS_COUNT
+      || header.payload_len as usize > MAX_PAYLOAD_LEN
+      || (header.payload_len as usize) > fragments_count * data.len()
+      || (header.payload_len as usize) <= (fragments_count - 1) * data.len()
+    {
+      return Err(FountainError::MalformedFrame);
+    }
+    Ok((header, data))
+  }
+
+  fn is_same_message(&self, other: &Self) -> bool {
+    self.fragments_count == other.fragments_count
+      && self.payload_len == other.payload_len
+      && self.message_id == other.message_id
+      && self.fragment_len == other.fragment_len
+  }
+}
+
+/// Return the indices of fragments mixed into the frame with given sequence number.
+///
+/// The first `fragments_count` frames carry single fragments.
+fn fragment_indices(message_id: &[u8], seq: u32, fragments_count: u32) -> Vec<usize> {
+  let count = fragments_count as usize;
+  if seq < fragments_count {
+    return vec![seq as usize];
+  }
+  let mut rng = Prng::new(message_id, seq);
+  let degree = rng.degree(count);
+  // partial Fisher-Yates shuffle
+  let mut indices: Vec<usize> = (0..count).collect();
+  for i in 0..degree {
+    let j = i + (rng.next_u32() as usize) % (count - i);
+    indices.swap(i, j);
+  }
+  indices.truncate(degree);
+  indices
+}
+
+pub(crate) fn xor(out: &mut [u8], data: &[u8]) {
+  for (a, b) in out.iter_mut().zip(data) {
+    *a ^= b;
+  }
+}
+
+/// Deterministic pseudo-random generator based on `blake2b512`.
+struct Prng {
+  seed: Vec<u8>,
+  counter: u32,
+  block: [u8; 64],
+  pos: usize,
+}
+
+impl Prng {
+  fn new(message_id: &[u8], seq: u32) -> Self {
+    Self {
+      seed: [message_id, &seq.to_be_bytes()].concat(),
+      counter: 0,
+      block: [0u8; 64],
+      pos: 64,
+    }
+  }
+
+  fn next_u32(&mut self) -> u32 {
+    if self.pos + 4 > self.block.len() {
+      let hash = crate::blake2b512(&[&*self.seed, &self.counter.to_be_bytes()].concat());
+      self.block.copy_from_slice(hash.as_slice());
+      self.counter += 1;
+      self.pos = 0;
+    }
+    let out = &self.block[self.pos..self.pos + 4];
+    self.pos += 4;
+    u32::from_be_bytes([out[0], out[1], out[2], out[3]])
+  }
+
+  /// Sample the frame degree from the ideal soliton distribution.
+  ///
+  /// `P(1) = 1/K` and `P(d) = 1/(d(d-1))`, so the cumulative distribution
+  /// is `1/K + 1 - 1/d` for `d > 1`.
+  fn degree(&mut self, count: usize) -> usize {
+    let u = self.next_u32() as f64 / (u32::MAX as f64 + 1.0);
+    let first = 1.0 / count as f64;
+    if u < first {
+      return 1;
+    }
+    let degree = (1.0 / (1.0 + first - u)).floor() as usize + 1;
+    degree.min(count)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn message() -> EncryptedMessage {
+    crate::encryption::tests::message(200)
+  }
+
+  #[test]
+  fn should_decode_from_systematic_frames() {
+    // given
+    let mut encoder = FountainEncoder::new(message(), 30);
+    let mut decoder = FountainDecoder::new();
+
+    // when
+    let mut complete = false;
+    for _ in 0..encoder.fragments_count() {
+      complete = decoder.receive(&encoder.next_frame()).unwrap();
+    }
+
+    // then
+    assert!(complete);
+    assert_eq!(decoder.message(), Some(Ok(message())));
+  }
+
+  #[test]
+  fn should_decode_from_any_sufficient_subset_of_frames() {
+    // given
+    let encoder = FountainEncoder::new(message(), 20);
+    let mut decoder = FountainDecoder::new();
+
+    // when
+    // skip every 3rd frame of the stream
+    let mut seq = 0;
+    while !decoder.receive(&encoder.frame(seq)).unwrap() {
+      seq += if seq % 3 == 1 { 2 } else { 1 };
+      assert!(seq < 1_000, "Decoding does not converge.");
+    }
+
+    // then
+    assert_eq!(decoder.decoded_fragments(), encoder.fragments_count());
+    assert_eq!(decoder.message(), Some(Ok(message())));
+  }
+
+  #[test]
+  fn should_reject_foreign_and_malformed_frames() {
+    let encoder = FountainEncoder::new(message(), 30);
+    let other = FountainEncoder::new(
+      EncryptedMessage::new(b"other".to_vec(), *b"test nonce x").unwrap(),
+      30,
+    );
+    let mut decoder = FountainDecoder::new();
+
+    assert_eq!(decoder.receive(&encoder.frame(3)), Ok(false));
+    assert_eq!(decoder.receive(&encoder.frame(3)), Ok(false));
+    assert_eq!(
+      decoder.receive(&other.frame(0)),
+      Err(FountainError::ForeignFrame)
+    );
+    assert_eq!(
+      decoder.receive(&encoder.frame(0)[..FRAME_HEADER_SIZE]),
+      Err(FountainError::MalformedFrame)
+    );
+    assert_eq!(decoder.decoded_fragments(), 1);
+    assert!(decoder.message().is_none());
+  }
+}
@@ -0,0 +1,161 @@
+//! Authenticated message header.
+//!
+//! The schemes up to `V3` use a constant [AAD], so nothing stored along the
+//! ciphertext is authenticated by the cipher. The [EncryptionKeyVersion::V4]
+//! scheme prepends a [MessageHeader] to the ciphertext and binds it (along
+//! with the scheme version) into the Additional Authenticated Data.
+//!
+//! The header is stored in plain text, so it can be read before decryption
+//! (see [super::EncryptedMessage::unverified_header]), however it's only
+//! trustworthy once the message was successfully decrypted (see
+//! [super::decrypt_message_with_header]).
+
+use super::{EncryptionKeyVersion, AAD};
+
+/// Byte size of the testament identifier.
+pub const TESTAMENT_ID_SIZE: usize = 16;
+
+/// Byte size of the encoded [MessageHeader].
+pub const HEADER_SIZE: usize = 1 + TESTAMENT_ID_SIZE + 8;
+
+/// Format of the encrypted plaintext.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+  /// Opaque bytes (i.e. a legacy UTF-8 message).
+  #[default]
+  Raw,
+  /// Encoded [super::Container].
+  Container,
+}
+
+impl PayloadFormat {
+  /// Return the byte representation of the format.
+  pub fn to_byte(self) -> u8 {
+    match self {
+      PayloadFormat::Raw => 0u8,
+      PayloadFormat::Container => 1u8,
+    }
+  }
+
+  /// Attempt to read the format from it's byte representation.
+  pub fn from_byte(byte: u8) -> Option<Self> {
+    match byte {
+      0 => Some(PayloadFormat::Raw),
+      1 => Some(PayloadFormat::Container),
+      _ => None,
+    }
+  }
+}
+
+/// Metadata authenticated along the encrypted message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+  format: PayloadFormat,
+  testament_id: [u8; TESTAMENT_ID_SIZE],
+  created_at: u64,
+}
+
+impl MessageHeader {
+  /// Create a new header.
+  ///
+  /// The `created_at` is a UNIX timestamp (in seconds).
+  pub fn new(
+    format: PayloadFormat,
+    testament_id: [u8; TESTAMENT_ID_SIZE],
+    created_at: u64,
+  ) -> Self {
+    Self {
+      format,
+      testament_id,
+      created_at,
+    }
+  }
+
+  /// Format of the plaintext.
+  pub fn format(&self) -> PayloadFormat {
+    self.format
+  }
+
+  /// Identifier of the testament the message belongs to.
+  pub fn testament_id(&self) -> &[u8; TESTAMENT_ID_SIZE] {
+    &self.testament_id
+  }
+
+  /// Creation UNIX timestamp (in seconds).
+  pub fn created_at(&self) -> u64 {
+    self.created_at
+  }
+
+  /// Encode the header.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | payload format (1 byte)        |
+  /// +--------------------------------+
+  /// | testament id (16 bytes)        |
+  /// +--------------------------------+
+  /// | created at (8 bytes)           | // Big Endian
+  /// +--------------------------------+
+  /// ```
+  pub fn encode(&self) -> [u8; HEADER_SIZE] {
+    let mut out = [0u8; HEADER_SIZE];
+    out[0] = self.format.to_byte();
+    out[1..1 + TESTAMENT_ID_SIZE].copy_from_slice(&self.testament_id);
+    out[1 + TESTAMENT_ID_SIZE..].copy_from_slice(&self.created_at.to_be_bytes());
+    out
+  }
+
+  /// Read the header from the beginning of `data`.
+  ///
+  /// Returns the header and the remaining data.
+  pub fn decode(data: &[u8]) -> Option<(Self, &[u8])> {
+    if data.len() < HEADER_SIZE {
+      return None;
+    }
+    let (header, rest) = data.split_at(HEADER_SIZE);
+    let format = PayloadFormat::from_byte(header[0])?;
+    let mut testament_id = [0u8; TESTAMENT_ID_SIZE];
+    testament_id.copy_from_slice(&header[1..1 + TESTAMENT_ID_SIZE]);
+    let mut created_at = [0u8; 8];
+    created_at.copy_from_slice(&header[1 + TESTAMENT_ID_SIZE..]);
+    Some((
+      Self::new(format, testament_id, u64::from_be_bytes(created_at)),
+      rest,
+    ))
+  }
+}
+
+/// Additional Authenticated Data binding the scheme version and the header.
+pub(crate) fn aad(header: &MessageHeader) -> Vec<u8> {
+  let mut aad = AAD.to_vec();
+  aad.push(EncryptionKeyVersion::V4.to_byte());
+  aad.extend_from_slice(&header.encode());
+  aad
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_encode_and_decode_header() {
+    // given
+    let header = MessageHeader::new(
+      PayloadFormat::Container,
+      [7u8; TESTAMENT_ID_SIZE],
+      1_700_000_000,
+    );
+
+    // when
+    let mut encoded = header.encode().to_vec();
+    encoded.extend_from_slice(b"rest");
+    let decoded = MessageHeader::decode(&encoded);
+
+    // then
+    assert_eq!(decoded, Some((header, &b"rest"[..])));
+    assert_eq!(MessageHeader::decode(&encoded[..HEADER_SIZE - 1]), None);
+    encoded[0] = 5;
+    assert_eq!(MessageHeader::decode(&encoded), None);
+  }
+}
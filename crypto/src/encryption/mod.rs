@@ -0,0 +1,1631 @@
+//! Message encryption.
+//!
+//! Encrypting the message is versioned (see [EncryptionKeyVersion]),
+//! only to maintain backward compatibility in case things are required
+//! to be changed in the future.
+//!
+//! The `V0` version is using `AES-GCM-SIV` with `256b` key size.
+//!
+//! The `V1` version is using the same cipher, but the plaintext is
+//! split into fixed-size segments encrypted using the `STREAM`
+//! construction (see [stream] module), so that arbitrarily large
+//! messages can be processed without holding them in memory.
+//!
+//! The `V2` version is using `AES-GCM-SIV` as well, but the plaintext
+//! is compressed and padded before encryption (see [compression] module).
+//!
+//! The `V3` version is using `XChaCha20-Poly1305` with `256b` key size and
+//! a random `192b` nonce, for environments without AES hardware support.
+//! Since the nonce is random, it's not derived from the message (see [Message]).
+//!
+//! The `V4` version is using `AES-GCM-SIV` with a [MessageHeader] prepended to
+//! the ciphertext and authenticated along with the version (see [header] module).
+//!
+//! The encoded message parts may be signed with the owner's identity key
+//! (see [crate::identity]), which is indicated by [SIGNED_PART_FLAG]
+//! in the version byte. Parts tagged with the message identifier and a checksum
+//! are indicated by [TAGGED_PART_FLAG].
+//!
+//! The encrypted data is treated as an opaque sequence of bytes, however
+//! to store multiple text notes or binary files it's recommended to wrap
+//! them in a [Container] first.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use aes_gcm_siv::{
+  aead::{Aead, AeadCore, OsRng, Payload},
+  Aes256GcmSiv, KeyInit, Nonce,
+};
+use chacha20poly1305::XChaCha20Poly1305;
+
+use crate::identity::{IdentityKey, IdentityPublicKey, Signature, SIGNED_BY_SIZE};
+use crate::Bytes;
+
+pub mod compression;
+pub mod container;
+pub mod dispersal;
+pub mod fountain;
+pub mod header;
+pub mod stream;
+
+pub use container::{Container, ContainerError, Entry, EntryKind};
+pub use dispersal::{DispersalError, MessageFragment};
+pub use fountain::{FountainDecoder, FountainEncoder, FountainError, FrameHeader};
+pub use header::{MessageHeader, PayloadFormat};
+
+/// An error that may occur during encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+  /// The [MessageEncryptionKey] given has unsupported version.
+  #[error("Given encryption version is unsupported.")]
+  UnsupportedVersion,
+  /// Opaque encryption error.
+  #[error("Opaque AES encryption error")]
+  EncryptionError,
+  /// Reading or writing the streamed data failed.
+  #[error("I/O error while streaming the data: {0:?}")]
+  Io(std::io::ErrorKind),
+  /// The decrypted data could not be decompressed.
+  #[error("The decrypted data is not correctly compressed.")]
+  Compression,
+}
+
+impl From<std::io::Error> for Error {
+  fn from(value: std::io::Error) -> Self {
+    Error::Io(value.kind())
+  }
+}
+
+impl From<aes_gcm_siv::Error> for Error {
+  fn from(_: aes_gcm_siv::Error) -> Self {
+    Error::EncryptionError
+  }
+}
+
+/// An error which may occur during decoding of the [MessageEncryptionKey].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum KeyDecodingError {
+  /// The byte input is missing [KEY_ENCODING_MAGIC_SEQUENCE] prefix.
+  #[error("Given data does not look like a key.")]
+  MissingMagicBytes,
+  /// The byte input has invalid version identifier.
+  #[error("The version of the key is invalid.")]
+  InvalidVersion,
+  /// The remaining byte data has invalid length.
+  #[error("The key length is invalid.")]
+  InvalidKeySize,
+}
+
+/// Encryption key version.
+///
+/// The version describes the scheme used for encryption
+/// and may be changed at any point in time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EncryptionKeyVersion {
+  /// A version only used for testing. It will never decode.
+  #[cfg(test)]
+  Test,
+  /// AES-GCM-SIV with 256b key.
+  V0,
+  /// AES-GCM-SIV with 256b key, applied to fixed-size segments
+  /// of the plaintext using the `STREAM` construction.
+  V1,
+  /// AES-GCM-SIV with 256b key, applied to compressed and padded plaintext.
+  V2,
+  /// XChaCha20-Poly1305 with 256b key and random 192b nonce.
+  V3,
+  /// AES-GCM-SIV with 256b key and authenticated [MessageHeader].
+  V4,
+}
+
+impl EncryptionKeyVersion {
+  /// Return the byte representation of the version.
+  pub fn to_byte(self) -> u8 {
+    match self {
+      #[cfg(test)]
+      EncryptionKeyVersion::Test => 255u8,
+      EncryptionKeyVersion::V0 => 0u8,
+      EncryptionKeyVersion::V1 => 1u8,
+      EncryptionKeyVersion::V2 => 2u8,
+      EncryptionKeyVersion::V3 => 3u8,
+      EncryptionKeyVersion::V4 => 4u8,
+    }
+  }
+
+  /// Attempt to read the version from it's byte representation.
+  pub fn from_byte(byte: u8) -> Option<Self> {
+    match byte {
+      0 => Some(EncryptionKeyVersion::V0),
+      1 => Some(EncryptionKeyVersion::V1),
+      2 => Some(EncryptionKeyVersion::V2),
+      3 => Some(EncryptionKeyVersion::V3),
+      4 => Some(EncryptionKeyVersion::V4),
+      _ => None,
+    }
+  }
+
+  /// Byte size of the nonce stored along the message encrypted with this version.
+  pub fn nonce_size(self) -> usize {
+    match self {
+      #[cfg(test)]
+      EncryptionKeyVersion::Test => NONCE_SIZE,
+      EncryptionKeyVersion::V0 => NONCE_SIZE,
+      EncryptionKeyVersion::V1 => stream::NONCE_PREFIX_SIZE,
+      EncryptionKeyVersion::V2 => NONCE_SIZE,
+      EncryptionKeyVersion::V3 => XNONCE_SIZE,
+      EncryptionKeyVersion::V4 => NONCE_SIZE,
+    }
+  }
+}
+
+/// The byte size of the current version key.
+pub const KEY_SIZE: usize = 32;
+
+/// A key to encrypt/decrypt the message.
+///
+/// The key is versioned in case there is a need to change
+/// the encryption scheme at some point.
+///
+/// Note the key should NEVER be used twice. Instead of re-using
+/// the key, we should be generating a new one and using to encrypt
+/// the message.
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct MessageEncryptionKey {
+  #[cfg(test)]
+  pub(crate) version: EncryptionKeyVersion,
+  #[cfg(not(test))]
+  version: EncryptionKeyVersion,
+  key: [u8; KEY_SIZE],
+}
+
+/// A specific byte sequence used to identify the encoding of [MessageEncryptionKey].
+///
+/// The encoding of [MessageEncryptionKey] is simply a concatenation of:
+/// 1. The magic sequence.
+/// 2. The version byte.
+/// 3. The 32-bytes of the key.
+///
+/// The key size might change with versions, but currently it's fixed-size with version `V0`.
+pub const KEY_ENCODING_MAGIC_SEQUENCE: &'static [u8] = b"icodk";
+
+impl MessageEncryptionKey {
+  /// Wrap an externally-generated 32-bytes encryption key.
+  pub fn new(key: [u8; KEY_SIZE]) -> Self {
+    Self {
+      version: EncryptionKeyVersion::V0,
+      key,
+    }
+  }
+
+  /// Generate a new encryption key using default entropy source (`OsRng`).
+  pub fn generate() -> Self {
+    let key = Aes256GcmSiv::generate_key(&mut OsRng);
+    let key = <[u8; KEY_SIZE]>::try_from(key.as_slice())
+      .expect("The key generate by `Aes256GcmSiv` has the correct size");
+    Self::new(key)
+  }
+
+  /// Alter the encryption scheme the key is going to be used with.
+  ///
+  /// By default keys are created with [EncryptionKeyVersion::V0].
+  pub fn with_version(mut self, version: EncryptionKeyVersion) -> Self {
+    self.version = version;
+    self
+  }
+
+  /// Return the encryption scheme version of this key.
+  pub fn version(&self) -> EncryptionKeyVersion {
+    self.version
+  }
+
+  /// Clear out the data of the key from stack.
+  ///
+  /// Note this method is best-effort prevention of leaking the key
+  /// on stack or in memory.
+  pub fn wipe(&mut self) {
+    crate::wipe(&mut self.key);
+  }
+
+  /// Encode the key into a vector of bytes.
+  ///
+  /// The key encoding has a magic sequence prepended and a byte representing the version.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | magic byte sequence (5 bytes)  |
+  /// | (b"icodk")                     |
+  /// +--------------------------------+
+  /// | version (1 byte)               |
+  /// +--------------------------------+
+  /// | key (32 bytes for version 0)   |
+  /// +--------------------------------+
+  /// ```
+  pub fn encode(mut self) -> Bytes {
+    let mut out = vec![];
+    out.extend_from_slice(KEY_ENCODING_MAGIC_SEQUENCE);
+    out.push(self.version.to_byte());
+    out.extend_from_slice(&self.key);
+    self.wipe();
+
+    Bytes::from(out)
+  }
+
+  /// Attempt to decode the [MessageEncryptionKey] from given byte slices.
+  pub fn decode(data: &[u8]) -> Result<Self, KeyDecodingError> {
+    let data = data
+      .strip_prefix(KEY_ENCODING_MAGIC_SEQUENCE)
+      .ok_or(KeyDecodingError::MissingMagicBytes)?;
+    let (&version, key) = data.split_first().ok_or(KeyDecodingError::InvalidVersion)?;
+    let version =
+      EncryptionKeyVersion::from_byte(version).ok_or(KeyDecodingError::InvalidVersion)?;
+
+    if key.len() != KEY_SIZE {
+      return Err(KeyDecodingError::InvalidKeySize);
+    }
+
+    let mut out = [0u8; KEY_SIZE];
+    out.copy_from_slice(key);
+    Ok(MessageEncryptionKey::new(out).with_version(version))
+  }
+}
+
+impl Drop for MessageEncryptionKey {
+  fn drop(&mut self) {
+    self.wipe()
+  }
+}
+
+/// A representation of the message to be encrypted.
+///
+/// The message consists of the actual `data` to be encrypted
+/// and additionally the `nonce`, which is derived from the `data`
+/// and MUST be unique for every `data`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Message {
+  data: Bytes,
+  /// The nonce must be [NONCE_SIZE] bytes!
+  nonce: Bytes,
+}
+
+impl Message {
+  /// Wrap a pre-generated `data` and `nonce` into [Message] type.
+  ///
+  /// The `nonce` must be unique for every `data`.
+  pub fn new<A>(data: A, nonce: [u8; NONCE_SIZE]) -> Self
+  where
+    A: Into<Bytes>,
+  {
+    Self {
+      data: data.into(),
+      nonce: Bytes::from_slice(nonce.as_slice()),
+    }
+  }
+
+  /// Copy given string into the `Message` type and compute nonce on the flight.
+  ///
+  /// The nonce is derived from the message using BLAKE2b512 hash function
+  pub fn from_str(message: &str) -> Self {
+    Self::from_bytes(message.as_bytes())
+  }
+
+  /// Copy given arbitrary bytes into the `Message` type and compute nonce on the flight.
+  ///
+  /// The nonce is derived from the message using BLAKE2b512 hash function
+  pub fn from_bytes(message: &[u8]) -> Self {
+    let hash = crate::blake2b512(message);
+    Self {
+      data: Bytes::from_slice(message),
+      nonce: Bytes::from_slice(&hash.as_slice()[0..NONCE_SIZE]),
+    }
+  }
+
+  /// Encode given [Container] into the `Message` type.
+  ///
+  /// The nonce is derived the same way as in [Message::from_bytes].
+  pub fn from_container(container: &Container) -> Self {
+    let mut data = container.encode();
+    let message = Self::from_bytes(&data);
+    data.wipe();
+    message
+  }
+
+  /// View the underlying `data` of the message.
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Convert the message into the underlying `data` and `nonce`.
+  pub fn into_tuple(self) -> (Bytes, Bytes) {
+    (self.data, self.nonce)
+  }
+
+  /// Clear out the message to prevent it from leaking the values on the heap.
+  pub fn wipe(&mut self) {
+    self.data.wipe();
+    self.nonce.wipe();
+  }
+}
+
+/// The size of the required nonce.
+pub const NONCE_SIZE: usize = 12;
+
+/// The size of the random nonce used by [EncryptionKeyVersion::V3].
+pub const XNONCE_SIZE: usize = 24;
+
+/// Error which can occur during [EncryptedMessage] instantiation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EncryptedMessageError {
+  /// The `data` length can't be encoded into 3 bytes.
+  #[error("Given data exceeds maximum of 16MBs.")]
+  DataTooBig,
+  /// The decoded version of message is invalid.
+  #[error("Given message seems to use unsupported crypto.")]
+  InvalidVersion,
+  /// We failed to fully decode message parts or found some inconsistencies.
+  #[error("The message parts are malformed or incorrect.")]
+  MalformedData(&'static str),
+  /// Some parts of the message were not passed.
+  #[error("There is not enough parts of the encrypted message.")]
+  MissingParts,
+  /// The checksum of a tagged part does not match it's content.
+  #[error("The message part is damaged.")]
+  ChecksumMismatch,
+  /// The part at given position (of the collated parts) is damaged.
+  #[error("The message part #{0} is damaged.")]
+  CorruptedPart(usize),
+  /// The part at given position (of the collated parts) belongs to a different message.
+  #[error("The message part #{0} belongs to a different message.")]
+  ForeignPart(usize),
+}
+
+/// An encrypted payload of the message and the `nonce` which was used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedMessage {
+  /// The version of encrypted message is currently matching the encryption key version.
+  version: EncryptionKeyVersion,
+  /// The nonce must be [NONCE_SIZE] bytes!
+  nonce: Bytes,
+  /// Arbitrary-length data.
+  data: Bytes,
+  /// The owner who signed all of the message parts.
+  signer: Option<IdentityPublicKey>,
+}
+
+impl EncryptedMessage {
+  /// Wrap externally received `data` and `nonce` into [EncryptedMessage] type.
+  pub fn new<A>(data: A, nonce: [u8; NONCE_SIZE]) -> Result<Self, EncryptedMessageError>
+  where
+    A: Into<Bytes>,
+  {
+    let data = data.into();
+    // Validate that data length can be encoded into 3-bytes.
+    if data.len() > 2usize.pow(8 * BYTES_PER_ID_PART as u32) {
+      return Err(EncryptedMessageError::DataTooBig);
+    }
+
+    Ok(Self {
+      version: EncryptionKeyVersion::V0,
+      data: data.into(),
+      nonce: Bytes::from_slice(nonce.as_slice()),
+      signer: None,
+    })
+  }
+
+  /// Restore the [EncryptedMessage] from multiple parts coming from [split_and_encode] method.
+  ///
+  /// Note that you need to provide all `parts` to fully restore the message.
+  /// Restoration DOES NOT guarantee that the message is consistent,
+  /// it might just happen the consistency checks will pass, but
+  /// the parts are not part of the same original message and you will just get
+  /// garbage.
+  ///
+  /// Parts produced by [EncryptedMessage::split_and_encode_tagged] don't have this
+  /// problem: damaged parts are reported as [EncryptedMessageError::CorruptedPart]
+  /// and parts of other messages as [EncryptedMessageError::ForeignPart].
+  pub fn collate_from_parts(parts: Vec<Bytes>) -> Result<Self, EncryptedMessageError> {
+    let mut nonce = None;
+    let mut version = None;
+    let mut signer = None;
+    let mut message_parts = BTreeMap::<u32, Vec<u8>>::new();
+    let mut expected_parts = 0u32;
+
+    let parts = parts
+      .iter()
+      .enumerate()
+      .map(|(position, raw_part)| {
+        EncryptedMessagePart::decode(raw_part).map_err(|e| match e {
+          EncryptedMessageError::ChecksumMismatch => EncryptedMessageError::CorruptedPart(position),
+          e => e,
+        })
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    // Parts of other messages are the ones not matching the majority.
+    let message_id = parts
+      .iter()
+      .map(|part| part.message_id)
+      .max_by_key(|id| parts.iter().filter(|p| p.message_id == *id).count())
+      .flatten();
+    if parts
+      .iter()
+      .any(|part| part.message_id.is_some() != message_id.is_some())
+    {
+      return Err(EncryptedMessageError::MalformedData(
+        "Part encoding mismatch.",
+      ));
+    }
+    if let Some(position) = parts.iter().position(|part| part.message_id != message_id) {
+      return Err(EncryptedMessageError::ForeignPart(position));
+    }
+
+    for part in parts {
+      // Check that all parts use the same encryption scheme
+      if *version.get_or_insert(part.version) != part.version {
+        return Err(EncryptedMessageError::MalformedData(
+          "Encryption version mismatch.",
+        ));
+      }
+      // Check that all parts are signed by the same owner (or none of them).
+      if *signer.get_or_insert(part.signer) != part.signer {
+        return Err(EncryptedMessageError::MalformedData("Signer mismatch."));
+      }
+      // Check number of expected parts
+      if expected_parts != part.parts_total {
+        if expected_parts == 0 {
+          expected_parts = part.parts_total;
+        } else {
+          return Err(EncryptedMessageError::MalformedData(
+            "Number of parts mismatch.",
+          ));
+        }
+      }
+      message_parts.insert(part.part_index, part.data.to_vec());
+      nonce = nonce.or_else(|| part.nonce.map(|n| Bytes::from(n.to_vec())));
+    }
+
+    if expected_parts as usize != message_parts.len() {
+      return Err(EncryptedMessageError::MissingParts);
+    }
+
+    let mut message = Vec::with_capacity(
+      expected_parts as usize
+        * message_parts
+          .first_key_value()
+          .map(|(_, v)| v.len() + NONCE_SIZE)
+          .unwrap_or(0),
+    );
+
+    for (_, part) in message_parts.into_iter() {
+      message.extend(part);
+    }
+
+    let nonce = nonce.ok_or(EncryptedMessageError::MalformedData("Missing nonce."))?;
+
+    let message = Self {
+      version: version.unwrap_or(EncryptionKeyVersion::V0),
+      data: message.into(),
+      nonce,
+      signer: signer.flatten(),
+    };
+    if message_id.is_some_and(|id| id != message.message_id()) {
+      return Err(EncryptedMessageError::MalformedData("Message id mismatch."));
+    }
+    Ok(message)
+  }
+
+  /// Encode the encrypted message into multiple vectors of bytes.
+  ///
+  /// Since the encrypted message may be quite long, the message
+  /// may be split into multiple PARTS, each part having less than
+  /// `split` bytes on top of the required bytes (version
+  /// + identification). Note that since `split` values of
+  /// less than the nonce size are not practical, the `nonce`
+  /// is never split, so the first part might be bigger than
+  /// others.
+  ///
+  /// Each part will have the version and the part identification prepended.
+  /// Unlike [MessageEncryptionKey] and [Chunk] we don't prepend any magic
+  /// sequence to save off these bytes, since message encoding may be quite
+  /// long.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | version (1 byte)               |
+  /// +--------------------------------+
+  /// | part id (3 bytes)              | // Big Endian
+  /// +--------------------------------+
+  /// | total parts (3 bytes)          | // Big Endian
+  /// +--------------------------------+
+  /// | nonce (12 bytes for V0)        | // only in the first part
+  /// | (24 bytes for V3)              |
+  /// +--------------------------------+
+  /// | data (variable length)         |
+  /// +--------------------------------+
+  /// ```
+  pub fn split_and_encode(self, split_arg: Option<usize>) -> Vec<Bytes> {
+    self.split_and_encode_with(split_arg, None, false)
+  }
+
+  /// Encode the encrypted message into multiple parts signed with the owner's identity key.
+  ///
+  /// Works exactly the same as [EncryptedMessage::split_and_encode], but every
+  /// part has [SIGNED_PART_FLAG] set in the version byte and the public key of
+  /// the signer followed by the `Ed25519` signature of the part appended:
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | regular part (variable length) | // with flagged version byte
+  /// +--------------------------------+
+  /// | public key (32 bytes)          |
+  /// +--------------------------------+
+  /// | signature (64 bytes)           |
+  /// +--------------------------------+
+  /// ```
+  pub fn split_and_encode_signed(
+    self,
+    split_arg: Option<usize>,
+    identity: &IdentityKey,
+  ) -> Vec<Bytes> {
+    self.split_and_encode_with(split_arg, Some(identity), false)
+  }
+
+  /// Encode the encrypted message into multiple parts tagged with the message identifier.
+  ///
+  /// Works exactly the same as [EncryptedMessage::split_and_encode] (or
+  /// [EncryptedMessage::split_and_encode_signed] if `identity` is given), but every
+  /// part has [TAGGED_PART_FLAG] set in the version byte, carries the
+  /// [EncryptedMessage::message_id] and a checksum of the part:
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | version (1 byte)               | // with flagged version byte
+  /// +--------------------------------+
+  /// | part id & total (6 bytes)      |
+  /// +--------------------------------+
+  /// | message id (4 bytes)           |
+  /// +--------------------------------+
+  /// | nonce & data (variable length) |
+  /// +--------------------------------+
+  /// | checksum (4 bytes)             |
+  /// +--------------------------------+
+  /// | signature (optional)           |
+  /// +--------------------------------+
+  /// ```
+  pub fn split_and_encode_tagged(
+    self,
+    split_arg: Option<usize>,
+    identity: Option<&IdentityKey>,
+  ) -> Vec<Bytes> {
+    self.split_and_encode_with(split_arg, identity, true)
+  }
+
+  /// Return a short identifier of the message.
+  ///
+  /// The identifier is a prefix of `blake2b512` hash of the version, nonce and data.
+  pub fn message_id(&self) -> [u8; MESSAGE_ID_SIZE] {
+    let hash = crate::blake2b512(&[&[self.version.to_byte()], &*self.nonce, &*self.data].concat());
+    let mut out = [0u8; MESSAGE_ID_SIZE];
+    out.copy_from_slice(&hash.as_slice()[..MESSAGE_ID_SIZE]);
+    out
+  }
+
+  /// Return the verified signer of the message parts.
+  ///
+  /// Only available for messages collated from signed parts.
+  pub fn signer(&self) -> Option<&IdentityPublicKey> {
+    self.signer.as_ref()
+  }
+
+  /// Return the header of the message encrypted with [EncryptionKeyVersion::V4]
+  /// without authenticating it.
+  ///
+  /// The header should not be trusted, use [decrypt_message_with_header] to
+  /// obtain the authenticated one.
+  pub fn unverified_header(&self) -> Option<MessageHeader> {
+    match self.version {
+      EncryptionKeyVersion::V4 => MessageHeader::decode(&self.data).map(|(header, _)| header),
+      _ => None,
+    }
+  }
+
+  fn split_and_encode_with(
+    self,
+    split_arg: Option<usize>,
+    identity: Option<&IdentityKey>,
+    tagged: bool,
+  ) -> Vec<Bytes> {
+    let message_id = tagged.then(|| self.message_id());
+    let data_len = self.data.len();
+    let nonce_size = self.nonce.len();
+    let total_len = data_len + nonce_size;
+    let split = split_arg.unwrap_or(total_len).min(total_len).max(1);
+    let capacity = if split_arg.is_none() {
+      // special case if we are not planning to do any splitting.
+      1
+    } else if split < nonce_size {
+      // add one extra chunk just for nonce
+      (data_len + split - 1) / split + 1
+    } else {
+      // treat nonce as regular data
+      (data_len + nonce_size + split - 1) / split
+    };
+    let mut output = Vec::with_capacity(capacity);
+
+    let mut data = &self.data[..];
+    let mut part_index = 0;
+    let parts_total = capacity as u32;
+
+    loop {
+      let mut nonce = None;
+      let mut split_point = data.len().min(split);
+      // The first chunk always contains the nonce.
+      if part_index == 0 {
+        nonce = Some(Cow::Borrowed(&*self.nonce));
+        // we subtract the nonce size from the chunk size
+        // to keep the chunks consistent
+        if capacity > 1 {
+          split_point = split_point.saturating_sub(nonce_size);
+        }
+      }
+      let (slice, rest) = data.split_at(split_point);
+      let part = EncryptedMessagePart {
+        version: self.version,
+        part_index,
+        parts_total,
+        nonce,
+        data: slice.into(),
+        signer: None,
+        message_id,
+      };
+      output.push(part.encode(identity));
+
+      part_index += 1;
+      data = rest;
+
+      if data.is_empty() || part_index > parts_total {
+        break;
+      }
+    }
+
+    output
+  }
+}
+
+/// Number of bytes used to encode each part location (index and total) of [EncryptedMessagePart].
+///
+/// We use 3 bytes, which allows us to store up to: 2**24 bytes (~16MB)
+pub const BYTES_PER_ID_PART: usize = 3;
+
+/// A flag set in the version byte of [EncryptedMessagePart] signed by the owner.
+pub const SIGNED_PART_FLAG: u8 = 0x80;
+
+/// A flag set in the version byte of [EncryptedMessagePart] tagged with the message identifier.
+pub const TAGGED_PART_FLAG: u8 = 0x40;
+
+/// Byte size of the message identifier (see [EncryptedMessage::message_id]).
+pub const MESSAGE_ID_SIZE: usize = 4;
+
+/// Byte size of the tagged [EncryptedMessagePart] checksum.
+pub const PART_CHECKSUM_SIZE: usize = 4;
+
+/// Domain separation string for message part signatures.
+const PART_SIGNATURE_DOMAIN: &[u8] = b"ICOD-Crypto signed message part.";
+
+/// A single part of encrypted message.
+#[derive(Debug)]
+pub struct EncryptedMessagePart<'a> {
+  /// Version of the message encoding.
+  version: EncryptionKeyVersion,
+  /// Current part index (0-based).
+  part_index: u32,
+  /// Total number of all parts.
+  parts_total: u32,
+  /// Message nonce. Only present in the `part_index = 0`.
+  nonce: Option<Cow<'a, [u8]>>,
+  /// Part of the original encrypted data.
+  data: Cow<'a, [u8]>,
+  /// Owner who signed the part (verified during decoding).
+  signer: Option<IdentityPublicKey>,
+  /// Identifier of the message (only in tagged parts).
+  message_id: Option<[u8; MESSAGE_ID_SIZE]>,
+}
+
+impl<'a> EncryptedMessagePart<'a> {
+  /// Return the version byte of this message encryption part.
+  pub fn version(&self) -> u8 {
+    self.version.to_byte()
+  }
+
+  /// Part index (0-based).
+  pub fn part_index(&self) -> u32 {
+    self.part_index
+  }
+
+  /// Total number of parts.
+  pub fn parts_total(&self) -> u32 {
+    self.parts_total
+  }
+
+  /// Message nonce. Only present in `part_index = 0`.
+  pub fn nonce(&self) -> Option<&[u8]> {
+    self.nonce.as_ref().map(|x| x.as_ref())
+  }
+
+  /// Part data.
+  pub fn data(&self) -> &[u8] {
+    &*self.data
+  }
+
+  /// Return the owner who signed this part.
+  pub fn signer(&self) -> Option<&IdentityPublicKey> {
+    self.signer.as_ref()
+  }
+
+  /// Identifier of the message this part belongs to (only in tagged parts).
+  pub fn message_id(&self) -> Option<&[u8; MESSAGE_ID_SIZE]> {
+    self.message_id.as_ref()
+  }
+
+  fn encode(&self, identity: Option<&IdentityKey>) -> Bytes {
+    let mut out = vec![];
+    let mut version = self.version();
+    if identity.is_some() {
+      version |= SIGNED_PART_FLAG;
+    }
+    if self.message_id.is_some() {
+      version |= TAGGED_PART_FLAG;
+    }
+    out.push(version);
+    out.extend_from_slice(&self.index_bytes());
+    // TODO [ToDr] We might consider dropping this and version from
+    // every chunk. Not sure if it adds anything.
+    out.extend_from_slice(&self.all_bytes());
+    if let Some(message_id) = self.message_id.as_ref() {
+      out.extend_from_slice(message_id);
+    }
+    if let Some(nonce) = self.nonce.as_ref() {
+      out.extend_from_slice(nonce);
+    }
+    out.extend_from_slice(&self.data);
+    if self.message_id.is_some() {
+      let checksum = Self::checksum(&out);
+      out.extend_from_slice(&checksum);
+    }
+    if let Some(identity) = identity {
+      let signature = identity.sign(PART_SIGNATURE_DOMAIN, &out);
+      out.extend(signature.encode());
+    }
+    out.into()
+  }
+
+  /// Decode the [MessageEncryptionPart] given a set of bytes.
+  pub fn decode(part: &'a [u8]) -> Result<Self, EncryptedMessageError> {
+    let (&version, _) = part
+      .split_first()
+      .ok_or(EncryptedMessageError::InvalidVersion)?;
+    let (signed_part, signature) = if version & SIGNED_PART_FLAG != 0 {
+      let split_point = part
+        .len()
+        .checked_sub(SIGNED_BY_SIZE)
+        .filter(|x| *x > 0)
+        .ok_or(EncryptedMessageError::MalformedData("Missing signature."))?;
+      let (part, signature) = part.split_at(split_point);
+      (part, Some(signature))
+    } else {
+      (part, None)
+    };
+    // The checksum goes first, so that damaged parts are reported as such
+    // instead of having an invalid signature.
+    let tagged = version & TAGGED_PART_FLAG != 0;
+    let part = if tagged {
+      let split_point = signed_part
+        .len()
+        .checked_sub(PART_CHECKSUM_SIZE)
+        .filter(|x| *x > 0)
+        .ok_or(EncryptedMessageError::ChecksumMismatch)?;
+      let (part, checksum) = signed_part.split_at(split_point);
+      if Self::checksum(part) != checksum {
+        return Err(EncryptedMessageError::ChecksumMismatch);
+      }
+      part
+    } else {
+      signed_part
+    };
+    let mut signer = None;
+    if let Some(signature) = signature {
+      let signature = Signature::decode(signature)
+        .map_err(|_| EncryptedMessageError::MalformedData("Invalid signature."))?;
+      signature
+        .verify(PART_SIGNATURE_DOMAIN, signed_part)
+        .map_err(|_| EncryptedMessageError::MalformedData("Invalid signature."))?;
+      signer = Some(*signature.signer());
+    }
+    let version = version & !SIGNED_PART_FLAG & !TAGGED_PART_FLAG;
+    let part = &part[1..];
+    let version =
+      EncryptionKeyVersion::from_byte(version).ok_or(EncryptedMessageError::InvalidVersion)?;
+    let nonce_size = version.nonce_size();
+
+    if part.len() < BYTES_PER_ID_PART * 2 {
+      return Err(EncryptedMessageError::MalformedData("Cannot read part id."));
+    }
+
+    let part_index = Self::slice_to_u32(&part[0..BYTES_PER_ID_PART]);
+    let parts_total = Self::slice_to_u32(&part[BYTES_PER_ID_PART..BYTES_PER_ID_PART * 2]);
+
+    let part = &part[BYTES_PER_ID_PART * 2..];
+    let mut message_id = None;
+    let part = if tagged {
+      if part.len() < MESSAGE_ID_SIZE {
+        return Err(EncryptedMessageError::MalformedData(
+          "Cannot read message id.",
+        ));
+      }
+      let (id, part) = part.split_at(MESSAGE_ID_SIZE);
+      let mut out = [0u8; MESSAGE_ID_SIZE];
+      out.copy_from_slice(id);
+      message_id = Some(out);
+      part
+    } else {
+      part
+    };
+    if part_index >= parts_total {
+      return Err(EncryptedMessageError::MalformedData("Invalid part id."));
+    }
+
+    // read nonce first for the first part
+    let mut nonce = None;
+    let part = if part_index == 0 {
+      if part.len() < nonce_size {
+        return Err(EncryptedMessageError::MalformedData(
+          "Not enough bytes to read NONCE.",
+        ));
+      }
+      nonce = Some(Cow::Owned(part[0..nonce_size].to_vec()));
+      &part[nonce_size..]
+    } else {
+      part
+    };
+    // now rest of the data
+    // TODO [ToDr] Avoid alloc? Use split_off just calculate where.
+    Ok(Self {
+      version,
+      part_index,
+      parts_total,
+      nonce,
+      data: part.into(),
+      signer,
+      message_id,
+    })
+  }
+
+  fn checksum(data: &[u8]) -> [u8; PART_CHECKSUM_SIZE] {
+    let hash = crate::blake2b512(data);
+    let mut out = [0u8; PART_CHECKSUM_SIZE];
+    out.copy_from_slice(&hash.as_slice()[..PART_CHECKSUM_SIZE]);
+    out
+  }
+
+  fn u32_to_bytes(val: u32) -> [u8; BYTES_PER_ID_PART] {
+    let mut out = [0u8; BYTES_PER_ID_PART];
+    out.copy_from_slice(&val.to_be_bytes()[1..]);
+    out
+  }
+
+  fn slice_to_u32(bytes: &[u8]) -> u32 {
+    assert_eq!(bytes.len(), 3);
+    let mut out = [0u8; 4];
+    out[1..].copy_from_slice(bytes);
+    u32::from_be_bytes(out)
+  }
+
+  /// Return 3-byte big endian encoding of part index.
+  pub fn index_bytes(&self) -> [u8; BYTES_PER_ID_PART] {
+    Self::u32_to_bytes(self.part_index)
+  }
+
+  /// Return 3-byte big endian encoding of total number of parts.
+  pub fn all_bytes(&self) -> [u8; BYTES_PER_ID_PART] {
+    Self::u32_to_bytes(self.parts_total)
+  }
+}
+
+/// This is AEAD "Additional Authenticated Data".
+///
+/// The string is used to verify integrity of the encrypted payload when decrypting it.
+const AAD: &'static [u8] = b"ICOD-Crypto library of ICOD project. Non omnis moriar.";
+
+/// Encrypt given message using provided [MessageEncryptionKey].
+///
+/// Messages encrypted with [EncryptionKeyVersion::V4] get a default [MessageHeader],
+/// use [encrypt_message_with_header] to provide the actual one.
+pub fn encrypt_message(
+  key: &MessageEncryptionKey,
+  msg: &Message,
+) -> Result<EncryptedMessage, Error> {
+  encrypt_message_with_header(key, msg, &MessageHeader::default())
+}
+
+/// Encrypt given message using provided [MessageEncryptionKey] and authenticate the `header`.
+///
+/// The `header` is only stored for [EncryptionKeyVersion::V4], older versions ignore it.
+pub fn encrypt_message_with_header(
+  key: &MessageEncryptionKey,
+  msg: &Message,
+  header: &MessageHeader,
+) -> Result<EncryptedMessage, Error> {
+  match key.version {
+    EncryptionKeyVersion::V0 => {
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: &*msg.data,
+        aad: AAD,
+      };
+      let encrypted = cipher.encrypt(nonce, payload)?;
+      Ok(EncryptedMessage {
+        version: key.version,
+        nonce: msg.nonce.clone(),
+        data: encrypted.into(),
+        signer: None,
+      })
+    }
+    EncryptionKeyVersion::V1 => {
+      // The nonce prefix is shorter, since STREAM appends segment counter to it.
+      let mut nonce = [0u8; stream::NONCE_PREFIX_SIZE];
+      nonce.copy_from_slice(&msg.nonce[..stream::NONCE_PREFIX_SIZE]);
+      let mut encrypted = Vec::with_capacity(stream::encrypted_size(msg.data.len()));
+      stream::encrypt_segments(&key.key, &nonce, &mut &*msg.data, &mut encrypted)?;
+      Ok(EncryptedMessage {
+        version: key.version,
+        nonce: Bytes::from_slice(&nonce),
+        data: encrypted.into(),
+        signer: None,
+      })
+    }
+    EncryptionKeyVersion::V2 => {
+      let (compression, mut padded) = compression::compress_and_pad(&msg.data)?;
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: &padded,
+        aad: &compression::aad(compression),
+      };
+      let encrypted = cipher.encrypt(nonce, payload);
+      crate::wipe(&mut padded);
+      // The compression algorithm is stored in front of the ciphertext.
+      let mut data = vec![compression.to_byte()];
+      data.extend(encrypted?);
+      Ok(EncryptedMessage {
+        version: key.version,
+        nonce: msg.nonce.clone(),
+        data: data.into(),
+        signer: None,
+      })
+    }
+    EncryptionKeyVersion::V3 => {
+      let cipher = XChaCha20Poly1305::new(&key.key.into());
+      // The nonce is random, so the one derived from the message is not used.
+      let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+      let payload = Payload {
+        msg: &msg.data,
+        aad: AAD,
+      };
+      let encrypted = cipher.encrypt(&nonce, payload)?;
+      Ok(EncryptedMessage {
+        version: key.version,
+        nonce: Bytes::from_slice(&nonce),
+        data: encrypted.into(),
+        signer: None,
+      })
+    }
+    EncryptionKeyVersion::V4 => {
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: &msg.data,
+        aad: &header::aad(header),
+      };
+      let encrypted = cipher.encrypt(nonce, payload)?;
+      // The header is stored in front of the ciphertext.
+      let mut data = header.encode().to_vec();
+      data.extend(encrypted);
+      Ok(EncryptedMessage {
+        version: key.version,
+        nonce: msg.nonce.clone(),
+        data: data.into(),
+        signer: None,
+      })
+    }
+    #[allow(unreachable_patterns)]
+    _ => Err(Error::UnsupportedVersion),
+  }
+}
+
+/// Decrypt given [EncryptedMessage] exactly like [decrypt_message], but also return
+/// it's [MessageHeader] (only for [EncryptionKeyVersion::V4]).
+///
+/// The header is authenticated by the cipher along with the message.
+pub fn decrypt_message_with_header(
+  key: &MessageEncryptionKey,
+  msg: &EncryptedMessage,
+) -> Result<(Message, Option<MessageHeader>), Error> {
+  let message = decrypt_message(key, msg)?;
+  // the decryption above succeeds only if the header is authentic.
+  let header = msg.unverified_header();
+  Ok((message, header))
+}
+
+/// Decrypt given [EncryptedMessage] using provided [MessageEncryptionKey].
+///
+/// The decryption scheme is picked based on the version of the [EncryptedMessage],
+/// the version of the key is irrelevant here.
+pub fn decrypt_message(
+  key: &MessageEncryptionKey,
+  msg: &EncryptedMessage,
+) -> Result<Message, Error> {
+  match msg.version {
+    EncryptionKeyVersion::V0 => {
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: &*msg.data,
+        aad: AAD,
+      };
+      let decrypted = cipher.decrypt(nonce, payload)?;
+      Ok(Message {
+        nonce: msg.nonce.clone(),
+        data: decrypted.into(),
+      })
+    }
+    EncryptionKeyVersion::V1 => {
+      let nonce = <[u8; stream::NONCE_PREFIX_SIZE]>::try_from(&*msg.nonce)
+        .map_err(|_| Error::EncryptionError)?;
+      let mut decrypted = Vec::with_capacity(msg.data.len());
+      stream::decrypt_segments(&key.key, &nonce, &mut &*msg.data, &mut decrypted)?;
+      let data = Bytes::from(decrypted);
+      // Only the nonce prefix is stored, so we re-derive the full one.
+      let hash = crate::blake2b512(&data);
+      Ok(Message {
+        nonce: Bytes::from_slice(&hash.as_slice()[0..NONCE_SIZE]),
+        data,
+      })
+    }
+    EncryptionKeyVersion::V2 => {
+      let (&compression, data) = msg.data.split_first().ok_or(Error::EncryptionError)?;
+      let compression =
+        compression::Compression::from_byte(compression).ok_or(Error::UnsupportedVersion)?;
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: data,
+        aad: &compression::aad(compression),
+      };
+      let mut padded = cipher.decrypt(nonce, payload)?;
+      let decompressed = compression::unpad_and_decompress(compression, &padded);
+      crate::wipe(&mut padded);
+      Ok(Message {
+        nonce: msg.nonce.clone(),
+        data: decompressed?.into(),
+      })
+    }
+    EncryptionKeyVersion::V3 => {
+      if msg.nonce.len() != XNONCE_SIZE {
+        return Err(Error::EncryptionError);
+      }
+      let cipher = XChaCha20Poly1305::new(&key.key.into());
+      let nonce = chacha20poly1305::XNonce::from_slice(&msg.nonce);
+      let payload = Payload {
+        msg: &msg.data,
+        aad: AAD,
+      };
+      let data = Bytes::from(cipher.decrypt(nonce, payload)?);
+      // The random nonce is longer, so we re-derive the message one.
+      let hash = crate::blake2b512(&data);
+      Ok(Message {
+        nonce: Bytes::from_slice(&hash.as_slice()[0..NONCE_SIZE]),
+        data,
+      })
+    }
+    EncryptionKeyVersion::V4 => {
+      let (header, data) = MessageHeader::decode(&msg.data).ok_or(Error::EncryptionError)?;
+      let k = key.key.into();
+      let cipher = Aes256GcmSiv::new(&k);
+      let nonce = Nonce::from_slice(&msg.nonce.data);
+      let payload = Payload {
+        msg: data,
+        aad: &header::aad(&header),
+      };
+      let decrypted = cipher.decrypt(nonce, payload)?;
+      Ok(Message {
+        nonce: msg.nonce.clone(),
+        data: decrypted.into(),
+      })
+    }
+    #[allow(unreachable_patterns)]
+    _ => Err(Error::UnsupportedVersion),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  /// An unencrypted message of given length used by the tests of message encodings.
+  pub(super) fn message(len: u32) -> EncryptedMessage {
+    let data: Vec<u8> = (0..len).map(|x| (x * 7 % 256) as u8).collect();
+    EncryptedMessage::new(data, *b"test nonce x").unwrap()
+  }
+
+  #[test]
+  fn should_format_message() {
+    let nonce = {
+      let mut nonce = [0u8; 12];
+      nonce.copy_from_slice(b"unique nonce");
+      nonce
+    };
+    let msg = Message::new(Bytes::from_slice(b"This is a message to encrypt."), nonce);
+
+    assert_eq!(
+      &format!("{:?}", msg),
+      r#"Message { data: String("This is a message to encrypt.") == Bytes("546869732069732061206d65737361676520746f20656e63727970742e"), nonce: String("unique nonce") == Bytes("756e69717565206e6f6e6365") }"#,
+    );
+  }
+
+  #[test]
+  fn should_generate_unique_nonce_for_string_message() {
+    let msg = Message::from_str("This is a message to encrypt.");
+
+    assert_eq!(
+      &format!("{:?}", msg),
+      r#"Message { data: String("This is a message to encrypt.") == Bytes("546869732069732061206d65737361676520746f20656e63727970742e"), nonce: Bytes("b058b8dcc94c1828d5e47d7d") }"#,
+    );
+  }
+
+  #[test]
+  fn should_format_encrypted_message() {
+    let msg = EncryptedMessage {
+      version: EncryptionKeyVersion::V0,
+      data: Bytes::from_slice(b"This is an encrypted message."),
+      nonce: Bytes::from_slice(b"This is a unique nonce"),
+      signer: None,
+    };
+
+    assert_eq!(
+      &format!("{:?}", msg),
+      r#"EncryptedMessage { version: V0, nonce: String("This is a unique nonce") == Bytes("54686973206973206120756e69717565206e6f6e6365"), data: String("This is an encrypted message.") == Bytes("5468697320697320616e20656e63727970746564206d6573736167652e"), signer: None }"#,
+    );
+  }
+
+  #[test]
+  fn should_encrypt_and_decrypt_a_message() {
+    // given
+    let message = "Hello World!";
+    let key = [0u8; 32];
+    let key = MessageEncryptionKey::new(key);
+    let nonce = {
+      let mut nonce = [0u8; 12];
+      nonce.copy_from_slice(b"unique nonce");
+      nonce
+    };
+    let message = Message::new(message.as_bytes().to_vec(), nonce);
+
+    // when
+    let encrypted = encrypt_message(&key, &message).unwrap();
+    let decrypted = decrypt_message(&key, &encrypted).unwrap();
+
+    // then
+    assert_eq!(
+      &format!("{:?}", encrypted),
+      r#"EncryptedMessage { version: V0, nonce: String("unique nonce") == Bytes("756e69717565206e6f6e6365"), data: Bytes("2a1ca7857f89ad9fbc02dadff3e9dddd174e85777a478fe316e361ff"), signer: None }"#
+    );
+    assert_eq!(decrypted, message);
+  }
+
+  #[test]
+  fn should_encode_and_decode_the_key() {
+    // given
+    let raw_key = [1u8; KEY_SIZE];
+    let key = MessageEncryptionKey::new(raw_key.clone());
+
+    let encoded = key.encode();
+
+    assert_eq!(
+      format!("{:?}", encoded),
+      r#"String("icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("69636f646b000101010101010101010101010101010101010101010101010101010101010101")"#
+    );
+
+    let key = MessageEncryptionKey::new(raw_key);
+    let decoded = MessageEncryptionKey::decode(&*encoded).unwrap();
+
+    assert_eq!(key, decoded);
+  }
+
+  #[test]
+  fn should_encode_encrypted_message() {
+    // given
+    let message = EncryptedMessage::new(b"Test Data".to_vec(), b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let encoded = message.split_and_encode(Some(4));
+
+    // then
+    assert_eq!(encoded.len(), 4);
+    assert_eq!(
+        format!("{:?}", encoded[0]),
+        "String(\"\\0\\0\\0\\0\\0\\0\\u{4}test nonce x\") == Bytes(\"0000000000000474657374206e6f6e63652078\")"
+    );
+    assert_eq!(
+      format!("{:?}", encoded[1]),
+      "String(\"\\0\\0\\0\\u{1}\\0\\0\\u{4}Test\") == Bytes(\"0000000100000454657374\")"
+    );
+    assert_eq!(
+      format!("{:?}", encoded[2]),
+      "String(\"\\0\\0\\0\\u{2}\\0\\0\\u{4} Dat\") == Bytes(\"0000000200000420446174\")"
+    );
+    assert_eq!(
+      format!("{:?}", encoded[3]),
+      "String(\"\\0\\0\\0\\u{3}\\0\\0\\u{4}a\") == Bytes(\"0000000300000461\")"
+    );
+  }
+
+  #[test]
+  fn should_encode_when_split_is_none() {
+    // given
+    let message = EncryptedMessage::new(b"Test Data".to_vec(), b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let encoded = message.split_and_encode(None);
+
+    // then
+    assert_eq!(encoded.len(), 1);
+    assert_eq!(
+        format!("{:?}", encoded[0]),
+        "String(\"\\0\\0\\0\\0\\0\\0\\u{1}test nonce xTest Data\") == Bytes(\"0000000000000174657374206e6f6e63652078546573742044617461\")"
+    );
+  }
+
+  #[test]
+  fn should_encode_when_split_is_zero() {
+    // given
+    let message = EncryptedMessage::new(b"Test Data".to_vec(), b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let encoded = message.split_and_encode(Some(0));
+
+    // then
+    // Assuming nonce has length 12 and data has length 9.
+    // Every chunk will have 1 byte of data + first chunk will contain the nonce.
+    assert_eq!(encoded.len(), 10);
+  }
+
+  #[test]
+  fn should_encode_large_data() {
+    // given
+    let large_data: Vec<u8> = (0..1000).map(|x| (x % 256) as u8).collect();
+    let message = EncryptedMessage::new(large_data, b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let encoded = message.split_and_encode(Some(200));
+
+    // then
+    assert_eq!(encoded.len(), 1 + 5); // The large_data with size 1000 will be split into 5 parts each of size 200.
+  }
+
+  #[test]
+  fn should_fail_encoding_too_large_message() {
+    // given
+    let mut large_data: Vec<u8> = (0..2usize.pow(8 * BYTES_PER_ID_PART as u32))
+      .map(|x| (x % 256) as u8)
+      .collect();
+    let message = EncryptedMessage::new(large_data.clone(), b"test nonce x".to_owned()).unwrap();
+    let encoded = message.split_and_encode(Some(200));
+    // with 60 QR codes a second, this would take ~23 mins to fully scan (0.o)!
+    assert_eq!(encoded.len(), 1 + 83886);
+
+    // when
+    large_data.push(1);
+    let message = EncryptedMessage::new(large_data.clone(), b"test nonce x".to_owned());
+
+    // then
+    assert_eq!(message, Err(EncryptedMessageError::DataTooBig));
+  }
+
+  #[test]
+  fn should_validate_what_it_decodes() {
+    let too_short = MessageEncryptionKey::decode(&[]).unwrap_err();
+    assert_eq!(too_short, KeyDecodingError::MissingMagicBytes);
+    let no_prefix = MessageEncryptionKey::decode(&[1, 2, 3, 4, 5, 6]).unwrap_err();
+    assert_eq!(no_prefix, KeyDecodingError::MissingMagicBytes);
+    let mut out = vec![];
+    out.extend_from_slice(KEY_ENCODING_MAGIC_SEQUENCE);
+    {
+      let mut o = out.clone();
+      o.push(254);
+      let invalid_version = MessageEncryptionKey::decode(&o).unwrap_err();
+      assert_eq!(invalid_version, KeyDecodingError::InvalidVersion);
+    }
+
+    out.push(0);
+    {
+      let mut o = out.clone();
+      o.extend_from_slice(&[1, 2, 3, 4]);
+      let key_to_short = MessageEncryptionKey::decode(&o).unwrap_err();
+      assert_eq!(key_to_short, KeyDecodingError::InvalidKeySize);
+    }
+
+    out.extend_from_slice(&[2u8; KEY_SIZE]);
+
+    let _ok = MessageEncryptionKey::decode(&out).unwrap();
+  }
+
+  #[test]
+  fn should_encode_and_verify_signed_parts() {
+    // given
+    let identity = IdentityKey::from_bytes(&[3u8; crate::identity::IDENTITY_KEY_SIZE]);
+    let message = EncryptedMessage::new(b"Test Data".to_vec(), b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let parts = message.clone().split_and_encode_signed(Some(16), &identity);
+    let part = EncryptedMessagePart::decode(&parts[1]).unwrap();
+    let collated = EncryptedMessage::collate_from_parts(parts.clone()).unwrap();
+
+    // then
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[1][0], SIGNED_PART_FLAG);
+    assert_eq!(part.version(), 0);
+    assert_eq!(part.signer(), Some(&identity.public_key()));
+    assert_eq!(collated.signer(), Some(&identity.public_key()));
+    assert_eq!(collated.data, message.data);
+
+    let mut tampered = parts[1].to_vec();
+    tampered[7] ^= 1;
+    assert_eq!(
+      EncryptedMessagePart::decode(&tampered).unwrap_err(),
+      EncryptedMessageError::MalformedData("Invalid signature.")
+    );
+
+    let unsigned = message.split_and_encode(Some(16));
+    assert_eq!(
+      EncryptedMessage::collate_from_parts(vec![parts[0].clone(), unsigned[1].clone()]),
+      Err(EncryptedMessageError::MalformedData("Signer mismatch."))
+    );
+  }
+
+  #[test]
+  fn should_reject_foreign_and_damaged_tagged_parts() {
+    // given
+    let identity = IdentityKey::from_bytes(&[3u8; crate::identity::IDENTITY_KEY_SIZE]);
+    let message = EncryptedMessage::new(b"Test Data".to_vec(), b"test nonce x".to_owned()).unwrap();
+    let other = EncryptedMessage::new(b"Test Dat4".to_vec(), b"test nonce x".to_owned()).unwrap();
+
+    // when
+    let parts = message.clone().split_and_encode_tagged(Some(16), None);
+    let signed = message
+      .clone()
+      .split_and_encode_tagged(Some(16), Some(&identity));
+    let other_parts = other.split_and_encode_tagged(Some(16), None);
+    let part = EncryptedMessagePart::decode(&parts[1]).unwrap();
+    let collated = EncryptedMessage::collate_from_parts(parts.clone()).unwrap();
+    let collated_signed = EncryptedMessage::collate_from_parts(signed.clone()).unwrap();
+
+    // then
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[1][0], TAGGED_PART_FLAG);
+    assert_eq!(signed[1][0], TAGGED_PART_FLAG | SIGNED_PART_FLAG);
+    assert_eq!(part.version(), 0);
+    assert_eq!(part.message_id(), Some(&message.message_id()));
+    assert_eq!(collated, message);
+    assert_eq!(collated_signed.signer(), Some(&identity.public_key()));
+
+    let mut damaged = parts[1].to_vec();
+    damaged[12] ^= 1;
+    let mut damaged_signed = signed[1].to_vec();
+    damaged_signed[12] ^= 1;
+    assert_eq!(
+      EncryptedMessagePart::decode(&damaged).unwrap_err(),
+      EncryptedMessageError::ChecksumMismatch
+    );
+    assert_eq!(
+      EncryptedMessage::collate_from_parts(vec![signed[0].clone(), damaged_signed.into()]),
+      Err(EncryptedMessageError::CorruptedPart(1))
+    );
+    assert_eq!(
+      EncryptedMessage::collate_from_parts(vec![parts[0].clone(), damaged.into()]),
+      Err(EncryptedMessageError::CorruptedPart(1))
+    );
+    assert_eq!(
+      EncryptedMessage::collate_from_parts(vec![
+        parts[0].clone(),
+        other_parts[1].clone(),
+        parts[1].clone()
+      ]),
+      Err(EncryptedMessageError::ForeignPart(1))
+    );
+    assert_eq!(
+      EncryptedMessage::collate_from_parts(vec![
+        parts[0].clone(),
+        message.split_and_encode(Some(16))[1].clone()
+      ]),
+      Err(EncryptedMessageError::MalformedData(
+        "Part encoding mismatch."
+      ))
+    );
+  }
+
+  #[test]
+  fn should_generate_a_random_key() {
+    let _key = MessageEncryptionKey::generate();
+  }
+
+  #[test]
+  fn should_encrypt_and_decrypt_a_segmented_message() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V1);
+    let data: Vec<u8> = (0..stream::SEGMENT_SIZE + 100)
+      .map(|x| (x % 256) as u8)
+      .collect();
+    let message = Message::new(data, b"unique nonce".to_owned());
+
+    // when
+    let encrypted = encrypt_message(&key, &message).unwrap();
+    let parts = encrypted.clone().split_and_encode(Some(1_000));
+    let collated = EncryptedMessage::collate_from_parts(parts).unwrap();
+    let decrypted = decrypt_message(&key, &collated).unwrap();
+
+    // then
+    assert_eq!(collated, encrypted);
+    assert_eq!(encrypted.version, EncryptionKeyVersion::V1);
+    assert_eq!(&*encrypted.nonce, b"unique ");
+    assert_eq!(decrypted.into_tuple().0, message.into_tuple().0);
+  }
+
+  #[test]
+  fn should_encrypt_and_decrypt_a_compressed_message() {
+    // given
+    let v0_key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let v2_key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V2);
+    let message = Message::from_str(&"My bank account password is: hunter2.\n".repeat(10));
+
+    // when
+    let uncompressed = encrypt_message(&v0_key, &message).unwrap();
+    let encrypted = encrypt_message(&v2_key, &message).unwrap();
+    let parts = encrypted.clone().split_and_encode(Some(20));
+    let collated = EncryptedMessage::collate_from_parts(parts).unwrap();
+    let decrypted = decrypt_message(&v2_key, &collated).unwrap();
+
+    // then
+    assert_eq!(collated, encrypted);
+    assert_eq!(
+      encrypted.data[0],
+      compression::Compression::Deflate.to_byte()
+    );
+    assert!(encrypted.data.len() * 4 < uncompressed.data.len());
+    assert_eq!(decrypted, message);
+  }
+
+  #[test]
+  fn should_hide_exact_compressed_length() {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V2);
+    // random bytes are not compressible, so the padding has to do all the job.
+    let mut random = crate::blake2b512(b"a").to_bytes().to_vec();
+    random.extend_from_slice(&crate::blake2b512(b"b").to_bytes());
+    let a = encrypt_message(&key, &Message::from_bytes(&random[..97])).unwrap();
+    let b = encrypt_message(&key, &Message::from_bytes(&random[..100])).unwrap();
+
+    assert_eq!(a.data[0], compression::Compression::None.to_byte());
+    assert_eq!(a.data.len(), b.data.len());
+  }
+
+  #[test]
+  fn should_authenticate_compression_algorithm() {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V2);
+    let encrypted = encrypt_message(&key, &Message::from_str(&"abc".repeat(10))).unwrap();
+
+    let mut data = encrypted.data.to_vec();
+    data[0] = compression::Compression::None.to_byte();
+    let tampered = EncryptedMessage {
+      data: data.into(),
+      ..encrypted.clone()
+    };
+    assert_eq!(
+      decrypt_message(&key, &tampered),
+      Err(Error::EncryptionError)
+    );
+
+    let mut data = encrypted.data.to_vec();
+    data[0] = 15;
+    let unknown = EncryptedMessage {
+      data: data.into(),
+      ..encrypted
+    };
+    assert_eq!(
+      decrypt_message(&key, &unknown),
+      Err(Error::UnsupportedVersion)
+    );
+  }
+
+  #[test]
+  fn should_encrypt_and_decrypt_with_xchacha() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V3);
+    let message = Message::from_str("Hello World!");
+
+    // when
+    let encrypted1 = encrypt_message(&key, &message).unwrap();
+    let encrypted2 = encrypt_message(&key, &message).unwrap();
+    let parts = encrypted1.clone().split_and_encode(Some(10));
+    let collated = EncryptedMessage::collate_from_parts(parts).unwrap();
+    let decrypted = decrypt_message(&key, &collated).unwrap();
+    let decoded_key = MessageEncryptionKey::decode(&key.encode()).unwrap();
+
+    // then
+    assert_eq!(collated, encrypted1);
+    assert_eq!(encrypted1.nonce.len(), XNONCE_SIZE);
+    assert_ne!(encrypted1.nonce, encrypted2.nonce);
+    assert_ne!(encrypted1.data, encrypted2.data);
+    assert_eq!(decrypted, message);
+    assert_eq!(decoded_key.version(), EncryptionKeyVersion::V3);
+  }
+
+  #[test]
+  fn should_authenticate_message_header() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V4);
+    let message = Message::from_str("Hello World!");
+    let header = MessageHeader::new(PayloadFormat::Raw, [5u8; header::TESTAMENT_ID_SIZE], 1234);
+
+    // when
+    let encrypted = encrypt_message_with_header(&key, &message, &header).unwrap();
+    let parts = encrypted.clone().split_and_encode(Some(10));
+    let collated = EncryptedMessage::collate_from_parts(parts).unwrap();
+    let (decrypted, authenticated) = decrypt_message_with_header(&key, &collated).unwrap();
+
+    let mut tampered = encrypted.clone();
+    tampered.data = {
+      let mut data = tampered.data.to_vec();
+      // claim the payload is a container
+      data[0] = PayloadFormat::Container.to_byte();
+      data.into()
+    };
+    let mut downgraded = encrypted.clone();
+    downgraded.version = EncryptionKeyVersion::V0;
+
+    // then
+    assert_eq!(decrypted, message);
+    assert_eq!(authenticated, Some(header));
+    assert_eq!(
+      tampered.unverified_header().map(|h| h.format()),
+      Some(PayloadFormat::Container)
+    );
+    assert_eq!(
+      decrypt_message(&key, &tampered),
+      Err(Error::EncryptionError)
+    );
+    assert_eq!(
+      decrypt_message(&key, &downgraded),
+      Err(Error::EncryptionError)
+    );
+    assert_eq!(downgraded.unverified_header(), None);
+  }
+
+  #[test]
+  fn should_decrypt_using_message_version() {
+    // given
+    let v0_key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let v1_key = MessageEncryptionKey::new([1u8; KEY_SIZE]).with_version(EncryptionKeyVersion::V1);
+    let message = Message::from_str("Hello World!");
+
+    // when
+    let encrypted = encrypt_message(&v1_key, &message).unwrap();
+    let decrypted = decrypt_message(&v0_key, &encrypted).unwrap();
+
+    // then
+    assert_eq!(decrypted, message);
+  }
+}
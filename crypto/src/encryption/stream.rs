@@ -0,0 +1,261 @@
+//! Streaming message encryption.
+//!
+//! The [EncryptionKeyVersion::V1] scheme splits the plaintext into segments
+//! of [SEGMENT_SIZE] bytes and encrypts each of them using `AES-GCM-SIV`
+//! following the `STREAM` construction (big-endian 32-bit segment counter
+//! and a flag marking the last segment appended to the nonce prefix).
+//!
+//! Since every segment is authenticated separately, but the nonce binds
+//! both it's position and the information whether it's the last one,
+//! re-ordering, dropping or truncating the segments is detected during
+//! decryption.
+//!
+//! Contrary to [super::encrypt_message] the functions here never hold
+//! the entire message in memory, so they are not limited by the size
+//! of a single [super::EncryptedMessage].
+
+use std::io::{Read, Write};
+
+use aes_gcm_siv::{
+  aead::{
+    rand_core::RngCore,
+    stream::{DecryptorBE32, EncryptorBE32},
+    OsRng, Payload,
+  },
+  Aes256GcmSiv, KeyInit,
+};
+
+use super::{EncryptionKeyVersion, Error, MessageEncryptionKey, AAD, KEY_SIZE};
+
+/// Byte size of a single plaintext segment.
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Byte size of the nonce prefix.
+///
+/// `STREAM` construction uses 5 out of 12 nonce bytes for the segment
+/// counter and the last segment flag.
+pub const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Byte size of the authentication tag appended to every segment.
+pub const TAG_SIZE: usize = 16;
+
+/// Encrypt everything that can be read from `reader` and write it to `writer`.
+///
+/// The output is always using [EncryptionKeyVersion::V1] scheme
+/// and a random nonce prefix.
+///
+/// ```markdown
+/// +--------------------------------+
+/// | version (1 byte)               |
+/// +--------------------------------+
+/// | nonce prefix (7 bytes)         |
+/// +--------------------------------+
+/// | segments (variable length)     | // SEGMENT_SIZE + TAG_SIZE each
+/// +--------------------------------+ // the last one might be shorter
+/// ```
+pub fn encrypt_stream(
+  key: &MessageEncryptionKey,
+  mut reader: impl Read,
+  mut writer: impl Write,
+) -> Result<(), Error> {
+  let mut nonce = [0u8; NONCE_PREFIX_SIZE];
+  OsRng.fill_bytes(&mut nonce);
+
+  writer.write_all(&[EncryptionKeyVersion::V1.to_byte()])?;
+  writer.write_all(&nonce)?;
+  encrypt_segments(&key.key, &nonce, &mut reader, &mut writer)
+}
+
+/// Decrypt the data produced by [encrypt_stream] and write the plaintext to `writer`.
+///
+/// Note that the plaintext segments are written as soon as they are authenticated,
+/// so in case of an error the `writer` might already contain some of the data.
+/// It MUST NOT be trusted unless the function returns `Ok`.
+pub fn decrypt_stream(
+  key: &MessageEncryptionKey,
+  mut reader: impl Read,
+  mut writer: impl Write,
+) -> Result<(), Error> {
+  let mut version = [0u8];
+  reader.read_exact(&mut version)?;
+  if EncryptionKeyVersion::from_byte(version[0]) != Some(EncryptionKeyVersion::V1) {
+    return Err(Error::UnsupportedVersion);
+  }
+
+  let mut nonce = [0u8; NONCE_PREFIX_SIZE];
+  reader.read_exact(&mut nonce)?;
+  decrypt_segments(&key.key, &nonce, &mut reader, &mut writer)
+}
+
+/// Return the byte size of encrypted segments given the plaintext size.
+pub fn encrypted_size(plaintext_size: usize) -> usize {
+  // Note the last segment is always present, even if empty.
+  (plaintext_size / SEGMENT_SIZE + 1) * TAG_SIZE + plaintext_size
+}
+
+pub(crate) fn encrypt_segments(
+  key: &[u8; KEY_SIZE],
+  nonce: &[u8; NONCE_PREFIX_SIZE],
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+) -> Result<(), Error> {
+  let cipher = Aes256GcmSiv::new(key.into());
+  let mut encryptor = EncryptorBE32::from_aead(cipher, nonce.into());
+  let mut buffer = vec![0u8; SEGMENT_SIZE];
+
+  loop {
+    let len = read_segment(reader, &mut buffer)?;
+    let msg = &buffer[..len];
+    // A segment that is not full has to be the last one.
+    // In case the plaintext is a multiple of `SEGMENT_SIZE`
+    // the last segment is going to be empty.
+    if len < SEGMENT_SIZE {
+      let encrypted = encryptor.encrypt_last(Payload { msg, aad: AAD })?;
+      writer.write_all(&encrypted)?;
+      break;
+    }
+    let encrypted = encryptor.encrypt_next(Payload { msg, aad: AAD })?;
+    writer.write_all(&encrypted)?;
+  }
+
+  crate::wipe(&mut buffer);
+  writer.flush()?;
+  Ok(())
+}
+
+pub(crate) fn decrypt_segments(
+  key: &[u8; KEY_SIZE],
+  nonce: &[u8; NONCE_PREFIX_SIZE],
+  reader: &mut impl Read,
+  writer: &mut impl Write,
+) -> Result<(), Error> {
+  let cipher = Aes256GcmSiv::new(key.into());
+  let mut decryptor = DecryptorBE32::from_aead(cipher, nonce.into());
+  let mut buffer = vec![0u8; SEGMENT_SIZE + TAG_SIZE];
+
+  loop {
+    let len = read_segment(reader, &mut buffer)?;
+    let msg = &buffer[..len];
+    if len < buffer.len() {
+      let mut decrypted = decryptor.decrypt_last(Payload { msg, aad: AAD })?;
+      writer.write_all(&decrypted)?;
+      crate::wipe(&mut decrypted);
+      break;
+    }
+    let mut decrypted = decryptor.decrypt_next(Payload { msg, aad: AAD })?;
+    writer.write_all(&decrypted)?;
+    crate::wipe(&mut decrypted);
+  }
+
+  writer.flush()?;
+  Ok(())
+}
+
+/// Read as many bytes as possible to fill up the buffer.
+///
+/// Returns the number of bytes read, which is only lower than
+/// the length of the buffer in case we reached the end of the stream.
+fn read_segment(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, Error> {
+  let mut len = 0;
+  while len < buffer.len() {
+    match reader.read(&mut buffer[len..]) {
+      Ok(0) => break,
+      Ok(read) => len += read,
+      Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e.into()),
+    }
+  }
+  Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn data(len: usize) -> Vec<u8> {
+    (0..len).map(|x| (x % 251) as u8).collect()
+  }
+
+  fn roundtrip(len: usize) -> Vec<u8> {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let plaintext = data(len);
+    let mut encrypted = vec![];
+    encrypt_stream(&key, &*plaintext, &mut encrypted).unwrap();
+    assert_eq!(encrypted.len(), 1 + NONCE_PREFIX_SIZE + encrypted_size(len));
+
+    let mut decrypted = vec![];
+    decrypt_stream(&key, &*encrypted, &mut decrypted).unwrap();
+    assert_eq!(decrypted, plaintext);
+    encrypted
+  }
+
+  #[test]
+  fn should_encrypt_and_decrypt_a_stream() {
+    roundtrip(0);
+    roundtrip(11);
+    roundtrip(SEGMENT_SIZE - 1);
+    roundtrip(SEGMENT_SIZE);
+    roundtrip(2 * SEGMENT_SIZE + 17);
+  }
+
+  #[test]
+  fn should_use_random_nonce_prefix() {
+    let a = roundtrip(5);
+    let b = roundtrip(5);
+
+    assert_eq!(a[0], 1);
+    assert!(a[1..1 + NONCE_PREFIX_SIZE] != b[1..1 + NONCE_PREFIX_SIZE]);
+  }
+
+  #[test]
+  fn should_detect_truncation() {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let encrypted = roundtrip(2 * SEGMENT_SIZE);
+    let header = 1 + NONCE_PREFIX_SIZE;
+
+    // drop the last (empty) segment
+    let truncated = &encrypted[..encrypted.len() - TAG_SIZE];
+    let res = decrypt_stream(&key, truncated, &mut vec![]);
+    assert_eq!(res, Err(Error::EncryptionError));
+
+    // drop the two last segments
+    let truncated = &encrypted[..header + SEGMENT_SIZE + TAG_SIZE];
+    let res = decrypt_stream(&key, truncated, &mut vec![]);
+    assert_eq!(res, Err(Error::EncryptionError));
+
+    // drop a single byte
+    let truncated = &encrypted[..encrypted.len() - 1];
+    let res = decrypt_stream(&key, truncated, &mut vec![]);
+    assert_eq!(res, Err(Error::EncryptionError));
+  }
+
+  #[test]
+  fn should_detect_reordered_segments() {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let encrypted = roundtrip(2 * SEGMENT_SIZE + 3);
+    let header = 1 + NONCE_PREFIX_SIZE;
+    let segment = SEGMENT_SIZE + TAG_SIZE;
+
+    let mut reordered = encrypted[..header].to_vec();
+    reordered.extend_from_slice(&encrypted[header + segment..header + 2 * segment]);
+    reordered.extend_from_slice(&encrypted[header..header + segment]);
+    reordered.extend_from_slice(&encrypted[header + 2 * segment..]);
+
+    let res = decrypt_stream(&key, &*reordered, &mut vec![]);
+    assert_eq!(res, Err(Error::EncryptionError));
+  }
+
+  #[test]
+  fn should_reject_invalid_header() {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let mut encrypted = roundtrip(3);
+
+    let res = decrypt_stream(&key, &[][..], &mut vec![]);
+    assert_eq!(res, Err(Error::Io(std::io::ErrorKind::UnexpectedEof)));
+
+    encrypted[0] = 0;
+    let res = decrypt_stream(&key, &*encrypted, &mut vec![]);
+    assert_eq!(res, Err(Error::UnsupportedVersion));
+  }
+}
@@ -0,0 +1,224 @@
+//! Owner identity keys.
+//!
+//! Anyone can craft a [crate::shamir::Chunk] or an [crate::encryption::EncryptedMessagePart]
+//! that looks genuine. To let the recipients detect spoofed pieces, the owner may
+//! hold a long-term `Ed25519` [IdentityKey] and sign every chunk and message part
+//! when securing the message.
+//!
+//! The signer is identified by a short [IdentityPublicKey::fingerprint], which
+//! the recipients can compare with the one shared by the owner.
+
+use aes_gcm_siv::aead::OsRng;
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::blake2b512;
+
+/// Byte size of the secret identity key.
+pub const IDENTITY_KEY_SIZE: usize = 32;
+
+/// Byte size of the public identity key.
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Byte size of the signature.
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Byte size of the encoded [Signature] (public key and signature).
+pub const SIGNED_BY_SIZE: usize = PUBLIC_KEY_SIZE + SIGNATURE_SIZE;
+
+/// Number of public key hash bytes used for the fingerprint.
+const FINGERPRINT_SIZE: usize = 8;
+
+/// An error which may occur when verifying a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SignatureError {
+  /// The public key is not a valid `Ed25519` point.
+  #[error("The public key is invalid.")]
+  InvalidPublicKey,
+  /// The encoded signature has invalid length.
+  #[error("The signature is malformed.")]
+  MalformedSignature,
+  /// The signature does not match the signed data.
+  #[error("The signature is invalid.")]
+  InvalidSignature,
+}
+
+/// Secret `Ed25519` key identifying the owner of the message.
+pub struct IdentityKey {
+  key: SigningKey,
+}
+
+impl IdentityKey {
+  /// Generate a new random identity key.
+  pub fn generate() -> Self {
+    Self {
+      key: SigningKey::generate(&mut OsRng),
+    }
+  }
+
+  /// Create the identity key from it's raw secret bytes.
+  pub fn from_bytes(key: &[u8; IDENTITY_KEY_SIZE]) -> Self {
+    Self {
+      key: SigningKey::from_bytes(key),
+    }
+  }
+
+  /// Return the raw secret bytes of the key.
+  pub fn to_bytes(&self) -> [u8; IDENTITY_KEY_SIZE] {
+    self.key.to_bytes()
+  }
+
+  /// Return the public part of the key.
+  pub fn public_key(&self) -> IdentityPublicKey {
+    IdentityPublicKey {
+      key: self.key.verifying_key(),
+    }
+  }
+
+  /// Sign given data, prefixed with `domain`.
+  pub(crate) fn sign(&self, domain: &[u8], data: &[u8]) -> Signature {
+    let signature = self.key.sign(&[domain, data].concat());
+    Signature {
+      signer: self.public_key(),
+      signature: signature.to_bytes(),
+    }
+  }
+}
+
+impl std::fmt::Debug for IdentityKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("IdentityKey")
+      .field(&self.public_key().fingerprint())
+      .finish()
+  }
+}
+
+/// Public `Ed25519` key of the owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdentityPublicKey {
+  key: VerifyingKey,
+}
+
+impl IdentityPublicKey {
+  /// Attempt to read the public key from raw bytes.
+  pub fn from_bytes(data: &[u8]) -> Result<Self, SignatureError> {
+    let data: [u8; PUBLIC_KEY_SIZE] = data
+      .try_into()
+      .map_err(|_| SignatureError::InvalidPublicKey)?;
+    let key = VerifyingKey::from_bytes(&data).map_err(|_| SignatureError::InvalidPublicKey)?;
+    Ok(Self { key })
+  }
+
+  /// Return the raw bytes of the public key.
+  pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_SIZE] {
+    self.key.to_bytes()
+  }
+
+  /// Return a short, human-readable identifier of the key.
+  ///
+  /// The fingerprint is a hex-encoded prefix of `blake2b512` hash of the key.
+  pub fn fingerprint(&self) -> String {
+    let hash = blake2b512(self.key.as_bytes());
+    hex::encode(&hash.as_slice()[..FINGERPRINT_SIZE])
+  }
+}
+
+/// A signature along with the public key of the signer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+  signer: IdentityPublicKey,
+  signature: [u8; SIGNATURE_SIZE],
+}
+
+impl Signature {
+  /// Return the public key of the signer.
+  pub fn signer(&self) -> &IdentityPublicKey {
+    &self.signer
+  }
+
+  /// Verify that the signature is valid for given data, prefixed with `domain`.
+  pub(crate) fn verify(&self, domain: &[u8], data: &[u8]) -> Result<(), SignatureError> {
+    let signature = ed25519_dalek::Signature::from_bytes(&self.signature);
+    self
+      .signer
+      .key
+      .verify(&[domain, data].concat(), &signature)
+      .map_err(|_| SignatureError::InvalidSignature)
+  }
+
+  /// Encode the signature as the public key followed by the signature bytes.
+  pub(crate) fn encode(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SIGNED_BY_SIZE);
+    out.extend_from_slice(&self.signer.to_bytes());
+    out.extend_from_slice(&self.signature);
+    out
+  }
+
+  /// Decode the signature produced by [Signature::encode].
+  pub(crate) fn decode(data: &[u8]) -> Result<Self, SignatureError> {
+    if data.len() != SIGNED_BY_SIZE {
+      return Err(SignatureError::MalformedSignature);
+    }
+    let (signer, signature) = data.split_at(PUBLIC_KEY_SIZE);
+    Ok(Self {
+      signer: IdentityPublicKey::from_bytes(signer)?,
+      signature: signature
+        .try_into()
+        .map_err(|_| SignatureError::MalformedSignature)?,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_sign_and_verify() {
+    // given
+    let key = IdentityKey::from_bytes(&[1u8; IDENTITY_KEY_SIZE]);
+
+    // when
+    let signature = key.sign(b"domain", b"data");
+    let decoded = Signature::decode(&signature.encode()).unwrap();
+
+    // then
+    assert_eq!(decoded, signature);
+    assert_eq!(decoded.signer(), &key.public_key());
+    assert_eq!(decoded.verify(b"domain", b"data"), Ok(()));
+    assert_eq!(
+      decoded.verify(b"other", b"data"),
+      Err(SignatureError::InvalidSignature)
+    );
+    assert_eq!(
+      decoded.verify(b"domain", b"Data"),
+      Err(SignatureError::InvalidSignature)
+    );
+  }
+
+  #[test]
+  fn should_compute_fingerprint() {
+    let key = IdentityKey::from_bytes(&[1u8; IDENTITY_KEY_SIZE]);
+
+    let fingerprint = key.public_key().fingerprint();
+
+    assert_eq!(fingerprint.len(), 2 * FINGERPRINT_SIZE);
+    assert_eq!(
+      format!("{:?}", key),
+      format!("IdentityKey({:?})", fingerprint)
+    );
+  }
+
+  #[test]
+  fn should_restore_key_from_bytes() {
+    let key = IdentityKey::generate();
+
+    let restored = IdentityKey::from_bytes(&key.to_bytes());
+
+    assert_eq!(restored.public_key(), key.public_key());
+    assert_eq!(
+      IdentityPublicKey::from_bytes(&key.public_key().to_bytes()),
+      Ok(key.public_key())
+    );
+  }
+}
@@ -7,7 +7,13 @@ use std::ops::Deref;
 use encryption::EncryptedMessage;
 
 pub mod encryption;
+pub mod identity;
+pub mod mnemonic;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod recipient;
 pub mod shamir;
+pub mod ur;
 
 /// A top-level purpose of the crate: encrypt given message and apply given SSS configuration
 /// to split the key into chunks.
@@ -29,6 +35,70 @@ pub fn secure_message(
   Ok((encrypted, chunks))
 }
 
+/// Secure the message exactly like [secure_message], but sign every chunk
+/// with the owner's [identity::IdentityKey].
+///
+/// The encrypted message should be encoded using
+/// [EncryptedMessage::split_and_encode_signed] with the same key.
+pub fn secure_signed_message(
+  message: encryption::Message,
+  chunks_configuration: shamir::ChunksConfiguration,
+  identity: &identity::IdentityKey,
+) -> Result<(EncryptedMessage, Vec<shamir::Chunk>), encryption::Error> {
+  let (encrypted, chunks) = secure_message(message, chunks_configuration)?;
+  let chunks = chunks.into_iter().map(|c| c.sign(identity)).collect();
+
+  Ok((encrypted, chunks))
+}
+
+/// An error during securing the message for recipients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RecipientsError {
+  /// The number of recipients does not match the total number of chunks.
+  #[error("Every chunk needs a recipient.")]
+  RecipientsCountMismatch,
+  /// Message encryption error.
+  #[error("The message could not have been encrypted.")]
+  Encryption(#[from] encryption::Error),
+}
+
+/// Secure the message exactly like [secure_message], but encrypt every chunk
+/// to it's recipient, i.e. `n`-th chunk is encrypted to `n`-th recipient.
+///
+/// The number of `recipients` must match the total number of chunks.
+pub fn secure_message_for_recipients(
+  message: encryption::Message,
+  chunks_configuration: shamir::ChunksConfiguration,
+  recipients: &[recipient::RecipientPublicKey],
+) -> Result<(EncryptedMessage, Vec<shamir::EncryptedChunk>), RecipientsError> {
+  if chunks_configuration.total() != recipients.len() {
+    return Err(RecipientsError::RecipientsCountMismatch);
+  }
+  let (encrypted, chunks) = secure_message(message, chunks_configuration)?;
+  let chunks = chunks
+    .iter()
+    .zip(recipients)
+    .map(|(chunk, recipient)| chunk.encrypt_to(recipient))
+    .collect();
+
+  Ok((encrypted, chunks))
+}
+
+/// Secure the message exactly like [secure_message], but instead of the complete
+/// encrypted message return it dispersed into fragments (see [encryption::dispersal]).
+///
+/// Every recipient should receive `n`-th fragment along with `n`-th chunk, so that
+/// any `required` recipients have enough fragments and chunks to restore the message.
+pub fn secure_dispersed_message(
+  message: encryption::Message,
+  chunks_configuration: shamir::ChunksConfiguration,
+) -> Result<(Vec<encryption::MessageFragment>, Vec<shamir::Chunk>), encryption::Error> {
+  let (encrypted, chunks) = secure_message(message, chunks_configuration)?;
+  let fragments = encryption::dispersal::disperse(encrypted, chunks_configuration);
+
+  Ok((fragments, chunks))
+}
+
 /// An error during message restoration process.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum RestorationError {
@@ -38,6 +108,12 @@ pub enum RestorationError {
   /// Message decryption error.
   #[error("The message could not have been decrypted.")]
   Decryption(#[from] encryption::Error),
+  /// The message parts and chunks are signed by different owners.
+  #[error("The message and chunks are signed by different owners.")]
+  SignerMismatch,
+  /// The dispersed message could not be reassembled.
+  #[error("The message could not be reassembled from given fragments.")]
+  Dispersal(#[from] encryption::DispersalError),
 }
 
 /// Restoring a message given the encrypted bytes and chunks required to recover the key.
@@ -45,16 +121,36 @@ pub enum RestorationError {
 /// The method will do the following:
 /// 1. Attempt to recover the key from given chunks.
 /// 2. Use the key to decrypt the provided encrypted message.
+///
+/// In case both the message parts and the chunks are signed, the signer must be the same.
 pub fn restore_message(
   encrypted_message: encryption::EncryptedMessage,
   chunks: Vec<shamir::Chunk>,
 ) -> Result<encryption::Message, RestorationError> {
   let key = shamir::recover_key(&chunks)?;
+  // signatures of the chunks are already verified during recovery.
+  let chunks_signer = chunks.first().and_then(|c| c.signer().ok().flatten());
+  if let (Some(a), Some(b)) = (chunks_signer.as_ref(), encrypted_message.signer()) {
+    if a != b {
+      return Err(RestorationError::SignerMismatch);
+    }
+  }
   let message = encryption::decrypt_message(&key, &encrypted_message)?;
 
   Ok(message)
 }
 
+/// Restore the message secured with [secure_dispersed_message].
+///
+/// At least `required` fragments and chunks are needed.
+pub fn restore_dispersed_message(
+  fragments: &[encryption::MessageFragment],
+  chunks: Vec<shamir::Chunk>,
+) -> Result<encryption::Message, RestorationError> {
+  let encrypted_message = encryption::dispersal::reassemble(fragments)?;
+  restore_message(encrypted_message, chunks)
+}
+
 /// Write zeros into given slice to wipe out the previous content.
 ///
 /// TODO [ToDr] use zeroize crate and copy the notes from there.
@@ -214,4 +310,65 @@ mod tests {
 
     assert_eq!(restored, encryption::Message::from_str(str_message));
   }
+
+  #[test]
+  fn should_secure_the_message_for_recipients() {
+    // given
+    let keys: Vec<_> = (0..3)
+      .map(|_| recipient::RecipientKey::generate())
+      .collect();
+    let recipients: Vec<_> = keys.iter().map(|k| k.public_key()).collect();
+    let message = encryption::Message::from_str("Hello World!");
+    let chunks_configuration = shamir::ChunksConfiguration::new(2, 1).unwrap();
+
+    // when
+    let (encrypted, chunks) =
+      secure_message_for_recipients(message, chunks_configuration, &recipients).unwrap();
+    let decrypted = vec![
+      chunks[0].decrypt(&keys[0]).unwrap(),
+      chunks[2].decrypt(&keys[2]).unwrap(),
+    ];
+    let restored = restore_message(encrypted, decrypted).unwrap();
+
+    // then
+    assert_eq!(restored, encryption::Message::from_str("Hello World!"));
+    assert!(chunks[1].decrypt(&keys[0]).is_err());
+  }
+
+  #[test]
+  fn should_reject_mismatched_number_of_recipients() {
+    // given
+    let recipients = vec![recipient::RecipientKey::generate().public_key(); 2];
+    let message = encryption::Message::from_str("Hello World!");
+    let chunks_configuration = shamir::ChunksConfiguration::new(2, 1).unwrap();
+
+    // when
+    let result = secure_message_for_recipients(message, chunks_configuration, &recipients);
+
+    // then
+    assert!(matches!(
+      result,
+      Err(RecipientsError::RecipientsCountMismatch)
+    ));
+  }
+
+  #[test]
+  fn should_secure_the_message_dispersed() {
+    // given
+    let message = encryption::Message::from_str("Hello World!");
+    let chunks_configuration = shamir::ChunksConfiguration::new(2, 1).unwrap();
+
+    // when
+    let (fragments, mut chunks) = secure_dispersed_message(message, chunks_configuration).unwrap();
+    let restored = restore_dispersed_message(&fragments[1..3], chunks.split_off(1)).unwrap();
+    let not_enough = restore_dispersed_message(&fragments[0..1], chunks);
+
+    // then
+    assert_eq!(fragments.len(), 3);
+    assert_eq!(restored, encryption::Message::from_str("Hello World!"));
+    assert_eq!(
+      not_enough.unwrap_err(),
+      RestorationError::Dispersal(encryption::DispersalError::NotEnoughFragments)
+    );
+  }
 }
@@ -0,0 +1,292 @@
+//! Mnemonic word encoding of chunks and keys.
+//!
+//! Users familiar with cryptocurrency wallets are used to write down seeds
+//! as a list of words. This module encodes [Chunk]s and [MessageEncryptionKey]s
+//! (e.g. for `1-of-1` setups) using the BIP39 English wordlist.
+//!
+//! The encoded bytes (without the magic sequence) are split into 11-bit words.
+//! The last word is padded with checksum bits and followed by a whole checksum word.
+//! The checksum is a prefix of `blake2b512(magic ++ bytes)`, hence the magic
+//! sequence identifies the type of the encoded value.
+//!
+//! Chunks are encoded using the compact encoding (see [crate::shamir::compact])
+//! to keep the list short. Every word can be abbreviated to it's first 4 letters.
+
+use bip39::Language;
+
+use crate::encryption::{KeyDecodingError, MessageEncryptionKey, KEY_ENCODING_MAGIC_SEQUENCE};
+use crate::shamir::{Chunk, ChunkDecodingError, CHUNK_ENCODING_MAGIC_SEQUENCE};
+
+/// Number of bits encoded by a single word.
+const BITS_PER_WORD: usize = 11;
+
+/// Length of the unique word prefix.
+const PREFIX_LEN: usize = 4;
+
+/// An error which may occur when decoding the mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MnemonicError {
+  /// The word at given position (0-based) is not on the wordlist.
+  #[error("Unknown word at position {0}.")]
+  UnknownWord(usize),
+  /// The checksum does not match, some of the words are incorrect.
+  #[error("Invalid checksum. Check the order and spelling of the words.")]
+  InvalidChecksum,
+  /// The words do not encode a valid chunk.
+  #[error("{0}")]
+  Chunk(#[from] ChunkDecodingError),
+  /// The words do not encode a valid key.
+  #[error("{0}")]
+  Key(#[from] KeyDecodingError),
+}
+
+/// The value decoded from the mnemonic.
+pub enum Mnemonic {
+  /// The words encode a [Chunk].
+  Chunk(Chunk),
+  /// The words encode a [MessageEncryptionKey].
+  Key(MessageEncryptionKey),
+}
+
+/// Encode the chunk as a list of words separated by spaces.
+pub fn chunk_to_mnemonic(chunk: &Chunk) -> String {
+  let encoded = chunk.encode_compact();
+  encode(
+    CHUNK_ENCODING_MAGIC_SEQUENCE,
+    &encoded[CHUNK_ENCODING_MAGIC_SEQUENCE.len()..],
+  )
+}
+
+/// Encode the key as a list of words separated by spaces.
+pub fn key_to_mnemonic(key: MessageEncryptionKey) -> String {
+  let mut encoded = key.encode();
+  let out = encode(
+    KEY_ENCODING_MAGIC_SEQUENCE,
+    &encoded[KEY_ENCODING_MAGIC_SEQUENCE.len()..],
+  );
+  encoded.wipe();
+  out
+}
+
+/// Decode the chunk or key encoded with [chunk_to_mnemonic] or [key_to_mnemonic].
+///
+/// The words may be separated by whitespace or commas and optionally numbered (`1. word`).
+pub fn decode_mnemonic(words: &str) -> Result<Mnemonic, MnemonicError> {
+  let indices = parse_words(words)?;
+  // the checksum may accidentally match for the other magic or length,
+  // so a candidate that fails to decode does not end the search.
+  let mut error = None;
+  for magic in [CHUNK_ENCODING_MAGIC_SEQUENCE, KEY_ENCODING_MAGIC_SEQUENCE] {
+    for bytes in decode(magic, &indices) {
+      let mut encoded = [magic, &bytes[..]].concat();
+      let decoded = if magic == CHUNK_ENCODING_MAGIC_SEQUENCE {
+        Chunk::decode(&encoded)
+          .map(Mnemonic::Chunk)
+          .map_err(MnemonicError::from)
+      } else {
+        let key = MessageEncryptionKey::decode(&encoded)
+          .map(Mnemonic::Key)
+          .map_err(MnemonicError::from);
+        crate::wipe(&mut encoded);
+        key
+      };
+      match decoded {
+        Ok(decoded) => return Ok(decoded),
+        Err(e) => {
+          error.get_or_insert(e);
+        }
+      }
+    }
+  }
+  Err(error.unwrap_or(MnemonicError::InvalidChecksum))
+}
+
+fn encode(magic: &[u8], bytes: &[u8]) -> String {
+  let words_count = (bytes.len() * 8).div_ceil(BITS_PER_WORD) + 1;
+  let checksum = checksum(magic, bytes);
+  let bits = bits(bytes).chain(bits(checksum.as_slice()));
+  let list = Language::English.word_list();
+
+  let mut words = Vec::with_capacity(words_count);
+  let mut index = 0usize;
+  for (i, bit) in bits.take(words_count * BITS_PER_WORD).enumerate() {
+    index = (index << 1) | bit as usize;
+    if (i + 1) % BITS_PER_WORD == 0 {
+      words.push(list[index]);
+      index = 0;
+    }
+  }
+  words.join(" ")
+}
+
+/// Find the byte lengths for which the padding and checksum word match.
+fn decode(magic: &[u8], indices: &[u16]) -> Vec<Vec<u8>> {
+  let Some(data_bits) = indices.len().checked_sub(1).map(|n| n * BITS_PER_WORD) else {
+    return vec![];
+  };
+  let all_bits: Vec<bool> = indices
+    .iter()
+    .flat_map(|index| (0..BITS_PER_WORD).rev().map(move |i| (index >> i) & 1 == 1))
+    .collect();
+
+  // the padding is shorter than a word, so there are at most two candidates.
+  (data_bits.saturating_sub(BITS_PER_WORD - 1).div_ceil(8)..=data_bits / 8)
+    .filter_map(|len| {
+      let bytes: Vec<u8> = all_bits[..len * 8]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8))
+        .collect();
+      let checksum = checksum(magic, &bytes);
+      let expected = bits(checksum.as_slice()).take(all_bits.len() - len * 8);
+      if len > 0 && expected.eq(all_bits[len * 8..].iter().copied()) {
+        Some(bytes)
+      } else {
+        None
+      }
+    })
+    .collect()
+}
+
+fn checksum(magic: &[u8], bytes: &[u8]) -> crate::Hash {
+  crate::blake2b512(&[magic, bytes].concat())
+}
+
+fn bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+  bytes
+    .iter()
+    .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+}
+
+/// Find the wordlist indices tolerating unique prefixes of the words.
+fn parse_words(words: &str) -> Result<Vec<u16>, MnemonicError> {
+  let list = Language::English.word_list();
+  words
+    .split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|w| !w.is_empty())
+    // skip the numbering of the words.
+    .filter(|w| {
+      !w.trim_end_matches(['.', ')'])
+        .chars()
+        .all(|c| c.is_ascii_digit())
+    })
+    .enumerate()
+    .map(|(position, word)| {
+      let word = word.to_lowercase();
+      if let Some(index) = Language::English.find_word(&word) {
+        return Ok(index);
+      }
+      if word.len() < PREFIX_LEN {
+        return Err(MnemonicError::UnknownWord(position));
+      }
+      // the first 4 letters of every word on the list are unique.
+      list
+        .iter()
+        .position(|w| w.starts_with(&word))
+        .map(|index| index as u16)
+        .ok_or(MnemonicError::UnknownWord(position))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use crate::shamir::{recover_key, split_into_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_encode_and_decode_key() {
+    // given
+    let key = MessageEncryptionKey::new([0u8; KEY_SIZE]);
+
+    // when
+    let words = key_to_mnemonic(key);
+    let abbreviated: Vec<String> = words
+      .split(' ')
+      .enumerate()
+      .map(|(i, w)| format!("{}. {}", i + 1, &w[..w.len().min(PREFIX_LEN)]))
+      .collect();
+    let decoded = match decode_mnemonic(&abbreviated.join(",\n")).unwrap() {
+      Mnemonic::Key(key) => key,
+      Mnemonic::Chunk(_) => panic!("Expected a key"),
+    };
+
+    // then
+    assert_eq!(words.split(' ').count(), 25);
+    assert!(words.starts_with("abandon abandon"));
+    assert_eq!(
+      decoded.encode(),
+      MessageEncryptionKey::new([0u8; KEY_SIZE]).encode()
+    );
+  }
+
+  #[test]
+  fn should_encode_and_decode_chunks() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let chunks = split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap());
+
+    // when
+    let words: Vec<_> = chunks.iter().map(chunk_to_mnemonic).collect();
+    let decoded: Vec<Chunk> = words
+      .iter()
+      .map(|w| match decode_mnemonic(w).unwrap() {
+        Mnemonic::Chunk(chunk) => chunk,
+        Mnemonic::Key(_) => panic!("Expected a chunk"),
+      })
+      .collect();
+
+    // then
+    assert_eq!(words[0].split(' ').count(), 43);
+    assert_eq!(decoded[2].set_id(), chunks[2].set_id());
+    assert!(recover_key(&decoded[1..]).is_ok());
+  }
+
+  #[test]
+  fn should_detect_invalid_words() {
+    // given
+    let words = key_to_mnemonic(MessageEncryptionKey::new([7u8; KEY_SIZE]));
+    let mut swapped: Vec<&str> = words.split(' ').collect();
+    swapped.swap(3, 4);
+
+    // when
+    let unknown = decode_mnemonic(&words.replacen(' ', " xyz ", 1));
+    let invalid = decode_mnemonic(&swapped.join(" "));
+
+    // then
+    assert!(matches!(unknown, Err(MnemonicError::UnknownWord(1))));
+    assert!(matches!(invalid, Err(MnemonicError::InvalidChecksum)));
+  }
+
+  #[test]
+  fn should_decode_keys_matching_chunk_checksum() {
+    // given
+    let keys: Vec<[u8; KEY_SIZE]> = (0u32..10_000)
+      .map(|i| {
+        let mut key = [0u8; KEY_SIZE];
+        key.copy_from_slice(&crate::blake2b512(&i.to_le_bytes()).as_slice()[..KEY_SIZE]);
+        key
+      })
+      .collect();
+
+    // when
+    let failed: Vec<usize> = keys
+      .iter()
+      .enumerate()
+      .filter(|(_, key)| {
+        let words = key_to_mnemonic(MessageEncryptionKey::new(**key));
+        match decode_mnemonic(&words) {
+          Ok(Mnemonic::Key(decoded)) => {
+            decoded.encode() != MessageEncryptionKey::new(**key).encode()
+          }
+          _ => true,
+        }
+      })
+      .map(|(i, _)| i)
+      .collect();
+
+    // then
+    assert_eq!(failed, Vec::<usize>::new());
+  }
+}
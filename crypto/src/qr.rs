@@ -0,0 +1,196 @@
+//! Native rendering of QR codes.
+//!
+//! The encoded chunks and message parts are meant to be printed as QR codes.
+//! This module renders them as SVG or PNG images and computes the exact
+//! capacity of given QR version, so the message can be split into parts
+//! fitting the target QR codes without relying on a JavaScript QR library.
+
+use qrcode::bits::Bits;
+use qrcode::render::svg;
+use qrcode::types::{Mode, Version};
+use qrcode::QrCode;
+
+/// The highest QR code version (`177x177` modules).
+pub const MAX_VERSION: u8 = 40;
+
+/// Width of the quiet zone around the rendered QR code (in modules).
+pub const QUIET_ZONE: u32 = 4;
+
+/// An error occuring when rendering the QR code or computing it's capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum QrError {
+  /// The QR code version is not in `1..=40` range.
+  #[error("Invalid QR code version.")]
+  InvalidVersion,
+  /// The data does not fit into the QR code.
+  #[error("The data is too long to fit into the QR code.")]
+  DataTooLong,
+  /// The PNG image could not be encoded.
+  #[error("Unable to encode PNG image.")]
+  ImageError,
+}
+
+/// Error correction level of the QR code.
+///
+/// Higher levels can restore more damaged codes, but hold less data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcLevel {
+  /// Allows recovery of ~7% of the code.
+  Low,
+  /// Allows recovery of ~15% of the code.
+  Medium,
+  /// Allows recovery of ~25% of the code.
+  Quartile,
+  /// Allows recovery of ~30% of the code.
+  High,
+}
+
+impl EcLevel {
+  /// Parse the error correction level from it's letter (`L`, `M`, `Q` or `H`).
+  pub fn from_letter(letter: char) -> Option<Self> {
+    match letter.to_ascii_uppercase() {
+      'L' => Some(Self::Low),
+      'M' => Some(Self::Medium),
+      'Q' => Some(Self::Quartile),
+      'H' => Some(Self::High),
+      _ => None,
+    }
+  }
+
+  fn to_qrcode(self) -> qrcode::EcLevel {
+    match self {
+      Self::Low => qrcode::EcLevel::L,
+      Self::Medium => qrcode::EcLevel::M,
+      Self::Quartile => qrcode::EcLevel::Q,
+      Self::High => qrcode::EcLevel::H,
+    }
+  }
+}
+
+/// Return the maximal number of characters fitting into the QR code of given `version`.
+///
+/// With `alphanumeric` set, the characters are expected to be from the QR alphanumeric
+/// set (uppercase letters, digits, space and `$%*+-./:`), otherwise any bytes are assumed.
+pub fn capacity(version: u8, ec_level: EcLevel, alphanumeric: bool) -> Result<usize, QrError> {
+  if !(1..=MAX_VERSION).contains(&version) {
+    return Err(QrError::InvalidVersion);
+  }
+  let version = Version::Normal(version.into());
+  let max_bits = Bits::new(version)
+    .max_len(ec_level.to_qrcode())
+    .map_err(|_| QrError::InvalidVersion)?;
+  let mode = if alphanumeric {
+    Mode::Alphanumeric
+  } else {
+    Mode::Byte
+  };
+  let data_bits = max_bits - version.mode_bits_count() - mode.length_bits_count(version);
+  Ok(match mode {
+    // pairs of characters take 11 bits, the last odd one takes 6 bits.
+    Mode::Alphanumeric => data_bits / 11 * 2 + usize::from(data_bits % 11 >= 6),
+    _ => data_bits / 8,
+  })
+}
+
+/// Render given `data` as a QR code in SVG format.
+///
+/// The smallest QR version fitting the data is used, each module being
+/// `module_size` units wide.
+pub fn render_svg(data: &str, ec_level: EcLevel, module_size: u32) -> Result<String, QrError> {
+  let code = encode(data, ec_level)?;
+  Ok(
+    code
+      .render::<svg::Color>()
+      .quiet_zone(true)
+      .module_dimensions(module_size, module_size)
+      .build(),
+  )
+}
+
+/// Render given `data` as a QR code in (grayscale) PNG format.
+///
+/// The smallest QR version fitting the data is used, each module being
+/// `module_size` pixels wide.
+pub fn render_png(data: &str, ec_level: EcLevel, module_size: u32) -> Result<Vec<u8>, QrError> {
+  let code = encode(data, ec_level)?;
+  let module_size = module_size.max(1) as usize;
+  let modules = code.width();
+  let colors = code.to_colors();
+  let size = (modules + 2 * QUIET_ZONE as usize) * module_size;
+  let mut pixels = vec![0xffu8; size * size];
+  for (i, color) in colors.iter().enumerate() {
+    if *color == qrcode::Color::Light {
+      continue;
+    }
+    let x = (i % modules + QUIET_ZONE as usize) * module_size;
+    let y = (i / modules + QUIET_ZONE as usize) * module_size;
+    for row in pixels[y * size..].chunks_mut(size).take(module_size) {
+      row[x..x + module_size].fill(0);
+    }
+  }
+
+  let mut out = vec![];
+  let mut encoder = png::Encoder::new(&mut out, size as u32, size as u32);
+  encoder.set_color(png::ColorType::Grayscale);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(&pixels))
+    .map_err(|_| QrError::ImageError)?;
+  Ok(out)
+}
+
+fn encode(data: &str, ec_level: EcLevel) -> Result<QrCode, QrError> {
+  QrCode::with_error_correction_level(data, ec_level.to_qrcode()).map_err(|_| QrError::DataTooLong)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_compute_capacity() {
+    // when
+    let capacities = [
+      capacity(1, EcLevel::Low, false),
+      capacity(1, EcLevel::Low, true),
+      capacity(10, EcLevel::Medium, false),
+      capacity(10, EcLevel::Medium, true),
+      capacity(40, EcLevel::Low, false),
+      capacity(40, EcLevel::High, true),
+    ];
+
+    // then
+    assert_eq!(capacities, [17, 25, 213, 311, 2953, 1852].map(Ok));
+    assert_eq!(
+      capacity(41, EcLevel::Low, false),
+      Err(QrError::InvalidVersion)
+    );
+  }
+
+  #[test]
+  fn should_render_data_fitting_the_version() {
+    // given
+    let data = "A".repeat(capacity(5, EcLevel::Quartile, true).unwrap());
+    let too_long = "a".repeat(capacity(40, EcLevel::Low, false).unwrap() + 1);
+
+    // when
+    let code = encode(&data, EcLevel::Quartile).unwrap();
+    let svg = render_svg(&data, EcLevel::Quartile, 4).unwrap();
+    let png = render_png(&data, EcLevel::Quartile, 2).unwrap();
+
+    // then
+    assert_eq!(code.version(), Version::Normal(5));
+    assert!(svg.contains("<svg"));
+    assert_eq!(&png[1..4], b"PNG");
+    assert_eq!(
+      encode(&format!("{}A", data), EcLevel::Quartile).map(|c| c.version()),
+      Ok(Version::Normal(6))
+    );
+    assert_eq!(
+      render_svg(&too_long, EcLevel::Low, 1),
+      Err(QrError::DataTooLong)
+    );
+  }
+}
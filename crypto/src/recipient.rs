@@ -0,0 +1,223 @@
+//! Recipient keys.
+//!
+//! A plain [crate::shamir::Chunk] is usable by anyone who sees it in transit
+//! (mail servers, chat apps or the backend storing it). To prevent that,
+//! recipients may register an `X25519` [RecipientPublicKey] and the chunks
+//! are encrypted to it (see [crate::shamir::EncryptedChunk]).
+//!
+//! The keys are a raw 32-byte Curve25519 scalar and a 32-byte Montgomery point.
+//! Note the sealing scheme below is specific to this crate, it's not compatible
+//! with `age` (neither the keys' encoding nor the stanza format).
+//!
+//! Data is sealed with an ephemeral `X25519` key agreement, the shared secret
+//! is hashed (along with both public keys) into an `AES-GCM-SIV` key. Since the
+//! ephemeral key is random for every sealed item, the nonce is constant.
+
+use aes_gcm_siv::{
+  aead::{Aead, OsRng, Payload},
+  Aes256GcmSiv, KeyInit, Nonce,
+};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::{blake2b512, encryption::KEY_SIZE};
+
+/// Byte size of the secret recipient key.
+pub const RECIPIENT_KEY_SIZE: usize = 32;
+
+/// Byte size of the public recipient key.
+pub const RECIPIENT_PUBLIC_KEY_SIZE: usize = 32;
+
+/// Number of public key hash bytes used for the fingerprint.
+const FINGERPRINT_SIZE: usize = 8;
+
+/// An error which may occur when decrypting sealed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RecipientError {
+  /// The public key is malformed.
+  #[error("The public key is invalid.")]
+  InvalidPublicKey,
+  /// The data is sealed to a different recipient.
+  #[error("The data is encrypted to a different recipient.")]
+  WrongRecipient,
+  /// The data could not be decrypted (it was tampered with).
+  #[error("The data could not be decrypted.")]
+  DecryptionFailed,
+}
+
+/// Secret `X25519` key of the recipient.
+pub struct RecipientKey {
+  key: StaticSecret,
+}
+
+impl RecipientKey {
+  /// Generate a new random recipient key.
+  pub fn generate() -> Self {
+    Self {
+      key: StaticSecret::random_from_rng(OsRng),
+    }
+  }
+
+  /// Create the recipient key from it's raw secret bytes.
+  pub fn from_bytes(key: &[u8; RECIPIENT_KEY_SIZE]) -> Self {
+    Self {
+      key: StaticSecret::from(*key),
+    }
+  }
+
+  /// Return the raw secret bytes of the key.
+  pub fn to_bytes(&self) -> [u8; RECIPIENT_KEY_SIZE] {
+    self.key.to_bytes()
+  }
+
+  /// Return the public part of the key.
+  pub fn public_key(&self) -> RecipientPublicKey {
+    RecipientPublicKey {
+      key: PublicKey::from(&self.key),
+    }
+  }
+
+  /// Decrypt data produced by [RecipientPublicKey::seal] with the same `domain`.
+  pub(crate) fn open(
+    &self,
+    domain: &[u8],
+    ephemeral: &RecipientPublicKey,
+    ciphertext: &[u8],
+  ) -> Result<Vec<u8>, RecipientError> {
+    let shared = self.key.diffie_hellman(&ephemeral.key);
+    if !shared.was_contributory() {
+      return Err(RecipientError::DecryptionFailed);
+    }
+    let (cipher, aad) = cipher(domain, shared.as_bytes(), ephemeral, &self.public_key());
+    cipher
+      .decrypt(
+        Nonce::from_slice(&[0u8; 12]),
+        Payload {
+          msg: ciphertext,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| RecipientError::DecryptionFailed)
+  }
+}
+
+impl std::fmt::Debug for RecipientKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("RecipientKey")
+      .field(&self.public_key().fingerprint())
+      .finish()
+  }
+}
+
+/// Public `X25519` key of the recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecipientPublicKey {
+  key: PublicKey,
+}
+
+impl RecipientPublicKey {
+  /// Attempt to read the public key from raw bytes.
+  pub fn from_bytes(data: &[u8]) -> Result<Self, RecipientError> {
+    let data: [u8; RECIPIENT_PUBLIC_KEY_SIZE] = data
+      .try_into()
+      .map_err(|_| RecipientError::InvalidPublicKey)?;
+    Ok(Self {
+      key: PublicKey::from(data),
+    })
+  }
+
+  /// Return the raw bytes of the public key.
+  pub fn to_bytes(&self) -> [u8; RECIPIENT_PUBLIC_KEY_SIZE] {
+    self.key.to_bytes()
+  }
+
+  /// Return a short, human-readable identifier of the key.
+  ///
+  /// The fingerprint is a hex-encoded prefix of `blake2b512` hash of the key.
+  pub fn fingerprint(&self) -> String {
+    let hash = blake2b512(self.key.as_bytes());
+    hex::encode(&hash.as_slice()[..FINGERPRINT_SIZE])
+  }
+
+  /// Encrypt `data` to this recipient.
+  ///
+  /// Returns the ephemeral public key and the ciphertext.
+  pub(crate) fn seal(&self, domain: &[u8], data: &[u8]) -> (RecipientPublicKey, Vec<u8>) {
+    let secret = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral = RecipientPublicKey {
+      key: PublicKey::from(&secret),
+    };
+    let shared = secret.diffie_hellman(&self.key);
+    let (cipher, aad) = cipher(domain, shared.as_bytes(), &ephemeral, self);
+    let ciphertext = cipher
+      .encrypt(
+        Nonce::from_slice(&[0u8; 12]),
+        Payload {
+          msg: data,
+          aad: &aad,
+        },
+      )
+      .expect("The data is small enough; qed");
+    (ephemeral, ciphertext)
+  }
+}
+
+/// Derive the cipher and associated data for given shared secret.
+fn cipher(
+  domain: &[u8],
+  shared: &[u8],
+  ephemeral: &RecipientPublicKey,
+  recipient: &RecipientPublicKey,
+) -> (Aes256GcmSiv, Vec<u8>) {
+  let aad = [domain, &ephemeral.to_bytes(), &recipient.to_bytes()].concat();
+  let hash = blake2b512(&[&aad, shared].concat());
+  let cipher = Aes256GcmSiv::new_from_slice(&hash.as_slice()[..KEY_SIZE])
+    .expect("The key size is correct; qed");
+  (cipher, aad)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_seal_and_open() {
+    // given
+    let key = RecipientKey::generate();
+    let other = RecipientKey::generate();
+
+    // when
+    let (ephemeral, sealed) = key.public_key().seal(b"domain", b"data");
+
+    // then
+    assert_eq!(
+      key.open(b"domain", &ephemeral, &sealed),
+      Ok(b"data".to_vec())
+    );
+    assert_eq!(
+      key.open(b"other", &ephemeral, &sealed),
+      Err(RecipientError::DecryptionFailed)
+    );
+    assert_eq!(
+      other.open(b"domain", &ephemeral, &sealed),
+      Err(RecipientError::DecryptionFailed)
+    );
+  }
+
+  #[test]
+  fn should_restore_key_from_bytes() {
+    let key = RecipientKey::generate();
+
+    let restored = RecipientKey::from_bytes(&key.to_bytes());
+
+    assert_eq!(restored.public_key(), key.public_key());
+    assert_eq!(
+      RecipientPublicKey::from_bytes(&key.public_key().to_bytes()),
+      Ok(key.public_key())
+    );
+    assert_eq!(
+      RecipientPublicKey::from_bytes(&[1, 2, 3]),
+      Err(RecipientError::InvalidPublicKey)
+    );
+  }
+}
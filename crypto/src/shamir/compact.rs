@@ -0,0 +1,376 @@
+//! Compact chunk encoding.
+//!
+//! The regular [Chunk] encoding spends 64 bytes on the `key_hash` and a typo
+//! in a transcribed chunk is only detected when the recovery fails.
+//!
+//! The [ChunkVersion::V2] encoding only stores a short set identifier (the
+//! first [SET_ID_SIZE] bytes of the `key_hash`), uses `LEB128` varints for
+//! the numeric fields and ends with a `CRC-32` checksum, so damaged chunks are
+//! rejected during decoding.
+//!
+//! The full `key_hash` is kept only for signed or passphrase-protected chunks,
+//! since the signature and encryption are bound to it. Chunks decoded with the
+//! short identifier only know the [KeyId::Short] and the identifier is what's
+//! compared during recovery.
+
+use super::{Chunk, ChunkDecodingError, ChunkExtension, ChunkVersion, ChunksConfiguration};
+use super::{CHUNK_ENCODING_MAGIC_SEQUENCE, PASSPHRASE_TAG, SIGNATURE_TAG};
+use crate::{Bytes, Hash, HASH_SIZE};
+
+/// Byte size of the set identifier stored in the compact encoding.
+pub const SET_ID_SIZE: usize = 8;
+
+/// Byte size of the checksum.
+const CHECKSUM_SIZE: usize = 4;
+
+/// A flag indicating the full `key_hash` is stored instead of the set identifier.
+const FULL_KEY_HASH_FLAG: u8 = 0x01;
+
+/// Identifier of the key (i.e. the set of chunks) the [Chunk] is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyId {
+  /// The `blake2b512` hash of the encoded key.
+  Full(Hash),
+  /// The set identifier (a prefix of the hash) stored by compact chunks.
+  Short([u8; SET_ID_SIZE]),
+}
+
+impl KeyId {
+  /// Return the identifier of the set of chunks (a prefix of the `key_hash`).
+  pub fn set_id(&self) -> &[u8] {
+    match self {
+      Self::Full(hash) => &hash.as_slice()[..SET_ID_SIZE],
+      Self::Short(id) => id,
+    }
+  }
+
+  /// Return the full hash of the key, if known.
+  pub fn key_hash(&self) -> Option<&Hash> {
+    match self {
+      Self::Full(hash) => Some(hash),
+      Self::Short(_) => None,
+    }
+  }
+
+  /// Return the raw bytes of the identifier.
+  pub fn as_bytes(&self) -> &[u8] {
+    match self {
+      Self::Full(hash) => hash.as_slice(),
+      Self::Short(id) => id,
+    }
+  }
+
+  /// Check if both identifiers point to the same set of chunks.
+  pub(crate) fn is_same_set(&self, other: &KeyId) -> bool {
+    match (self, other) {
+      (Self::Full(a), Self::Full(b)) => a == b,
+      _ => self.set_id() == other.set_id(),
+    }
+  }
+
+  /// Check if the hash of the recovered key matches this identifier.
+  pub(crate) fn matches(&self, key_hash: &Hash) -> bool {
+    match self {
+      Self::Full(hash) => hash == key_hash,
+      Self::Short(id) => id == &key_hash.as_slice()[..SET_ID_SIZE],
+    }
+  }
+}
+
+impl From<Hash> for KeyId {
+  fn from(hash: Hash) -> Self {
+    Self::Full(hash)
+  }
+}
+
+impl Chunk {
+  /// Encode the chunk using the compact [ChunkVersion::V2] encoding.
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | magic byte sequence (5 bytes)  |
+  /// | (b"icodc")                     |
+  /// +--------------------------------+
+  /// | version (1 byte, `2`)          |
+  /// +--------------------------------+
+  /// | flags (1 byte)                 |
+  /// +--------------------------------+
+  /// | set id (8 bytes)               | // or full key_hash (64 bytes)
+  /// +--------------------------------+
+  /// | required, spare, index         | // varints
+  /// +--------------------------------+
+  /// | extensions (variable length)   | // varint count, tag, length
+  /// +--------------------------------+
+  /// | data (variable length)         |
+  /// +--------------------------------+
+  /// | checksum (4 bytes)             | // CRC-32, Big Endian
+  /// +--------------------------------+
+  /// ```
+  ///
+  /// Once decoded, the chunk keeps using the compact encoding (see [Chunk::into_compact]).
+  pub fn encode_compact(&self) -> Bytes {
+    let full_key_hash = self.key_id.key_hash().is_some()
+      && self
+        .extensions
+        .iter()
+        .any(|e| e.tag() == SIGNATURE_TAG || e.tag() == PASSPHRASE_TAG);
+
+    let mut out = vec![];
+    out.extend_from_slice(CHUNK_ENCODING_MAGIC_SEQUENCE);
+    out.push(2);
+    if full_key_hash {
+      out.push(FULL_KEY_HASH_FLAG);
+      out.extend_from_slice(self.key_id.as_bytes());
+    } else {
+      out.push(0);
+      out.extend_from_slice(self.set_id());
+    }
+    write_varint(&mut out, self.chunks_configuration.required as u64);
+    write_varint(&mut out, self.chunks_configuration.spare as u64);
+    write_varint(&mut out, self.chunk_index as u64);
+    write_varint(&mut out, self.extensions.len() as u64);
+    for extension in &self.extensions {
+      let value = extension.encode_value();
+      write_varint(&mut out, extension.tag() as u64);
+      write_varint(&mut out, value.len() as u64);
+      out.extend_from_slice(&value);
+    }
+    out.extend_from_slice(&self.chunk_data);
+    let checksum = crc32(&out);
+    out.extend_from_slice(&checksum.to_be_bytes());
+    Bytes::from(out)
+  }
+
+  /// Switch the chunk to the [ChunkVersion::V2] encoding.
+  ///
+  /// The chunk is then encoded with [Chunk::encode_compact] by [Chunk::encode].
+  pub fn into_compact(mut self) -> Self {
+    self.version = ChunkVersion::V2;
+    self
+  }
+
+  /// Return the identifier of the set of chunks (a prefix of the `key_hash`).
+  pub fn set_id(&self) -> &[u8] {
+    self.key_id.set_id()
+  }
+
+  /// Check if the chunks claim to be part of the same set.
+  pub(crate) fn has_same_key_hash(&self, other: &Chunk) -> bool {
+    self.key_id.is_same_set(&other.key_id)
+  }
+
+  /// Check if the hash of the recovered key matches this chunk.
+  pub(crate) fn matches_key_hash(&self, key_hash: &Hash) -> bool {
+    self.key_id.matches(key_hash)
+  }
+}
+
+/// Decode the [ChunkVersion::V2] chunk (including magic sequence and version byte).
+pub(crate) fn decode(encoded: &[u8]) -> Result<Chunk, ChunkDecodingError> {
+  let prefix_len = CHUNK_ENCODING_MAGIC_SEQUENCE.len() + 1;
+  if encoded.len() < prefix_len + CHECKSUM_SIZE {
+    return Err(ChunkDecodingError::NotEnoughData);
+  }
+  let (encoded, checksum) = encoded.split_at(encoded.len() - CHECKSUM_SIZE);
+  if crc32(encoded).to_be_bytes() != checksum {
+    return Err(ChunkDecodingError::ChecksumMismatch);
+  }
+
+  let mut reader = Reader {
+    data: &encoded[prefix_len..],
+  };
+  let flags = reader.bytes(1)?[0];
+  let key_id = match flags {
+    0 => {
+      let mut id = [0u8; SET_ID_SIZE];
+      id.copy_from_slice(reader.bytes(SET_ID_SIZE)?);
+      KeyId::Short(id)
+    }
+    FULL_KEY_HASH_FLAG => KeyId::Full(
+      Hash::from_slice(reader.bytes(HASH_SIZE)?).map_err(|_| ChunkDecodingError::NotEnoughData)?,
+    ),
+    _ => return Err(ChunkDecodingError::InvalidVersion),
+  };
+  let required = reader.u8()?;
+  let spare = reader.u8()?;
+  let chunks_configuration = ChunksConfiguration::new(required, spare)
+    .map_err(|_| ChunkDecodingError::InvalidConfiguration)?;
+  let chunk_index = reader.u8()?;
+
+  let mut extensions = vec![];
+  for _ in 0..reader.u8()? {
+    let tag = reader.u8()?;
+    let len = reader.varint()?;
+    let len = usize::try_from(len).map_err(|_| ChunkDecodingError::NotEnoughData)?;
+    let extension = ChunkExtension::decode(tag, reader.bytes(len)?)?;
+    if extensions
+      .iter()
+      .any(|e: &ChunkExtension| e.tag() == extension.tag())
+    {
+      return Err(ChunkDecodingError::DuplicateExtension);
+    }
+    extensions.push(extension);
+  }
+
+  Chunk {
+    version: ChunkVersion::V2,
+    key_id,
+    chunks_configuration,
+    chunk_index,
+    extensions,
+    chunk_data: Bytes::from_slice(reader.data),
+  }
+  .validated()
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn bytes(&mut self, len: usize) -> Result<&'a [u8], ChunkDecodingError> {
+    if self.data.len() < len {
+      return Err(ChunkDecodingError::NotEnoughData);
+    }
+    let (out, rest) = self.data.split_at(len);
+    self.data = rest;
+    Ok(out)
+  }
+
+  fn varint(&mut self) -> Result<u64, ChunkDecodingError> {
+    let mut out = 0u64;
+    for shift in (0..64).step_by(7) {
+      let byte = self.bytes(1)?[0];
+      out |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(out);
+      }
+    }
+    Err(ChunkDecodingError::NotEnoughData)
+  }
+
+  fn u8(&mut self) -> Result<u8, ChunkDecodingError> {
+    u8::try_from(self.varint()?).map_err(|_| ChunkDecodingError::InvalidConfiguration)
+  }
+}
+
+/// Write `LEB128` encoded unsigned integer.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+/// `CRC-32` (IEEE 802.3) checksum.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+  use crate::identity::{IdentityKey, IDENTITY_KEY_SIZE};
+  use crate::shamir::{recover_key, split_into_chunks, KeyRecoveryError};
+  use pretty_assertions::assert_eq;
+
+  fn chunks() -> Vec<Chunk> {
+    split_into_chunks(
+      MessageEncryptionKey::new([1u8; KEY_SIZE]),
+      ChunksConfiguration::new(2, 1).unwrap(),
+    )
+  }
+
+  #[test]
+  fn should_compute_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+  }
+
+  #[test]
+  fn should_encode_and_decode_compact_chunks() {
+    // given
+    let chunks = chunks();
+
+    // when
+    let encoded: Vec<_> = chunks.iter().map(Chunk::encode_compact).collect();
+    let decoded: Vec<_> = encoded.iter().map(|c| Chunk::decode(c).unwrap()).collect();
+    let recovered = recover_key(&[
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&decoded[2].encode()).unwrap(),
+    ])
+    .unwrap();
+
+    // then
+    assert_eq!(encoded[0].len() + 50, chunks[0].encode().len());
+    assert_eq!(decoded[1].version(), 2);
+    assert_eq!(decoded[1].set_id(), chunks[1].set_id());
+    assert_eq!(decoded[1].key_hash(), None);
+    assert!(matches!(decoded[1].key_id(), KeyId::Short(_)));
+    assert_eq!(decoded[1].encode(), encoded[1]);
+    assert_eq!(
+      recovered.encode(),
+      MessageEncryptionKey::new([1u8; KEY_SIZE]).encode()
+    );
+  }
+
+  #[test]
+  fn should_issue_compact_chunks_from_short_identifiers() {
+    // given
+    let encoded: Vec<_> = chunks().iter().map(Chunk::encode_compact).collect();
+    let decoded: Vec<_> = encoded.iter().map(|c| Chunk::decode(c).unwrap()).collect();
+
+    // when
+    let issued = crate::shamir::issue_chunk(&decoded[..2], 3).unwrap();
+    let reencoded = Chunk::decode(&issued.encode()).unwrap();
+
+    // then
+    assert_eq!(issued.version(), 2);
+    assert_eq!(reencoded.key_id(), decoded[0].key_id());
+    assert!(recover_key(&[reencoded, Chunk::decode(&encoded[2]).unwrap()]).is_ok());
+  }
+
+  #[test]
+  fn should_detect_typos_and_keep_signatures_valid() {
+    // given
+    let identity = IdentityKey::from_bytes(&[3u8; IDENTITY_KEY_SIZE]);
+    let signed: Vec<_> = chunks().into_iter().map(|c| c.sign(&identity)).collect();
+
+    // when
+    let mut damaged = signed[0].encode_compact().to_vec();
+    damaged[20] ^= 0x10;
+    let decoded: Vec<_> = signed
+      .iter()
+      .map(|c| Chunk::decode(&c.encode_compact()).unwrap())
+      .collect();
+
+    // then
+    assert_eq!(
+      Chunk::decode(&damaged).unwrap_err(),
+      ChunkDecodingError::ChecksumMismatch
+    );
+    assert_eq!(decoded[0].key_hash(), signed[0].key_hash());
+    assert_eq!(decoded[0].signer(), Ok(Some(identity.public_key())));
+    assert!(recover_key(&decoded[0..2]).is_ok());
+    assert!(matches!(
+      recover_key(&[
+        decoded.into_iter().next().unwrap(),
+        Chunk::decode(&chunks()[1].encode_compact()).unwrap()
+      ]),
+      Err(KeyRecoveryError::SignerMismatch)
+    ));
+  }
+}
@@ -0,0 +1,149 @@
+//! Chunks encrypted to their recipients.
+//!
+//! An [EncryptedChunk] is a [Chunk] sealed to the recipient's
+//! [RecipientPublicKey] (see [crate::recipient]), so it can be sent
+//! and stored by untrusted parties. The recipient has to decrypt it
+//! using [EncryptedChunk::decrypt] before the key can be recovered.
+
+use super::{Chunk, ChunkDecodingError};
+use crate::recipient::{
+  RecipientError, RecipientKey, RecipientPublicKey, RECIPIENT_PUBLIC_KEY_SIZE,
+};
+use crate::Bytes;
+
+/// A specific byte sequence used to identify the encoding of [EncryptedChunk].
+pub const ENCRYPTED_CHUNK_MAGIC_SEQUENCE: &[u8] = b"icode";
+
+/// Domain separation string for chunk encryption.
+const DOMAIN: &[u8] = b"ICOD-Crypto encrypted chunk.";
+
+/// An error which may occur when decrypting the [EncryptedChunk].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EncryptedChunkError {
+  /// The chunk could not be decrypted.
+  #[error("{0}")]
+  Recipient(#[from] RecipientError),
+  /// The decrypted chunk could not be decoded.
+  #[error("{0}")]
+  Decoding(#[from] ChunkDecodingError),
+}
+
+/// A [Chunk] encrypted to a single recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedChunk {
+  recipient: RecipientPublicKey,
+  ephemeral: RecipientPublicKey,
+  ciphertext: Bytes,
+}
+
+impl EncryptedChunk {
+  /// Return the public key of the recipient.
+  pub fn recipient(&self) -> &RecipientPublicKey {
+    &self.recipient
+  }
+
+  /// Decrypt the chunk with the recipient's secret key.
+  pub fn decrypt(&self, key: &RecipientKey) -> Result<Chunk, EncryptedChunkError> {
+    if key.public_key() != self.recipient {
+      return Err(RecipientError::WrongRecipient.into());
+    }
+    let mut encoded = Bytes::from(key.open(DOMAIN, &self.ephemeral, &self.ciphertext)?);
+    let chunk = Chunk::decode(&encoded);
+    encoded.wipe();
+    Ok(chunk?)
+  }
+
+  /// Encode the encrypted chunk.
+  ///
+  /// The encoding is: magic sequence, version (1 byte, `0`), recipient's
+  /// public key, ephemeral public key (32 bytes each) and the ciphertext.
+  pub fn encode(&self) -> Bytes {
+    let mut out = Vec::with_capacity(
+      ENCRYPTED_CHUNK_MAGIC_SEQUENCE.len()
+        + 1
+        + 2 * RECIPIENT_PUBLIC_KEY_SIZE
+        + self.ciphertext.len(),
+    );
+    out.extend_from_slice(ENCRYPTED_CHUNK_MAGIC_SEQUENCE);
+    out.push(0);
+    out.extend_from_slice(&self.recipient.to_bytes());
+    out.extend_from_slice(&self.ephemeral.to_bytes());
+    out.extend_from_slice(&self.ciphertext);
+    Bytes::from(out)
+  }
+
+  /// Decode the encrypted chunk produced by [EncryptedChunk::encode].
+  pub fn decode(data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    let data = data
+      .strip_prefix(ENCRYPTED_CHUNK_MAGIC_SEQUENCE)
+      .ok_or(ChunkDecodingError::MissingMagicBytes)?;
+    let (version, data) = data
+      .split_first()
+      .ok_or(ChunkDecodingError::NotEnoughData)?;
+    if *version != 0 {
+      return Err(ChunkDecodingError::InvalidVersion);
+    }
+    if data.len() <= 2 * RECIPIENT_PUBLIC_KEY_SIZE {
+      return Err(ChunkDecodingError::NotEnoughData);
+    }
+    let (recipient, data) = data.split_at(RECIPIENT_PUBLIC_KEY_SIZE);
+    let (ephemeral, ciphertext) = data.split_at(RECIPIENT_PUBLIC_KEY_SIZE);
+    Ok(Self {
+      recipient: RecipientPublicKey::from_bytes(recipient)
+        .map_err(|_| ChunkDecodingError::NotEnoughData)?,
+      ephemeral: RecipientPublicKey::from_bytes(ephemeral)
+        .map_err(|_| ChunkDecodingError::NotEnoughData)?,
+      ciphertext: Bytes::from_slice(ciphertext),
+    })
+  }
+}
+
+impl Chunk {
+  /// Encrypt the chunk to given recipient.
+  pub fn encrypt_to(&self, recipient: &RecipientPublicKey) -> EncryptedChunk {
+    let mut encoded = self.encode();
+    let (ephemeral, ciphertext) = recipient.seal(DOMAIN, &encoded);
+    encoded.wipe();
+    EncryptedChunk {
+      recipient: *recipient,
+      ephemeral,
+      ciphertext: ciphertext.into(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+  use crate::shamir::{split_into_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn should_encrypt_and_decrypt_chunk() {
+    // given
+    let key = RecipientKey::generate();
+    let other = RecipientKey::generate();
+    let chunks = split_into_chunks(
+      MessageEncryptionKey::new([1u8; KEY_SIZE]),
+      ChunksConfiguration::new(1, 0).unwrap(),
+    );
+
+    // when
+    let encrypted = chunks[0].encrypt_to(&key.public_key());
+    let decoded = EncryptedChunk::decode(&encrypted.encode()).unwrap();
+
+    // then
+    assert_eq!(decoded, encrypted);
+    assert_eq!(decoded.recipient(), &key.public_key());
+    assert_eq!(decoded.decrypt(&key).unwrap().encode(), chunks[0].encode());
+    assert_eq!(
+      decoded.decrypt(&other).unwrap_err(),
+      EncryptedChunkError::Recipient(RecipientError::WrongRecipient)
+    );
+    assert_eq!(
+      EncryptedChunk::decode(&chunks[0].encode()).unwrap_err(),
+      ChunkDecodingError::MissingMagicBytes
+    );
+  }
+}
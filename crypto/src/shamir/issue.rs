@@ -0,0 +1,183 @@
+//! Issuing additional or replacement chunks.
+//!
+//! The shares are points of random polynomials, so given `required` chunks
+//! the polynomials can be evaluated at any other x-coordinate. This allows
+//! minting a [Chunk] for a new recipient (or replacing a lost one) without
+//! starting over with a new key.
+//!
+//! Chunks issued at an index beyond the current set carry an updated
+//! [ChunksConfiguration] with more `spare` chunks. During recovery, only such
+//! chunks may have a different configuration than the rest of the set.
+
+use gf256::gf256;
+
+use super::{recover_key, Chunk, ChunkExtension, ChunksConfiguration, KeyRecoveryError};
+
+/// The error which may occur when issuing a new chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum IssueError {
+  /// The provided chunks could not be used to recover the key.
+  #[error("{0}")]
+  Recovery(#[from] KeyRecoveryError),
+  /// Issuing verifiable, weighted or policy chunks is not supported.
+  #[error("Only regular chunks can be used to issue a new one.")]
+  Unsupported,
+  /// The chunk index is already used by one of the provided chunks or is out of range.
+  #[error("The chunk index is invalid.")]
+  InvalidIndex,
+}
+
+/// Mint a new [Chunk] at given `index` from a threshold of existing `chunks`.
+///
+/// The `index` must not be used by any of the provided `chunks`. Issuing a chunk
+/// at an index of a lost chunk re-creates exactly the same chunk.
+///
+/// The new chunk is not signed, even if the provided chunks are (see [Chunk::sign]).
+pub fn issue_chunk(chunks: &[Chunk], index: u8) -> Result<Chunk, IssueError> {
+  let unsupported = |c: &Chunk| c.is_verifiable() || c.weight() > 1 || c.policy_path().is_some();
+  if chunks.iter().any(unsupported) {
+    return Err(IssueError::Unsupported);
+  }
+  // the chunk at index `254` would require a configuration of `255` chunks.
+  if index >= u8::MAX - 1 || chunks.iter().any(|c| c.index() == index) {
+    return Err(IssueError::InvalidIndex);
+  }
+
+  // Make sure the chunks are consistent and sufficient.
+  recover_key(chunks)?;
+
+  let first = &chunks[0];
+  let configuration = first.configuration();
+  let mut unique = Vec::<&Chunk>::with_capacity(configuration.required());
+  for chunk in chunks {
+    if unique.len() < configuration.required() && unique.iter().all(|c| c.index() != chunk.index())
+    {
+      unique.push(chunk);
+    }
+  }
+  let shares: Vec<&[u8]> = unique.iter().map(|c| &**c.data()).collect();
+  let data = interpolate_at(&shares, index + 1);
+
+  let totals = chunks.iter().map(|c| c.configuration().total());
+  let smallest = totals.clone().min().unwrap_or_default();
+  // replacements keep the original configuration.
+  let total = if (index as usize) < smallest {
+    smallest
+  } else {
+    totals.max().unwrap_or_default().max(index as usize + 1)
+  };
+  let spare = (total - configuration.required()) as u8;
+  let configuration = ChunksConfiguration::new(configuration.required, spare)
+    .map_err(|_| IssueError::InvalidIndex)?;
+
+  let mut chunk = Chunk::new(index, data, configuration, first.key_id().clone());
+  if first.generation() > 0 {
+    chunk.set_extension(ChunkExtension::Generation(first.generation()));
+  }
+  Ok(chunk)
+}
+
+/// Evaluate the polynomials defined by `shares` at given `x` coordinate.
+///
+/// Every share is encoded as the x coordinate followed by the y coordinates.
+pub(crate) fn interpolate_at(shares: &[&[u8]], x: u8) -> Vec<u8> {
+  let x = gf256::new(x);
+  let xs: Vec<gf256> = shares.iter().map(|s| gf256::new(s[0])).collect();
+  // Lagrange basis polynomials evaluated at `x`.
+  let basis: Vec<gf256> = xs
+    .iter()
+    .enumerate()
+    .map(|(i, xi)| {
+      xs.iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .fold(gf256::new(1), |acc, (_, xj)| acc * (x - xj) / (xi - xj))
+    })
+    .collect();
+
+  let len = shares.first().map(|s| s.len()).unwrap_or_default();
+  let mut out = Vec::with_capacity(len);
+  out.push(u8::from(x));
+  for byte in 1..len {
+    let y = shares
+      .iter()
+      .zip(&basis)
+      .fold(gf256::new(0), |acc, (s, l)| acc + gf256::new(s[byte]) * l);
+    out.push(u8::from(y));
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+  use crate::shamir::split_into_chunks;
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  #[test]
+  fn should_replace_lost_chunk() {
+    // given
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+
+    // when
+    let replaced = issue_chunk(&chunks[1..3], 0).unwrap();
+
+    // then
+    assert_eq!(replaced.encode(), chunks[0].encode());
+  }
+
+  #[test]
+  fn should_issue_additional_chunk() {
+    // given
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+
+    // when
+    let issued = issue_chunk(&chunks[0..2], 4).unwrap();
+    let decoded = Chunk::decode(&issued.encode()).unwrap();
+    let recovered = recover_key(&[decoded, Chunk::decode(&chunks[2].encode()).unwrap()]).unwrap();
+
+    // then
+    assert_eq!(issued.index(), 4);
+    assert_eq!(
+      issued.configuration(),
+      ChunksConfiguration::new(2, 3).unwrap()
+    );
+    assert_eq!(recovered.encode(), key().encode());
+  }
+
+  #[test]
+  fn should_replace_lost_chunk_using_issued_ones() {
+    // given
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+    let issued = issue_chunk(&chunks[0..2], 3).unwrap();
+
+    // when
+    let replaced = issue_chunk(&[Chunk::decode(&chunks[1].encode()).unwrap(), issued], 0).unwrap();
+
+    // then
+    assert_eq!(replaced.encode(), chunks[0].encode());
+  }
+
+  #[test]
+  fn should_reject_invalid_index() {
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+
+    assert_eq!(
+      issue_chunk(&chunks[0..2], 1).unwrap_err(),
+      IssueError::InvalidIndex
+    );
+    assert_eq!(
+      issue_chunk(&chunks[0..2], 254).unwrap_err(),
+      IssueError::InvalidIndex
+    );
+    assert_eq!(
+      issue_chunk(&chunks[0..1], 3).unwrap_err(),
+      IssueError::Recovery(KeyRecoveryError::NotEnoughChunks)
+    );
+  }
+}
@@ -0,0 +1,223 @@
+//! Authenticated chunk metadata.
+//!
+//! The name of the chunk stored in front of the encoded bytes (`icod-chunk:name:...`)
+//! can be changed by anyone. [ChunkMetadata] is stored inside the chunk encoding
+//! instead (see [ChunkExtension::Metadata]) and carries the display name, a hint
+//! who the chunk is for, the owner's contact, short restoration instructions and
+//! the creation date.
+//!
+//! The metadata is covered by the owner's signature (see [Chunk::sign]), so it
+//! should be considered authentic only if the chunk is signed.
+
+use super::{Chunk, ChunkDecodingError, ChunkExtension};
+
+/// Maximal byte length of every text field of [ChunkMetadata].
+pub const MAX_METADATA_FIELD_LEN: usize = 255;
+
+const NAME_FIELD: u8 = 1;
+const RECIPIENT_FIELD: u8 = 2;
+const CONTACT_FIELD: u8 = 3;
+const INSTRUCTIONS_FIELD: u8 = 4;
+const CREATED_AT_FIELD: u8 = 5;
+
+/// An error which may occur when attaching the metadata to a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MetadataError {
+  /// One of the fields exceeds [MAX_METADATA_FIELD_LEN] bytes.
+  #[error("The metadata field is too long.")]
+  FieldTooLong,
+}
+
+/// Descriptive information about the chunk.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChunkMetadata {
+  /// Display name of the chunk.
+  pub name: Option<String>,
+  /// Hint who the chunk is intended for.
+  pub recipient: Option<String>,
+  /// Contact to the owner.
+  pub owner_contact: Option<String>,
+  /// Short restoration instructions.
+  pub instructions: Option<String>,
+  /// Creation date (UNIX timestamp in seconds).
+  pub created_at: Option<u64>,
+}
+
+impl ChunkMetadata {
+  fn text_fields(&self) -> [(u8, &Option<String>); 4] {
+    [
+      (NAME_FIELD, &self.name),
+      (RECIPIENT_FIELD, &self.recipient),
+      (CONTACT_FIELD, &self.owner_contact),
+      (INSTRUCTIONS_FIELD, &self.instructions),
+    ]
+  }
+
+  /// Encode the metadata as a sequence of present fields.
+  ///
+  /// Every field is encoded as field tag (1 byte), length (1 byte) and value.
+  pub(crate) fn encode(&self) -> Vec<u8> {
+    let mut out = vec![];
+    for (tag, value) in self.text_fields() {
+      if let Some(value) = value {
+        out.push(tag);
+        out.push(value.len() as u8);
+        out.extend_from_slice(value.as_bytes());
+      }
+    }
+    if let Some(created_at) = self.created_at {
+      out.push(CREATED_AT_FIELD);
+      out.push(8);
+      out.extend_from_slice(&created_at.to_be_bytes());
+    }
+    out
+  }
+
+  /// Decode the metadata produced by [ChunkMetadata::encode].
+  pub(crate) fn decode(mut data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    let mut metadata = Self::default();
+    let mut last_tag = 0;
+    while let [tag, len, rest @ ..] = data {
+      let len = *len as usize;
+      // the fields have to be sorted, which also rules out duplicates.
+      if *tag <= last_tag || rest.len() < len {
+        return Err(ChunkDecodingError::InvalidExtension);
+      }
+      let (value, rest) = rest.split_at(len);
+      let text = || {
+        String::from_utf8(value.to_vec())
+          .map(Some)
+          .map_err(|_| ChunkDecodingError::InvalidExtension)
+      };
+      match *tag {
+        NAME_FIELD => metadata.name = text()?,
+        RECIPIENT_FIELD => metadata.recipient = text()?,
+        CONTACT_FIELD => metadata.owner_contact = text()?,
+        INSTRUCTIONS_FIELD => metadata.instructions = text()?,
+        CREATED_AT_FIELD => {
+          let created_at: [u8; 8] = value
+            .try_into()
+            .map_err(|_| ChunkDecodingError::InvalidExtension)?;
+          metadata.created_at = Some(u64::from_be_bytes(created_at));
+        }
+        _ => return Err(ChunkDecodingError::InvalidExtension),
+      }
+      last_tag = *tag;
+      data = rest;
+    }
+    if !data.is_empty() || metadata == Self::default() {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    Ok(metadata)
+  }
+}
+
+impl Chunk {
+  /// Attach the metadata to the chunk, replacing the previous one.
+  ///
+  /// Empty metadata removes it from the chunk. The owner's signature (if any)
+  /// is removed, since it no longer matches the chunk, hence the chunk should
+  /// be signed after the metadata is attached.
+  pub fn with_metadata(mut self, metadata: ChunkMetadata) -> Result<Self, MetadataError> {
+    let too_long = metadata.text_fields().iter().any(|(_, value)| {
+      value
+        .as_ref()
+        .is_some_and(|v| v.len() > MAX_METADATA_FIELD_LEN)
+    });
+    if too_long {
+      return Err(MetadataError::FieldTooLong);
+    }
+
+    self.remove_signature();
+    if metadata == ChunkMetadata::default() {
+      self.remove_extension(super::METADATA_TAG);
+    } else {
+      self.set_extension(ChunkExtension::Metadata(metadata));
+    }
+    Ok(self)
+  }
+
+  /// Return the metadata of the chunk (if any).
+  ///
+  /// Note the metadata is authentic only if the chunk is signed (see [Chunk::signer]).
+  pub fn metadata(&self) -> Option<&ChunkMetadata> {
+    self.extensions.iter().find_map(|e| match e {
+      ChunkExtension::Metadata(metadata) => Some(metadata),
+      _ => None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+  use crate::identity::{IdentityKey, IDENTITY_KEY_SIZE};
+  use crate::shamir::{split_into_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  fn chunk() -> Chunk {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap()).remove(0)
+  }
+
+  fn metadata() -> ChunkMetadata {
+    ChunkMetadata {
+      name: Some("Grandma's piece".into()),
+      recipient: Some("Alice".into()),
+      owner_contact: None,
+      instructions: Some("Call Bob when I'm gone.".into()),
+      created_at: Some(1_700_000_000),
+    }
+  }
+
+  #[test]
+  fn should_encode_and_authenticate_metadata() {
+    // given
+    let identity = IdentityKey::from_bytes(&[3u8; IDENTITY_KEY_SIZE]);
+    let chunk = chunk().with_metadata(metadata()).unwrap().sign(&identity);
+
+    // when
+    let decoded = Chunk::decode(&chunk.encode()).unwrap();
+    let mut tampered = chunk.encode().to_vec();
+    let at = tampered.windows(5).position(|w| w == b"Alice").unwrap();
+    tampered[at] = b'E';
+    let tampered = Chunk::decode(&tampered).unwrap();
+
+    // then
+    assert_eq!(decoded.metadata(), Some(&metadata()));
+    assert_eq!(decoded.signer(), Ok(Some(identity.public_key())));
+    assert_eq!(tampered.metadata().unwrap().recipient, Some("Elice".into()));
+    assert!(tampered.signer().is_err());
+  }
+
+  #[test]
+  fn should_replace_and_remove_metadata() {
+    // given
+    let identity = IdentityKey::from_bytes(&[3u8; IDENTITY_KEY_SIZE]);
+    let signed = chunk().with_metadata(metadata()).unwrap().sign(&identity);
+    let renamed = ChunkMetadata {
+      name: Some("Renamed".into()),
+      ..Default::default()
+    };
+
+    // when
+    let too_long = chunk().with_metadata(ChunkMetadata {
+      name: Some("x".repeat(MAX_METADATA_FIELD_LEN + 1)),
+      ..Default::default()
+    });
+    let renamed_chunk = signed.with_metadata(renamed.clone()).unwrap();
+    let replaced = renamed_chunk.metadata().cloned();
+    let unsigned = renamed_chunk.signature().is_none();
+    let removed = renamed_chunk
+      .with_metadata(ChunkMetadata::default())
+      .unwrap();
+
+    // then
+    assert_eq!(too_long.unwrap_err(), MetadataError::FieldTooLong);
+    assert_eq!(replaced, Some(renamed));
+    assert!(unsigned);
+    assert_eq!(removed.metadata(), None);
+    assert_eq!(removed.version(), 0);
+  }
+}
@@ -13,10 +13,52 @@
 //! Every [Chunk] is versioned to provide backward-compatibility
 //! in case of any changes. Also we bundle a bunch of additional
 //! information with the chunk to improve User Experience.
+//!
+//! Optional features of the chunk (i.e. passphrase protection, see [passphrase])
+//! are stored as [ChunkExtension]s, which forces the `V1` encoding.
+//!
+//! Chunks that can be verified by their recipients are produced using
+//! the [vss] module, while the chunks can be signed by the owner
+//! (see [signature]) to detect spoofing. Recipients may also be trusted
+//! differently and receive chunks with multiple share points (see [weighted]),
+//! while nested thresholds over named groups are expressed with a [policy] tree.
+//! Suspected leaks are handled by issuing a new generation of chunks (see [refresh]),
+//! while lost chunks can be replaced or new ones added (see [issue]).
+//! Corrupted chunks can be detected and skipped during recovery (see [robust]).
+//! To be safely sent or stored, chunks can be encrypted to their recipients (see [encrypted]).
+//! The key can also be exported to (or imported from) SLIP-39 mnemonic shares (see [slip39]).
 
 use crate::encryption::MessageEncryptionKey;
 use crate::{blake2b512, encryption, Bytes, Hash, HASH_SIZE};
 
+pub mod compact;
+pub mod encrypted;
+pub mod issue;
+pub mod metadata;
+pub mod passphrase;
+pub mod policy;
+pub mod refresh;
+pub mod robust;
+pub mod signature;
+pub mod slip39;
+pub mod vss;
+pub mod weighted;
+
+pub use compact::{KeyId, SET_ID_SIZE};
+pub use encrypted::{EncryptedChunk, EncryptedChunkError};
+pub use issue::{issue_chunk, IssueError};
+pub use metadata::{ChunkMetadata, MetadataError};
+pub use passphrase::{PassphraseError, PassphraseParams};
+pub use policy::{split_with_policy, Policy, PolicyError, PolicyPath};
+pub use refresh::{refresh_chunks, RefreshError};
+pub use robust::{recover_key_robust, RobustRecovery};
+pub use slip39::{
+  chunks_to_slip39, recover_key_from_slip39, slip39_to_chunks, split_into_slip39_shares,
+  Slip39Error,
+};
+pub use vss::{split_into_verifiable_chunks, Commitments, VssError};
+pub use weighted::{split_into_weighted_chunks, WeightedConfiguration};
+
 /// A configuration of the Shamir Secret Sharing split.
 ///
 /// A total number of chunks is equal to `required + spare`
@@ -69,6 +111,121 @@ impl ChunksConfiguration {
 pub enum ChunkVersion {
   /// Version V0 - SSS using `gf256` library.
   V0,
+  /// Version V1 - same as `V0`, but with a list of [ChunkExtension]s.
+  V1,
+  /// Version V2 - same as `V1`, but using the compact encoding with a checksum.
+  ///
+  /// See [compact] module for details.
+  V2,
+}
+
+/// [ChunkExtension::Passphrase] encoding tag.
+const PASSPHRASE_TAG: u8 = 1;
+/// [ChunkExtension::Vss] encoding tag.
+const VSS_TAG: u8 = 2;
+/// [ChunkExtension::Signature] encoding tag.
+const SIGNATURE_TAG: u8 = 3;
+/// [ChunkExtension::Weight] encoding tag.
+const WEIGHT_TAG: u8 = 4;
+/// [ChunkExtension::Policy] encoding tag.
+const POLICY_TAG: u8 = 5;
+/// [ChunkExtension::Generation] encoding tag.
+const GENERATION_TAG: u8 = 6;
+/// [ChunkExtension::Metadata] encoding tag.
+const METADATA_TAG: u8 = 7;
+
+/// Optional feature of the [Chunk].
+///
+/// Presence of any extension makes the chunk use the [ChunkVersion::V1] encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkExtension {
+  /// The chunk data is encrypted with a passphrase-derived key.
+  ///
+  /// See [passphrase] module for details.
+  Passphrase(PassphraseParams),
+  /// The chunk data is a Pedersen VSS share bound to [Commitments] with given digest.
+  ///
+  /// See [vss] module for details.
+  Vss(Hash),
+  /// The chunk is signed by the owner's identity key.
+  ///
+  /// See [signature] module for details.
+  Signature(crate::identity::Signature),
+  /// The chunk data is a concatenation of given number of share points.
+  ///
+  /// See [weighted] module for details.
+  Weight(u8),
+  /// The chunk is a leaf of the [Policy] tree at given path.
+  ///
+  /// See [policy] module for details.
+  Policy(PolicyPath),
+  /// The chunk was produced by given (non-zero) refresh of the original chunks.
+  ///
+  /// See [refresh] module for details.
+  Generation(u16),
+  /// The chunk carries descriptive information.
+  ///
+  /// See [metadata] module for details.
+  Metadata(ChunkMetadata),
+}
+
+impl ChunkExtension {
+  fn tag(&self) -> u8 {
+    match self {
+      ChunkExtension::Passphrase(_) => PASSPHRASE_TAG,
+      ChunkExtension::Vss(_) => VSS_TAG,
+      ChunkExtension::Signature(_) => SIGNATURE_TAG,
+      ChunkExtension::Weight(_) => WEIGHT_TAG,
+      ChunkExtension::Policy(_) => POLICY_TAG,
+      ChunkExtension::Generation(_) => GENERATION_TAG,
+      ChunkExtension::Metadata(_) => METADATA_TAG,
+    }
+  }
+
+  fn encode_to(&self, out: &mut Vec<u8>) {
+    let value = self.encode_value();
+    // the values are bounded when the extensions are created (see [policy::MAX_ENCODED_LEN]).
+    let len = u16::try_from(value.len()).expect("The extension value length fits in u16; qed");
+    out.push(self.tag());
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&value);
+  }
+
+  fn encode_value(&self) -> Vec<u8> {
+    match self {
+      ChunkExtension::Passphrase(params) => params.encode(),
+      ChunkExtension::Vss(digest) => digest.as_slice().to_vec(),
+      ChunkExtension::Signature(signature) => signature.encode(),
+      ChunkExtension::Weight(weight) => vec![*weight],
+      ChunkExtension::Policy(path) => path.encode(),
+      ChunkExtension::Generation(generation) => generation.to_be_bytes().to_vec(),
+      ChunkExtension::Metadata(metadata) => metadata.encode(),
+    }
+  }
+
+  fn decode(tag: u8, value: &[u8]) -> Result<Self, ChunkDecodingError> {
+    match tag {
+      PASSPHRASE_TAG => Ok(ChunkExtension::Passphrase(PassphraseParams::decode(value)?)),
+      VSS_TAG => Ok(ChunkExtension::Vss(vss::decode_digest(value)?)),
+      SIGNATURE_TAG => Ok(ChunkExtension::Signature(
+        crate::identity::Signature::decode(value)
+          .map_err(|_| ChunkDecodingError::InvalidExtension)?,
+      )),
+      WEIGHT_TAG => match value {
+        &[weight] if weight > 1 => Ok(ChunkExtension::Weight(weight)),
+        _ => Err(ChunkDecodingError::InvalidExtension),
+      },
+      POLICY_TAG => Ok(ChunkExtension::Policy(PolicyPath::decode(value)?)),
+      GENERATION_TAG => match value {
+        &[a, b] if u16::from_be_bytes([a, b]) > 0 => {
+          Ok(ChunkExtension::Generation(u16::from_be_bytes([a, b])))
+        }
+        _ => Err(ChunkDecodingError::InvalidExtension),
+      },
+      METADATA_TAG => Ok(ChunkExtension::Metadata(ChunkMetadata::decode(value)?)),
+      _ => Err(ChunkDecodingError::UnknownExtension),
+    }
+  }
 }
 
 /// A single [Chunk] obtained from [split_into_chunks] function.
@@ -86,9 +243,10 @@ pub enum ChunkVersion {
 #[derive(Debug)]
 pub struct Chunk {
   version: ChunkVersion,
-  key_hash: Hash,
+  key_id: KeyId,
   chunks_configuration: ChunksConfiguration,
   chunk_index: u8,
+  extensions: Vec<ChunkExtension>,
   chunk_data: Bytes,
 }
 
@@ -100,17 +258,25 @@ pub const CHUNK_ENCODING_MAGIC_SEQUENCE: &'static [u8] = b"icodc";
 
 impl Chunk {
   /// Create a new [Chunk] providing it's index, data configuration and hash of the key.
+  ///
+  /// Chunks identified by [KeyId::Short] can only use the compact encoding.
   pub fn new(
     chunk_index: u8,
     chunk_data: impl Into<Bytes>,
     chunks_configuration: ChunksConfiguration,
-    key_hash: Hash,
+    key_id: impl Into<KeyId>,
   ) -> Self {
+    let key_id = key_id.into();
+    let version = match key_id {
+      KeyId::Full(_) => ChunkVersion::V0,
+      KeyId::Short(_) => ChunkVersion::V2,
+    };
     Self {
-      version: ChunkVersion::V0,
-      key_hash,
+      version,
+      key_id,
       chunks_configuration,
       chunk_index,
+      extensions: vec![],
       chunk_data: chunk_data.into(),
     }
   }
@@ -125,41 +291,77 @@ impl Chunk {
       }
     }
 
+    let encoded = data;
     let data = data
       .strip_prefix(CHUNK_ENCODING_MAGIC_SEQUENCE)
       .ok_or(ChunkDecodingError::MissingMagicBytes)?;
-    let version = [0u8];
-    let data = data
-      .strip_prefix(&version)
-      .ok_or(ChunkDecodingError::InvalidVersion)?;
+    let (version, data) = match data.split_first() {
+      Some((2, _)) => return compact::decode(encoded),
+      Some((0, data)) => (ChunkVersion::V0, data),
+      Some((1, data)) => (ChunkVersion::V1, data),
+      _ => return Err(ChunkDecodingError::InvalidVersion),
+    };
     let (key, data) = split_at(&data, HASH_SIZE)?;
-    let key_hash = Hash::from_slice(&key).map_err(|_| ChunkDecodingError::NotEnoughData)?;
+    let key_id =
+      KeyId::Full(Hash::from_slice(&key).map_err(|_| ChunkDecodingError::NotEnoughData)?);
     let (conf, data) = split_at(data, 2)?;
     let chunks_configuration = ChunksConfiguration::new(conf[0], conf[1])
       .map_err(|_| ChunkDecodingError::InvalidConfiguration)?;
 
-    let (&chunk_index, data) = data
+    let (&chunk_index, mut data) = data
       .split_first()
       .ok_or(ChunkDecodingError::NotEnoughData)?;
-    let chunk_data = Bytes::from_slice(data);
-    if chunk_data.is_empty() {
-      return Err(ChunkDecodingError::NotEnoughData);
+
+    let mut extensions = vec![];
+    if version == ChunkVersion::V1 {
+      let (&count, rest) = data
+        .split_first()
+        .ok_or(ChunkDecodingError::NotEnoughData)?;
+      data = rest;
+      for _ in 0..count {
+        let (header, rest) = split_at(data, 3)?;
+        let len = u16::from_be_bytes([header[1], header[2]]) as usize;
+        let (value, rest) = split_at(rest, len)?;
+        let extension = ChunkExtension::decode(header[0], value)?;
+        if extensions
+          .iter()
+          .any(|e: &ChunkExtension| e.tag() == extension.tag())
+        {
+          return Err(ChunkDecodingError::DuplicateExtension);
+        }
+        extensions.push(extension);
+        data = rest;
+      }
     }
 
-    Ok(Self {
-      version: ChunkVersion::V0,
-      key_hash,
+    Self {
+      version,
+      key_id,
       chunk_index,
-      chunk_data,
+      extensions,
+      chunk_data: Bytes::from_slice(data),
       chunks_configuration,
-    })
+    }
+    .validated()
+  }
+
+  /// Check the consistency of the decoded chunk.
+  fn validated(self) -> Result<Self, ChunkDecodingError> {
+    if self.chunk_data.is_empty() {
+      return Err(ChunkDecodingError::NotEnoughData);
+    }
+    // the data of protected chunks is checked once unlocked.
+    if !self.is_protected() && !self.has_valid_points() {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    Ok(self)
   }
 
   /// Encode the chunk into a vector of bytes.
   ///
   /// The encoding has a magic sequence prepended for identification.
   ///
-  /// /// ```markdown
+  /// ```markdown
   /// +--------------------------------+
   /// | magic byte sequence (5 bytes)  |
   /// | (b"icodc")                     |
@@ -172,17 +374,44 @@ impl Chunk {
   /// +--------------------------------+
   /// | chunk index (1 byte)           |
   /// +--------------------------------+
+  /// | extensions (variable length)   | // only in V1
+  /// +--------------------------------+
   /// | data (variable length)         |
   /// +--------------------------------+
   /// ```
+  ///
+  /// The `V1` extensions are encoded as number of extensions (1 byte)
+  /// followed by every [ChunkExtension] encoded as:
+  ///
+  /// ```markdown
+  /// +--------------------------------+
+  /// | tag (1 byte)                   |
+  /// +--------------------------------+
+  /// | length (2 bytes)               | // Big Endian
+  /// +--------------------------------+
+  /// | value (variable length)        |
+  /// +--------------------------------+
+  /// ```
+  ///
+  /// Chunks decoded from the [ChunkVersion::V2] encoding are encoded
+  /// with [Chunk::encode_compact].
   pub fn encode(&self) -> Bytes {
+    if self.version == ChunkVersion::V2 {
+      return self.encode_compact();
+    }
     let mut out = vec![];
     out.extend_from_slice(CHUNK_ENCODING_MAGIC_SEQUENCE);
     out.push(self.version());
-    out.extend_from_slice(self.key_hash.as_slice());
+    out.extend_from_slice(self.key_id.as_bytes());
     out.push(self.chunks_configuration.required);
     out.push(self.chunks_configuration.spare);
     out.push(self.chunk_index);
+    if self.version == ChunkVersion::V1 {
+      out.push(self.extensions.len() as u8);
+      for extension in &self.extensions {
+        extension.encode_to(&mut out);
+      }
+    }
     out.extend_from_slice(&*self.chunk_data);
     Bytes::from(out)
   }
@@ -191,12 +420,43 @@ impl Chunk {
   pub fn version(&self) -> u8 {
     match self.version {
       ChunkVersion::V0 => 0u8,
+      ChunkVersion::V1 => 1u8,
+      ChunkVersion::V2 => 2u8,
     }
   }
 
+  /// Return the list of extensions of this chunk.
+  pub fn extensions(&self) -> &[ChunkExtension] {
+    &self.extensions
+  }
+
+  /// Replace the extension of the same type or add a new one.
+  fn set_extension(&mut self, extension: ChunkExtension) {
+    self.extensions.retain(|e| e.tag() != extension.tag());
+    self.extensions.push(extension);
+    if self.version == ChunkVersion::V0 {
+      self.version = ChunkVersion::V1;
+    }
+  }
+
+  /// Remove the extension with given tag.
+  fn remove_extension(&mut self, tag: u8) {
+    self.extensions.retain(|e| e.tag() != tag);
+    if self.extensions.is_empty() && self.version == ChunkVersion::V1 {
+      self.version = ChunkVersion::V0;
+    }
+  }
+
+  /// Return the identifier of the key the chunk is for.
+  pub fn key_id(&self) -> &KeyId {
+    &self.key_id
+  }
+
   /// Return the hash of the key the chunk is for.
-  pub fn key_hash(&self) -> &Hash {
-    &self.key_hash
+  ///
+  /// The hash is not known for compact chunks storing only the set identifier.
+  pub fn key_hash(&self) -> Option<&Hash> {
+    self.key_id.key_hash()
   }
 
   /// Return the configuration of the splitting setup.
@@ -204,6 +464,26 @@ impl Chunk {
     self.chunks_configuration
   }
 
+  /// Check if the configurations of both chunks match.
+  ///
+  /// Only a chunk issued beyond the original set (see [issue_chunk]) may have
+  /// more `spare` chunks than the other one.
+  fn has_compatible_configuration(&self, other: &Chunk) -> bool {
+    let (a, b) = (self.chunks_configuration, other.chunks_configuration);
+    if a == b {
+      return true;
+    }
+    if a.required != b.required {
+      return false;
+    }
+    let (smaller, issued) = if a.total() < b.total() {
+      (a, other)
+    } else {
+      (b, self)
+    };
+    issued.chunk_index as usize >= smaller.total()
+  }
+
   /// Return this chunk index.
   pub fn index(&self) -> u8 {
     self.chunk_index
@@ -256,13 +536,17 @@ pub enum KeyRecoveryError {
 
   /// The chunks have inconsistent configuration.
   ///
+  /// Only the number of `required` chunks has to match, since chunks
+  /// issued later (see [issue_chunk]) may have more `spare` chunks.
+  ///
   /// This should not happen if the `key_hash` is the same,
   /// since we recommend generating unique key, but we still
   /// need to check that.
   #[error("The chunks have incosistent configurations.")]
   InconsistentConfiguration,
 
-  /// We have less chunks than the configuration states is `required`.
+  /// We have less chunks (or share points of weighted chunks) than
+  /// the configuration states is `required`.
   #[error("Not enough chunks to recover the key.")]
   NotEnoughChunks,
 
@@ -276,6 +560,24 @@ pub enum KeyRecoveryError {
   /// This should rather never happen in reality, but we still perform t.unwrap(he checks.
   #[error("The recovered key is not usable.")]
   KeyDecodingError,
+
+  /// Some of the chunks are protected with a passphrase.
+  ///
+  /// They need to be unlocked using [Chunk::unprotect] first.
+  #[error("The chunks need to be unlocked with a passphrase first.")]
+  PassphraseProtected,
+
+  /// The chunk with given index does not match the VSS [Commitments].
+  #[error("The chunk {0} is invalid.")]
+  InvalidChunk(u8),
+
+  /// The chunk with given index has an invalid owner's signature.
+  #[error("The chunk {0} has invalid signature.")]
+  InvalidSignature(u8),
+
+  /// The chunks are signed by different owners (or only some of them are signed).
+  #[error("The chunks are signed by different owners.")]
+  SignerMismatch,
 }
 
 impl From<encryption::KeyDecodingError> for KeyRecoveryError {
@@ -304,6 +606,22 @@ pub enum ChunkDecodingError {
   /// The configuration stored in the chunk is not correct.
   #[error("The chunk configuration is incorrect.")]
   InvalidConfiguration,
+
+  /// The chunk contains an extension we don't know.
+  #[error("The chunk uses unsupported features.")]
+  UnknownExtension,
+
+  /// The chunk contains the same extension twice.
+  #[error("The chunk extensions are duplicated.")]
+  DuplicateExtension,
+
+  /// The value of extension is malformed.
+  #[error("The chunk extension is malformed.")]
+  InvalidExtension,
+
+  /// The checksum of the compact encoding does not match (see [compact]).
+  #[error("The chunk checksum does not match. Check for typos.")]
+  ChecksumMismatch,
 }
 
 /// Given a slice of [Chunk]s recover the original key.
@@ -316,35 +634,77 @@ pub enum ChunkDecodingError {
 ///
 /// Obviously these [Chunk]s are easy to spoof (via decoding mechanism),
 /// but the checks are there mostly to improve the user experience in the happy case.
+/// Signed chunks (see [Chunk::sign]) are verified and need to have the same signer.
 pub fn recover_key(chunks: &[Chunk]) -> Result<MessageEncryptionKey, KeyRecoveryError> {
   let first = chunks.first().ok_or(KeyRecoveryError::NotEnoughChunks)?;
   let configuration = first.configuration();
 
+  if chunks.iter().any(Chunk::is_protected) {
+    return Err(KeyRecoveryError::PassphraseProtected);
+  }
+
+  let signers = chunks
+    .iter()
+    .map(|c| {
+      c.signer()
+        .map_err(|_| KeyRecoveryError::InvalidSignature(c.index()))
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  if signers.iter().any(|s| s != &signers[0]) {
+    return Err(KeyRecoveryError::SignerMismatch);
+  }
+
   // First let's make sure that the chunks are coming from the same set
   // and we have enough of them.
-  for chunk in &chunks[1..] {
-    if configuration != chunk.configuration() {
+  for (position, chunk) in chunks.iter().enumerate() {
+    if chunks[..position]
+      .iter()
+      .any(|c| !c.has_compatible_configuration(chunk))
+    {
       return Err(KeyRecoveryError::InconsistentConfiguration);
     }
-    if first.key_hash() != chunk.key_hash() {
+  }
+  for chunk in &chunks[1..] {
+    if !first.has_same_key_hash(chunk)
+      || first.vss_digest() != chunk.vss_digest()
+      || first.generation() != chunk.generation()
+      || first.policy_path().map(PolicyPath::policy) != chunk.policy_path().map(PolicyPath::policy)
+    {
       return Err(KeyRecoveryError::InconsistentChunks);
     }
   }
 
-  if chunks.len() < configuration.required() {
+  // Weighted chunks count as multiple share points, but the same chunk
+  // provided twice should not be counted again.
+  let mut unique = Vec::<&Chunk>::with_capacity(chunks.len());
+  for chunk in chunks {
+    if unique.iter().all(|c| c.index() != chunk.index()) {
+      unique.push(chunk);
+    }
+  }
+  let weight: usize = unique.iter().map(|c| c.weight() as usize).sum();
+  if weight < configuration.required() {
     return Err(KeyRecoveryError::NotEnoughChunks);
   }
 
-  let raw_chunks = {
-    let mut raw_chunks = Vec::with_capacity(chunks.len());
-    raw_chunks.extend(chunks.iter().map(|c| &**c.data()));
-    raw_chunks
-  };
+  let key = if first.is_verifiable() {
+    vss::reconstruct(&unique).ok_or(KeyRecoveryError::UnexpectedKey)?
+  } else if let Some(path) = first.policy_path() {
+    Bytes::from(
+      policy::reconstruct(path.policy(), chunks).map_err(|_| KeyRecoveryError::NotEnoughChunks)?,
+    )
+  } else {
+    let raw_chunks = {
+      let mut raw_chunks = Vec::with_capacity(chunks.len());
+      raw_chunks.extend(unique.iter().flat_map(|c| c.points()));
+      raw_chunks
+    };
 
-  let key = gf256::shamir::shamir::reconstruct(&raw_chunks);
+    Bytes::from(gf256::shamir::shamir::reconstruct(&raw_chunks))
+  };
   let key_hash = blake2b512(&*key);
 
-  if &key_hash != first.key_hash() {
+  if !first.matches_key_hash(&key_hash) {
     return Err(KeyRecoveryError::UnexpectedKey);
   }
 
@@ -386,7 +746,7 @@ mod tests {
       assert_eq!(chunks1.len(), 1);
       assert_eq!(
         format!("{:?}", chunks1[0]),
-        r#"Chunk { version: V0, key_hash: Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15"), chunks_configuration: ChunksConfiguration { required: 1, spare: 0 }, chunk_index: 0, chunk_data: String("\u{1}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0169636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
+        r#"Chunk { version: V0, key_id: Full(Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15")), chunks_configuration: ChunksConfiguration { required: 1, spare: 0 }, chunk_index: 0, extensions: [], chunk_data: String("\u{1}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0169636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
       );
     }
 
@@ -400,11 +760,11 @@ mod tests {
       assert_eq!(chunks1.len(), 2);
       assert_eq!(
         format!("{:?}", chunks1[0]),
-        r#"Chunk { version: V0, key_hash: Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15"), chunks_configuration: ChunksConfiguration { required: 1, spare: 1 }, chunk_index: 0, chunk_data: String("\u{1}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0169636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
+        r#"Chunk { version: V0, key_id: Full(Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15")), chunks_configuration: ChunksConfiguration { required: 1, spare: 1 }, chunk_index: 0, extensions: [], chunk_data: String("\u{1}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0169636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
       );
       assert_eq!(
         format!("{:?}", chunks1[1]),
-        r#"Chunk { version: V0, key_hash: Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15"), chunks_configuration: ChunksConfiguration { required: 1, spare: 1 }, chunk_index: 1, chunk_data: String("\u{2}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0269636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
+        r#"Chunk { version: V0, key_id: Full(Hash("57d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221dad0995aa98f72f8b2ddc431093e69c791425bee10d461f003447a0f7fca7b9faa6d15")), chunks_configuration: ChunksConfiguration { required: 1, spare: 1 }, chunk_index: 1, extensions: [], chunk_data: String("\u{2}icodk\0\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}\u{1}") == Bytes("0269636f646b000101010101010101010101010101010101010101010101010101010101010101") }"#
       );
     }
   }
@@ -490,6 +850,25 @@ r#"Bytes("69636f64630057d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221
     assert_eq!(recovered, Err(KeyRecoveryError::InconsistentConfiguration));
   }
 
+  #[test]
+  fn should_only_accept_larger_configuration_of_issued_chunks() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; crate::encryption::KEY_SIZE]);
+    let chunks = split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap());
+    let issued = issue_chunk(&chunks[..2], 3).unwrap();
+    let decode = |c: &Chunk| Chunk::decode(&c.encode()).unwrap();
+    let mut forged = decode(&chunks[1]);
+    forged.chunks_configuration = ChunksConfiguration::new(2, 2).unwrap();
+
+    // when
+    let recovered = recover_key(&[decode(&chunks[2]), issued]);
+    let mismatched = recover_key(&[decode(&chunks[0]), forged]);
+
+    // then
+    assert!(recovered.is_ok());
+    assert_eq!(mismatched, Err(KeyRecoveryError::InconsistentConfiguration));
+  }
+
   #[test]
   fn should_fail_restoring_if_chunks_have_inconsistent_key_hash() {
     let key1 = MessageEncryptionKey::new([1u8; 32]);
@@ -516,8 +895,8 @@ r#"Bytes("69636f64630057d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221
     let mut chunk_a = chunks1.pop().unwrap();
     let mut chunk_b = chunks1.pop().unwrap();
 
-    chunk_a.key_hash = Hash::new([1u8; crate::HASH_SIZE]);
-    chunk_b.key_hash = Hash::new([1u8; crate::HASH_SIZE]);
+    chunk_a.key_id = KeyId::Full(Hash::new([1u8; crate::HASH_SIZE]));
+    chunk_b.key_id = KeyId::Full(Hash::new([1u8; crate::HASH_SIZE]));
 
     // when
     let recovered = recover_key(&[chunk_a, chunk_b]);
@@ -553,7 +932,7 @@ r#"Bytes("69636f64630057d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221
       let mut out = out.clone();
       let err = Chunk::decode(&out).unwrap_err();
       assert_eq!(err, ChunkDecodingError::InvalidVersion);
-      out.push(1);
+      out.push(254);
       let err = Chunk::decode(&out).unwrap_err();
       assert_eq!(err, ChunkDecodingError::InvalidVersion);
     }
@@ -592,7 +971,7 @@ r#"Bytes("69636f64630057d20cb83fe35db059c4ac1a85acab73a1ac0103d1380c8b8d90cc2221
     let ok = Chunk::decode(&out).unwrap();
     assert_eq!(
             format!("{:?}", ok),
-            "Chunk { version: V0, key_hash: Hash(\"01010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101\"), chunks_configuration: ChunksConfiguration { required: 2, spare: 0 }, chunk_index: 0, chunk_data: String(\"\\u{1}\\u{2}\\u{3}\\u{4}\") == Bytes(\"01020304\") }",
+            "Chunk { version: V0, key_id: Full(Hash(\"01010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101\")), chunks_configuration: ChunksConfiguration { required: 2, spare: 0 }, chunk_index: 0, extensions: [], chunk_data: String(\"\\u{1}\\u{2}\\u{3}\\u{4}\") == Bytes(\"01020304\") }",
         );
   }
 }
@@ -0,0 +1,448 @@
+//! Passphrase-protected chunks.
+//!
+//! A [Chunk] printed on paper or sent via e-mail is directly usable
+//! by anyone who finds it. To add a second factor, the chunk data
+//! can be encrypted with a key derived from a passphrase (shared with
+//! the recipient out-of-band) using `Argon2id`.
+//!
+//! The passphrase-derived key is used with `AES-GCM-SIV` and since the
+//! salt is random for every chunk, the nonce is constant. The key hash
+//! and index of the chunk are authenticated as well, so the protected
+//! data can't be moved between chunks.
+//!
+//! Protected chunks need to be unlocked using [Chunk::unprotect] before
+//! they can be used in [super::recover_key].
+
+use aes_gcm_siv::{
+  aead::{rand_core::RngCore, Aead, OsRng, Payload},
+  Aes256GcmSiv, KeyInit, Nonce,
+};
+
+use super::{Chunk, ChunkDecodingError, ChunkExtension, PASSPHRASE_TAG};
+use crate::{encryption::KEY_SIZE, Bytes};
+
+/// Byte size of the random salt.
+pub const SALT_SIZE: usize = 16;
+
+/// Maximal memory cost (in KiB) accepted from a chunk (1GiB).
+pub const MAX_M_COST: u32 = 1 << 20;
+
+/// Maximal number of iterations accepted from a chunk.
+pub const MAX_T_COST: u32 = 16;
+
+/// Maximal degree of parallelism accepted from a chunk.
+pub const MAX_P_COST: u32 = 16;
+
+/// Domain separation string for passphrase encryption.
+const AAD: &[u8] = b"ICOD-Crypto passphrase-protected chunk.";
+
+/// An error which may occur when protecting or unlocking a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PassphraseError {
+  /// The chunk is already protected with a passphrase.
+  #[error("The chunk is already protected.")]
+  AlreadyProtected,
+  /// The chunk is not protected with a passphrase.
+  #[error("The chunk is not protected.")]
+  NotProtected,
+  /// The passphrase is incorrect (or the chunk was tampered with).
+  #[error("Invalid passphrase.")]
+  InvalidPassphrase,
+  /// The key derivation parameters are not accepted by `Argon2`.
+  #[error("Invalid key derivation parameters.")]
+  InvalidParams,
+  /// The unlocked data doesn't match the weight of the chunk.
+  #[error("The unlocked chunk data is malformed.")]
+  MalformedData,
+}
+
+/// Key derivation parameters stored along the protected chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassphraseParams {
+  salt: [u8; SALT_SIZE],
+  /// Memory cost in KiB.
+  m_cost: u32,
+  /// Number of iterations.
+  t_cost: u32,
+  /// Degree of parallelism.
+  p_cost: u32,
+}
+
+impl PassphraseParams {
+  /// Create parameters with a random salt and given `Argon2id` costs.
+  pub fn new(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+    let mut salt = [0u8; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    Self {
+      salt,
+      m_cost,
+      t_cost,
+      p_cost,
+    }
+  }
+
+  /// Return the memory cost (in KiB).
+  pub fn m_cost(&self) -> u32 {
+    self.m_cost
+  }
+
+  /// Return the number of iterations.
+  pub fn t_cost(&self) -> u32 {
+    self.t_cost
+  }
+
+  /// Return the degree of parallelism.
+  pub fn p_cost(&self) -> u32 {
+    self.p_cost
+  }
+
+  /// Check the costs are within the limits, so a tampered chunk can't exhaust
+  /// the memory or stall the recovery.
+  fn is_bounded(&self) -> bool {
+    self.m_cost <= MAX_M_COST && self.t_cost <= MAX_T_COST && self.p_cost <= MAX_P_COST
+  }
+
+  fn derive_key(&self, passphrase: &[u8]) -> Result<[u8; KEY_SIZE], PassphraseError> {
+    if !self.is_bounded() {
+      return Err(PassphraseError::InvalidParams);
+    }
+    let params = argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_SIZE))
+      .map_err(|_| PassphraseError::InvalidParams)?;
+    let argon = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = [0u8; KEY_SIZE];
+    argon
+      .hash_password_into(passphrase, &self.salt, &mut key)
+      .map_err(|_| PassphraseError::InvalidParams)?;
+    Ok(key)
+  }
+
+  /// Encode the parameters as: salt (16 bytes), memory, iterations
+  /// and parallelism costs (4 bytes each, Big Endian).
+  pub(crate) fn encode(&self) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SALT_SIZE + 12);
+    out.extend_from_slice(&self.salt);
+    out.extend_from_slice(&self.m_cost.to_be_bytes());
+    out.extend_from_slice(&self.t_cost.to_be_bytes());
+    out.extend_from_slice(&self.p_cost.to_be_bytes());
+    out
+  }
+
+  pub(crate) fn decode(data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    if data.len() != SALT_SIZE + 12 {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    let u32_at =
+      |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&data[..SALT_SIZE]);
+    let params = Self {
+      salt,
+      m_cost: u32_at(SALT_SIZE),
+      t_cost: u32_at(SALT_SIZE + 4),
+      p_cost: u32_at(SALT_SIZE + 8),
+    };
+    if !params.is_bounded() {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    Ok(params)
+  }
+}
+
+impl Default for PassphraseParams {
+  /// Random salt and default `Argon2id` costs (19MiB, 2 iterations, no parallelism).
+  fn default() -> Self {
+    Self::new(
+      argon2::Params::DEFAULT_M_COST,
+      argon2::Params::DEFAULT_T_COST,
+      argon2::Params::DEFAULT_P_COST,
+    )
+  }
+}
+
+impl Chunk {
+  /// Returns `true` if the chunk data is encrypted with a passphrase.
+  pub fn is_protected(&self) -> bool {
+    self.passphrase_params().is_some()
+  }
+
+  /// Return the key derivation parameters of a protected chunk.
+  pub fn passphrase_params(&self) -> Option<&PassphraseParams> {
+    self.extensions.iter().find_map(|e| match e {
+      ChunkExtension::Passphrase(params) => Some(params),
+      _ => None,
+    })
+  }
+
+  /// Encrypt the chunk data with a key derived from `passphrase` using default parameters.
+  pub fn protect(self, passphrase: &[u8]) -> Result<Self, PassphraseError> {
+    self.protect_with(passphrase, PassphraseParams::default())
+  }
+
+  /// Encrypt the chunk data with a key derived from `passphrase` using given parameters.
+  ///
+  /// The owner's signature (if any) is removed, since it no longer matches the data.
+  pub fn protect_with(
+    mut self,
+    passphrase: &[u8],
+    params: PassphraseParams,
+  ) -> Result<Self, PassphraseError> {
+    if self.is_protected() {
+      return Err(PassphraseError::AlreadyProtected);
+    }
+
+    let mut key = params.derive_key(passphrase)?;
+    let cipher = Aes256GcmSiv::new(&key.into());
+    crate::wipe(&mut key);
+    let aad = self.passphrase_aad();
+    let encrypted = cipher
+      .encrypt(
+        &Nonce::default(),
+        Payload {
+          msg: &self.chunk_data,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| PassphraseError::InvalidParams)?;
+
+    self.chunk_data.wipe();
+    self.chunk_data = Bytes::from(encrypted);
+    self.set_extension(ChunkExtension::Passphrase(params));
+    self.remove_signature();
+    Ok(self)
+  }
+
+  /// Decrypt the chunk data protected with [Chunk::protect].
+  ///
+  /// Since the data changes, the owner's signature (if any) is removed,
+  /// so make sure to verify it first.
+  pub fn unprotect(mut self, passphrase: &[u8]) -> Result<Self, PassphraseError> {
+    let params = self
+      .passphrase_params()
+      .ok_or(PassphraseError::NotProtected)?
+      .clone();
+
+    let mut key = params.derive_key(passphrase)?;
+    let cipher = Aes256GcmSiv::new(&key.into());
+    crate::wipe(&mut key);
+    let aad = self.passphrase_aad();
+    let decrypted = cipher
+      .decrypt(
+        &Nonce::default(),
+        Payload {
+          msg: &self.chunk_data,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| PassphraseError::InvalidPassphrase)?;
+
+    self.chunk_data = Bytes::from(decrypted);
+    self.remove_extension(PASSPHRASE_TAG);
+    if !self.has_valid_points() {
+      return Err(PassphraseError::MalformedData);
+    }
+    self.remove_signature();
+    Ok(self)
+  }
+
+  fn passphrase_aad(&self) -> Vec<u8> {
+    let mut aad = AAD.to_vec();
+    aad.extend_from_slice(self.key_id.as_bytes());
+    aad.push(self.chunk_index);
+    aad
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::MessageEncryptionKey;
+  use crate::shamir::{
+    recover_key, split_into_chunks, split_into_weighted_chunks, ChunksConfiguration,
+    KeyRecoveryError, WeightedConfiguration,
+  };
+  use pretty_assertions::assert_eq;
+
+  /// Cheap parameters to keep the tests fast.
+  fn params() -> PassphraseParams {
+    PassphraseParams::new(8, 1, 1)
+  }
+
+  fn chunks() -> Vec<Chunk> {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap())
+  }
+
+  #[test]
+  fn should_protect_and_unprotect_chunk() {
+    // given
+    let mut chunks = chunks();
+    let original = chunks[0].encode();
+
+    // when
+    let protected = chunks.remove(0).protect_with(b"secret", params()).unwrap();
+    let encoded = protected.encode();
+    let decoded = Chunk::decode(&encoded).unwrap();
+
+    // then
+    assert!(decoded.is_protected());
+    assert_eq!(decoded.version(), 1);
+    assert_eq!(decoded.data().len(), original.len() - 73 + 16);
+    assert_eq!(decoded.passphrase_params(), protected.passphrase_params());
+
+    let unprotected = decoded.unprotect(b"secret").unwrap();
+    assert!(!unprotected.is_protected());
+    assert_eq!(unprotected.encode(), original);
+  }
+
+  #[test]
+  fn should_reject_invalid_passphrase() {
+    let protected = chunks()
+      .remove(0)
+      .protect_with(b"secret", params())
+      .unwrap();
+
+    assert_eq!(
+      protected.unprotect(b"Secret").unwrap_err(),
+      PassphraseError::InvalidPassphrase
+    );
+  }
+
+  #[test]
+  fn should_bind_protected_data_to_the_chunk() {
+    let mut chunks = chunks();
+    let mut protected = chunks.remove(0).protect_with(b"secret", params()).unwrap();
+    protected.chunk_index = 2;
+
+    assert_eq!(
+      protected.unprotect(b"secret").unwrap_err(),
+      PassphraseError::InvalidPassphrase
+    );
+  }
+
+  #[test]
+  fn should_require_unprotected_chunks_for_recovery() {
+    // given
+    let mut chunks = chunks();
+    let a = chunks.remove(0).protect_with(b"secret", params()).unwrap();
+    let b = chunks.remove(0);
+    let protected = [
+      Chunk::decode(&a.encode()).unwrap(),
+      Chunk::decode(&b.encode()).unwrap(),
+    ];
+
+    // when
+    let err = recover_key(&protected).unwrap_err();
+    let key = recover_key(&[a.unprotect(b"secret").unwrap(), b]);
+
+    // then
+    assert_eq!(err, KeyRecoveryError::PassphraseProtected);
+    assert_eq!(
+      key.unwrap().encode(),
+      MessageEncryptionKey::new([1u8; KEY_SIZE]).encode()
+    );
+  }
+
+  #[test]
+  fn should_protect_weighted_chunks() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let configuration = WeightedConfiguration::new(3, vec![3, 1, 1]).unwrap();
+    let mut chunks = split_into_weighted_chunks(key, &configuration);
+    let protected = chunks.remove(0).protect_with(b"secret", params()).unwrap();
+
+    // when
+    let decoded = Chunk::decode(&protected.encode()).unwrap();
+    let unprotected = decoded.unprotect(b"secret").unwrap();
+    let key = recover_key(&[Chunk::decode(&unprotected.encode()).unwrap()]);
+
+    // then
+    assert_eq!(unprotected.weight(), 3);
+    assert_eq!(
+      key.unwrap().encode(),
+      MessageEncryptionKey::new([1u8; KEY_SIZE]).encode()
+    );
+  }
+
+  #[test]
+  fn should_reject_malformed_weighted_data() {
+    // given
+    let mut protected = chunks()
+      .remove(0)
+      .protect_with(b"secret", params())
+      .unwrap();
+    protected.set_extension(ChunkExtension::Weight(2));
+
+    // when
+    let decoded = Chunk::decode(&protected.encode()).unwrap();
+
+    // then
+    assert_eq!(
+      decoded.unprotect(b"secret").unwrap_err(),
+      PassphraseError::MalformedData
+    );
+  }
+
+  #[test]
+  fn should_not_protect_twice() {
+    let protected = chunks()
+      .remove(0)
+      .protect_with(b"secret", params())
+      .unwrap();
+
+    assert_eq!(
+      protected.protect_with(b"secret", params()).unwrap_err(),
+      PassphraseError::AlreadyProtected
+    );
+    assert_eq!(
+      chunks().remove(0).unprotect(b"secret").unwrap_err(),
+      PassphraseError::NotProtected
+    );
+  }
+
+  #[test]
+  fn should_reject_invalid_params() {
+    let invalid = PassphraseParams::new(0, 0, 0);
+    let excessive = PassphraseParams::new(MAX_M_COST + 1, 1, 1);
+
+    assert_eq!(
+      chunks()
+        .remove(0)
+        .protect_with(b"secret", invalid)
+        .unwrap_err(),
+      PassphraseError::InvalidParams
+    );
+    assert_eq!(
+      chunks()
+        .remove(0)
+        .protect_with(b"secret", excessive)
+        .unwrap_err(),
+      PassphraseError::InvalidParams
+    );
+  }
+
+  #[test]
+  fn should_reject_excessive_params_of_decoded_chunk() {
+    // given
+    let protected = chunks()
+      .remove(0)
+      .protect_with(b"secret", params())
+      .unwrap();
+    let encode = |params: PassphraseParams| {
+      let mut chunk = Chunk::decode(&protected.encode()).unwrap();
+      chunk.set_extension(ChunkExtension::Passphrase(params));
+      chunk.encode()
+    };
+
+    // when
+    let results = [
+      PassphraseParams::new(MAX_M_COST + 1, 1, 1),
+      PassphraseParams::new(8, MAX_T_COST + 1, 1),
+      PassphraseParams::new(8, 1, MAX_P_COST + 1),
+    ]
+    .map(|params| Chunk::decode(&encode(params)).map(|_| ()));
+
+    // then
+    assert_eq!(results, [Err(ChunkDecodingError::InvalidExtension); 3]);
+    assert!(Chunk::decode(&encode(params())).is_ok());
+  }
+}
@@ -0,0 +1,614 @@
+//! Compartmented access structures.
+//!
+//! A flat `required`/`spare` threshold can't express policies like
+//! "2 of the 3 family members AND 1 of the 2 lawyers" or "any 3 friends
+//! OR the executor alone". A [Policy] is a tree of threshold gates over
+//! named groups, with recipients as leaves.
+//!
+//! The key is split recursively: every gate splits the secret it receives
+//! into shares for it's children (`AND` is an `N-of-N` gate and `OR` is
+//! a `1-of-N` gate), and every recipient gets a [Chunk] with the share
+//! of it's leaf. The chunks record their path in the tree along with the
+//! whole [Policy] (see [ChunkExtension::Policy]), so the recovery can
+//! evaluate the tree and explain which gates are still unsatisfied.
+
+use super::{Chunk, ChunkDecodingError, ChunkExtension, ChunksConfiguration, KeyRecoveryError};
+use crate::encryption::MessageEncryptionKey;
+use crate::{blake2b512, Bytes};
+
+/// Maximal byte length of recipient or group name.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Maximal depth of the policy tree.
+pub const MAX_DEPTH: usize = 16;
+
+/// Maximal byte length of the encoded [Policy].
+///
+/// Every chunk carries the whole policy along with it's path (see [PolicyPath]),
+/// which has to fit in a single [ChunkExtension].
+pub const MAX_ENCODED_LEN: usize = u16::MAX as usize - 1 - MAX_DEPTH;
+
+/// A node of the access structure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Policy {
+  /// A single recipient receiving a [Chunk].
+  Recipient(String),
+  /// A gate satisfied when at least `required` of it's children are satisfied.
+  Threshold {
+    /// Name of the group.
+    name: String,
+    /// Number of children required to satisfy the gate.
+    required: u8,
+    /// Recipients or nested groups.
+    children: Vec<Policy>,
+  },
+}
+
+/// An error which may occur when the [Policy] is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PolicyError {
+  /// The gate has no children or requires more children than it has.
+  #[error("The gate threshold is invalid.")]
+  InvalidThreshold,
+  /// The name is too long.
+  #[error("The name is too long.")]
+  NameTooLong,
+  /// The tree has too many recipients, children or levels, or it's encoding
+  /// exceeds [MAX_ENCODED_LEN] bytes.
+  #[error("The policy is too big.")]
+  TooBig,
+}
+
+impl Policy {
+  /// Create a leaf for a single recipient.
+  pub fn recipient(name: impl Into<String>) -> Self {
+    Self::Recipient(name.into())
+  }
+
+  /// Create a gate requiring `required` of the `children`.
+  pub fn threshold(name: impl Into<String>, required: u8, children: Vec<Policy>) -> Self {
+    Self::Threshold {
+      name: name.into(),
+      required,
+      children,
+    }
+  }
+
+  /// Create a gate requiring all of the `children`.
+  pub fn all(name: impl Into<String>, children: Vec<Policy>) -> Self {
+    let required = children.len().min(u8::MAX as usize) as u8;
+    Self::threshold(name, required, children)
+  }
+
+  /// Create a gate requiring any of the `children`.
+  pub fn any(name: impl Into<String>, children: Vec<Policy>) -> Self {
+    Self::threshold(name, 1, children)
+  }
+
+  /// Return the name of the recipient or group.
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Recipient(name) => name,
+      Self::Threshold { name, .. } => name,
+    }
+  }
+
+  /// Return the number of recipients (leaves) of the tree.
+  pub fn recipients(&self) -> usize {
+    match self {
+      Self::Recipient(_) => 1,
+      Self::Threshold { children, .. } => children.iter().map(Policy::recipients).sum(),
+    }
+  }
+
+  /// Check that the tree is well formed.
+  pub fn validate(&self) -> Result<(), PolicyError> {
+    if self.recipients() > u8::MAX as usize {
+      return Err(PolicyError::TooBig);
+    }
+    self.validate_node(0)?;
+    if self.encoded_len() > MAX_ENCODED_LEN {
+      return Err(PolicyError::TooBig);
+    }
+    Ok(())
+  }
+
+  fn encoded_len(&self) -> usize {
+    match self {
+      Self::Recipient(name) => 2 + name.len(),
+      Self::Threshold { name, children, .. } => {
+        4 + name.len() + children.iter().map(Policy::encoded_len).sum::<usize>()
+      }
+    }
+  }
+
+  fn validate_node(&self, depth: usize) -> Result<(), PolicyError> {
+    if depth > MAX_DEPTH {
+      return Err(PolicyError::TooBig);
+    }
+    if self.name().len() > MAX_NAME_LEN {
+      return Err(PolicyError::NameTooLong);
+    }
+    if let Self::Threshold {
+      required, children, ..
+    } = self
+    {
+      if children.len() >= u8::MAX as usize {
+        return Err(PolicyError::TooBig);
+      }
+      if *required == 0 || *required as usize > children.len() {
+        return Err(PolicyError::InvalidThreshold);
+      }
+      for child in children {
+        child.validate_node(depth + 1)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Return the configuration of the top-level gate.
+  fn configuration(&self) -> ChunksConfiguration {
+    match self {
+      Self::Recipient(_) => ChunksConfiguration::new(1, 0),
+      Self::Threshold {
+        required, children, ..
+      } => ChunksConfiguration::new(*required, (children.len() - *required as usize) as u8),
+    }
+    .expect("The policy is validated; qed")
+  }
+
+  /// Encode the tree.
+  ///
+  /// Every recipient is encoded as `0` byte followed by the name length (1 byte)
+  /// and the name, while every gate is encoded as `1` byte followed by the name
+  /// length (1 byte), the name, number of required children (1 byte), number of
+  /// children (1 byte) and all of the children.
+  pub fn encode(&self) -> Vec<u8> {
+    let mut out = vec![];
+    self.encode_to(&mut out);
+    out
+  }
+
+  fn encode_to(&self, out: &mut Vec<u8>) {
+    match self {
+      Self::Recipient(name) => {
+        out.push(0);
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+      }
+      Self::Threshold {
+        name,
+        required,
+        children,
+      } => {
+        out.push(1);
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+        out.push(*required);
+        out.push(children.len() as u8);
+        for child in children {
+          child.encode_to(out);
+        }
+      }
+    }
+  }
+
+  /// Decode the tree produced by [Policy::encode].
+  pub fn decode(data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    let (policy, rest) = Self::decode_node(data, 0)?;
+    if !rest.is_empty() {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    policy
+      .validate()
+      .map_err(|_| ChunkDecodingError::InvalidExtension)?;
+    Ok(policy)
+  }
+
+  fn decode_node(data: &[u8], depth: usize) -> Result<(Self, &[u8]), ChunkDecodingError> {
+    let err = ChunkDecodingError::InvalidExtension;
+    if depth > MAX_DEPTH {
+      return Err(err);
+    }
+    let (&kind, data) = data.split_first().ok_or(err)?;
+    let (&len, data) = data.split_first().ok_or(err)?;
+    if data.len() < len as usize {
+      return Err(err);
+    }
+    let (name, data) = data.split_at(len as usize);
+    let name = String::from_utf8(name.to_vec()).map_err(|_| err)?;
+    match (kind, data) {
+      (0, data) => Ok((Self::Recipient(name), data)),
+      (1, [required, count, data @ ..]) => {
+        let mut data = data;
+        let mut children = Vec::with_capacity(*count as usize);
+        for _ in 0..*count {
+          let (child, rest) = Self::decode_node(data, depth + 1)?;
+          children.push(child);
+          data = rest;
+        }
+        Ok((Self::threshold(name, *required, children), data))
+      }
+      _ => Err(err),
+    }
+  }
+
+  /// Return the node at given path.
+  fn at(&self, path: &[u8]) -> Option<&Policy> {
+    match (self, path.split_first()) {
+      (_, None) => Some(self),
+      (Self::Threshold { children, .. }, Some((idx, rest))) => {
+        children.get(*idx as usize)?.at(rest)
+      }
+      _ => None,
+    }
+  }
+}
+
+/// The position of the [Chunk] in the [Policy] tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyPath {
+  path: Vec<u8>,
+  policy: Policy,
+}
+
+impl PolicyPath {
+  /// Return the indices of the consecutive children leading to the recipient.
+  pub fn path(&self) -> &[u8] {
+    &self.path
+  }
+
+  /// Return the whole policy tree.
+  pub fn policy(&self) -> &Policy {
+    &self.policy
+  }
+
+  /// Return the name of the recipient.
+  pub fn recipient(&self) -> &str {
+    self
+      .policy
+      .at(&self.path)
+      .map(Policy::name)
+      .unwrap_or_default()
+  }
+
+  /// Encode as path length (1 byte), the path and the encoded [Policy].
+  pub(crate) fn encode(&self) -> Vec<u8> {
+    let mut out = vec![self.path.len() as u8];
+    out.extend_from_slice(&self.path);
+    self.policy.encode_to(&mut out);
+    out
+  }
+
+  pub(crate) fn decode(data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    let (&len, data) = data
+      .split_first()
+      .ok_or(ChunkDecodingError::InvalidExtension)?;
+    if data.len() < len as usize {
+      return Err(ChunkDecodingError::InvalidExtension);
+    }
+    let (path, policy) = data.split_at(len as usize);
+    let policy = Policy::decode(policy)?;
+    match policy.at(path) {
+      Some(Policy::Recipient(_)) => Ok(Self {
+        path: path.to_vec(),
+        policy,
+      }),
+      _ => Err(ChunkDecodingError::InvalidExtension),
+    }
+  }
+}
+
+/// A gate which is not satisfied by the provided chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsatisfiedGate {
+  /// Name of the group.
+  pub name: String,
+  /// Path of the gate in the tree.
+  pub path: Vec<u8>,
+  /// Number of children required to satisfy the gate.
+  pub required: u8,
+  /// Number of children that are already satisfied.
+  pub satisfied: u8,
+}
+
+impl std::fmt::Display for UnsatisfiedGate {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}: {} of {} required",
+      self.name, self.satisfied, self.required
+    )
+  }
+}
+
+/// The error which may occur during key recovery according to the [Policy].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PolicyRecoveryError {
+  /// The chunks do not satisfy the policy.
+  #[error("The policy is not satisfied.")]
+  Unsatisfied(Vec<UnsatisfiedGate>),
+  /// The key could not be recovered.
+  #[error("{0}")]
+  Recovery(#[from] KeyRecoveryError),
+}
+
+/// Split given key into chunks according to the [Policy].
+///
+/// The function returns one [Chunk] per recipient, in the depth-first
+/// order of the tree.
+pub fn split_with_policy(
+  key: MessageEncryptionKey,
+  policy: &Policy,
+) -> Result<Vec<Chunk>, PolicyError> {
+  policy.validate()?;
+
+  let mut payload = key.encode();
+  let key_hash = blake2b512(&payload);
+  let mut leaves = vec![];
+  split_node(policy, &payload, &mut vec![], &mut leaves);
+  payload.wipe();
+
+  let configuration = policy.configuration();
+  Ok(
+    leaves
+      .into_iter()
+      .enumerate()
+      .map(|(idx, (path, share))| {
+        let mut chunk = Chunk::new(idx as u8, share, configuration, key_hash.clone());
+        chunk.set_extension(ChunkExtension::Policy(PolicyPath {
+          path,
+          policy: policy.clone(),
+        }));
+        chunk
+      })
+      .collect(),
+  )
+}
+
+fn split_node(policy: &Policy, secret: &[u8], path: &mut Vec<u8>, out: &mut Vec<(Vec<u8>, Bytes)>) {
+  match policy {
+    Policy::Recipient(_) => out.push((path.clone(), Bytes::from_slice(secret))),
+    Policy::Threshold {
+      required, children, ..
+    } => {
+      let shares = gf256::shamir::shamir::generate(secret, children.len(), *required as usize);
+      for (idx, (child, mut share)) in children.iter().zip(shares).enumerate() {
+        path.push(idx as u8);
+        split_node(child, &share, path, out);
+        path.pop();
+        crate::wipe(&mut share);
+      }
+    }
+  }
+}
+
+/// Given a slice of [Chunk]s split according to a [Policy] recover the original key.
+///
+/// Contrary to [super::recover_key] in case the chunks are not sufficient
+/// the error contains all of the unsatisfied gates.
+pub fn recover_key(chunks: &[Chunk]) -> Result<MessageEncryptionKey, PolicyRecoveryError> {
+  match super::recover_key(chunks) {
+    Err(KeyRecoveryError::NotEnoughChunks) => {
+      let policy = chunks
+        .first()
+        .and_then(Chunk::policy_path)
+        .ok_or(KeyRecoveryError::NotEnoughChunks)?;
+      let mut unsatisfied = vec![];
+      evaluate(policy.policy(), &mut vec![], chunks, &mut unsatisfied);
+      Err(PolicyRecoveryError::Unsatisfied(unsatisfied))
+    }
+    result => Ok(result?),
+  }
+}
+
+/// Reconstruct the encoded key evaluating the policy tree.
+///
+/// Returns the list of unsatisfied gates in case the chunks are not sufficient.
+pub(crate) fn reconstruct(
+  policy: &Policy,
+  chunks: &[Chunk],
+) -> Result<Vec<u8>, Vec<UnsatisfiedGate>> {
+  let mut unsatisfied = vec![];
+  evaluate(policy, &mut vec![], chunks, &mut unsatisfied).ok_or(unsatisfied)
+}
+
+fn evaluate(
+  policy: &Policy,
+  path: &mut Vec<u8>,
+  chunks: &[Chunk],
+  unsatisfied: &mut Vec<UnsatisfiedGate>,
+) -> Option<Vec<u8>> {
+  match policy {
+    Policy::Recipient(_) => chunks
+      .iter()
+      .find(|c| c.policy_path().map(PolicyPath::path) == Some(path.as_slice()))
+      .map(|c| c.data().to_vec()),
+    Policy::Threshold {
+      name,
+      required,
+      children,
+    } => {
+      let mut shares = vec![];
+      for (idx, child) in children.iter().enumerate() {
+        path.push(idx as u8);
+        shares.extend(evaluate(child, path, chunks, unsatisfied));
+        path.pop();
+      }
+      let result = if shares.len() >= *required as usize {
+        Some(gf256::shamir::shamir::reconstruct(&shares))
+      } else {
+        unsatisfied.push(UnsatisfiedGate {
+          name: name.clone(),
+          path: path.clone(),
+          required: *required,
+          satisfied: shares.len() as u8,
+        });
+        None
+      };
+      shares.iter_mut().for_each(|s| crate::wipe(s));
+      result
+    }
+  }
+}
+
+impl Chunk {
+  /// Return the position of the chunk in the [Policy] tree.
+  pub fn policy_path(&self) -> Option<&PolicyPath> {
+    self.extensions.iter().find_map(|e| match e {
+      ChunkExtension::Policy(path) => Some(path),
+      _ => None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  /// 2 of the 3 family members AND 1 of the 2 lawyers.
+  fn family_and_lawyers() -> Policy {
+    Policy::all(
+      "root",
+      vec![
+        Policy::threshold(
+          "family",
+          2,
+          vec![
+            Policy::recipient("mom"),
+            Policy::recipient("dad"),
+            Policy::recipient("sis"),
+          ],
+        ),
+        Policy::any(
+          "lawyers",
+          vec![Policy::recipient("alice"), Policy::recipient("bob")],
+        ),
+      ],
+    )
+  }
+
+  fn decode(chunks: &[Chunk], indices: &[usize]) -> Vec<Chunk> {
+    indices
+      .iter()
+      .map(|i| Chunk::decode(&chunks[*i].encode()).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn should_encode_and_decode_policy() {
+    let policy = family_and_lawyers();
+
+    let decoded = Policy::decode(&policy.encode());
+
+    assert_eq!(policy.encoded_len(), policy.encode().len());
+    assert_eq!(decoded, Ok(policy));
+  }
+
+  #[test]
+  fn should_validate_policy() {
+    assert_eq!(
+      Policy::threshold("x", 3, vec![Policy::recipient("a")]).validate(),
+      Err(PolicyError::InvalidThreshold)
+    );
+    assert_eq!(
+      Policy::any("x", vec![]).validate(),
+      Err(PolicyError::InvalidThreshold)
+    );
+    assert_eq!(
+      Policy::recipient("x".repeat(MAX_NAME_LEN + 1)).validate(),
+      Err(PolicyError::NameTooLong)
+    );
+    let name = "x".repeat(MAX_NAME_LEN);
+    let chain = |depth| {
+      (0..depth).fold(Policy::recipient(&name), |child, _| {
+        Policy::all(&name, vec![child])
+      })
+    };
+    let big = Policy::any(&name, (0..100).map(|_| chain(10)).collect());
+    assert!(big.encode().len() > MAX_ENCODED_LEN);
+    assert_eq!(big.validate(), Err(PolicyError::TooBig));
+    assert_eq!(
+      split_with_policy(key(), &big).unwrap_err(),
+      PolicyError::TooBig
+    );
+  }
+
+  #[test]
+  fn should_split_and_recover_with_policy() {
+    // given
+    let chunks = split_with_policy(key(), &family_and_lawyers()).unwrap();
+
+    // when
+    let recovered = recover_key(&decode(&chunks, &[0, 2, 4])).unwrap();
+
+    // then
+    assert_eq!(chunks.len(), 5);
+    assert_eq!(chunks[4].policy_path().unwrap().path(), &[1, 1]);
+    assert_eq!(chunks[4].policy_path().unwrap().recipient(), "bob");
+    assert_eq!(recovered.encode(), key().encode());
+  }
+
+  #[test]
+  fn should_explain_unsatisfied_gates() {
+    // given
+    let chunks = split_with_policy(key(), &family_and_lawyers()).unwrap();
+
+    // when
+    let only_family = recover_key(&decode(&chunks, &[0, 1, 2]));
+    let not_enough_family = recover_key(&decode(&chunks, &[0, 3, 4]));
+
+    // then
+    let gate = |name: &str, path: Vec<u8>, required, satisfied| UnsatisfiedGate {
+      name: name.into(),
+      path,
+      required,
+      satisfied,
+    };
+    assert_eq!(
+      only_family.unwrap_err(),
+      PolicyRecoveryError::Unsatisfied(vec![
+        gate("lawyers", vec![1], 1, 0),
+        gate("root", vec![], 2, 1)
+      ])
+    );
+    assert_eq!(
+      not_enough_family.unwrap_err(),
+      PolicyRecoveryError::Unsatisfied(vec![
+        gate("family", vec![0], 2, 1),
+        gate("root", vec![], 2, 1)
+      ])
+    );
+  }
+
+  #[test]
+  fn should_support_alternative_branches() {
+    // any 3 friends OR the executor alone
+    let policy = Policy::any(
+      "root",
+      vec![
+        Policy::threshold(
+          "friends",
+          3,
+          (0..4)
+            .map(|i| Policy::recipient(format!("friend {}", i)))
+            .collect(),
+        ),
+        Policy::recipient("executor"),
+      ],
+    );
+    let chunks = split_with_policy(key(), &policy).unwrap();
+
+    let executor = recover_key(&decode(&chunks, &[4])).unwrap();
+    let friends = recover_key(&decode(&chunks, &[0, 1, 3])).unwrap();
+
+    assert_eq!(executor.encode(), key().encode());
+    assert_eq!(friends.encode(), key().encode());
+  }
+}
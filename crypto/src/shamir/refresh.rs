@@ -0,0 +1,143 @@
+//! Proactive share refresh.
+//!
+//! When a chunk might have leaked (a lost laptop, a divorce) the owner doesn't
+//! have to re-encrypt the message and re-distribute everything. Given enough
+//! current chunks, [refresh_chunks] produces a brand new set of chunks for the
+//! same [MessageEncryptionKey], so the encrypted message parts stay valid.
+//!
+//! The refreshed chunks are generated from a fresh random polynomial and carry
+//! an incremented generation number (see [ChunkExtension::Generation]), hence
+//! they can't be combined with the chunks of the previous generation. Once the
+//! old chunks are destroyed by the honest recipients, the leaked one is useless.
+//!
+//! NOTE the key is reconstructed in memory during the refresh.
+
+use super::{recover_key, Chunk, ChunkExtension, KeyRecoveryError};
+use crate::encryption::MessageEncryptionKey;
+
+/// The error which may occur during the share refresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RefreshError {
+  /// The current chunks could not be used to recover the key.
+  #[error("{0}")]
+  Recovery(#[from] KeyRecoveryError),
+  /// Refreshing verifiable, weighted or policy chunks is not supported.
+  #[error("Only regular chunks can be refreshed.")]
+  Unsupported,
+  /// The chunks have been refreshed too many times.
+  #[error("The chunks can't be refreshed anymore.")]
+  GenerationOverflow,
+}
+
+/// Produce a new set of chunks for the key recovered from given `chunks`.
+///
+/// The new chunks have the same configuration as the current ones, but
+/// are incompatible with them. Note that the owner's signature is not
+/// preserved and the refreshed chunks need to be signed again (see [Chunk::sign]).
+pub fn refresh_chunks(chunks: &[Chunk]) -> Result<Vec<Chunk>, RefreshError> {
+  let unsupported = |c: &Chunk| c.is_verifiable() || c.weight() > 1 || c.policy_path().is_some();
+  if chunks.iter().any(unsupported) {
+    return Err(RefreshError::Unsupported);
+  }
+
+  let key: MessageEncryptionKey = recover_key(chunks)?;
+  let first = &chunks[0];
+  let generation = first
+    .generation()
+    .checked_add(1)
+    .ok_or(RefreshError::GenerationOverflow)?;
+
+  Ok(
+    super::split_into_chunks(key, first.configuration())
+      .into_iter()
+      .map(|mut chunk| {
+        chunk.set_extension(ChunkExtension::Generation(generation));
+        chunk
+      })
+      .collect(),
+  )
+}
+
+impl Chunk {
+  /// Return how many times the chunks were refreshed.
+  pub fn generation(&self) -> u16 {
+    self
+      .extensions
+      .iter()
+      .find_map(|e| match e {
+        ChunkExtension::Generation(generation) => Some(*generation),
+        _ => None,
+      })
+      .unwrap_or(0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use crate::shamir::{split_into_chunks, split_into_weighted_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  fn decode(chunks: &[Chunk]) -> Vec<Chunk> {
+    chunks
+      .iter()
+      .map(|c| Chunk::decode(&c.encode()).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn should_refresh_chunks() {
+    // given
+    let configuration = ChunksConfiguration::new(2, 1).unwrap();
+    let old = split_into_chunks(key(), configuration);
+
+    // when
+    let new = decode(&refresh_chunks(&old[1..3]).unwrap());
+    let newer = decode(&refresh_chunks(&new[0..2]).unwrap());
+
+    // then
+    assert_eq!(new.len(), 3);
+    assert_eq!(new[0].generation(), 1);
+    assert_eq!(newer[0].generation(), 2);
+    assert_eq!(new[0].configuration(), configuration);
+    assert_eq!(new[0].key_hash(), old[0].key_hash());
+    assert_eq!(recover_key(&new[1..3]).unwrap().encode(), key().encode());
+    assert_eq!(recover_key(&newer[0..2]).unwrap().encode(), key().encode());
+  }
+
+  #[test]
+  fn should_not_combine_chunks_of_different_generations() {
+    let old = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+    let new = decode(&refresh_chunks(&old[0..2]).unwrap());
+    let mixed = [
+      Chunk::decode(&old[0].encode()).unwrap(),
+      Chunk::decode(&new[1].encode()).unwrap(),
+    ];
+
+    assert_eq!(
+      recover_key(&mixed).unwrap_err(),
+      KeyRecoveryError::InconsistentChunks
+    );
+  }
+
+  #[test]
+  fn should_reject_unsupported_chunks() {
+    let configuration = crate::shamir::WeightedConfiguration::new(2, vec![2, 1]).unwrap();
+    let weighted = split_into_weighted_chunks(key(), &configuration);
+    let old = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+
+    assert_eq!(
+      refresh_chunks(&weighted).unwrap_err(),
+      RefreshError::Unsupported
+    );
+    assert_eq!(
+      refresh_chunks(&old[0..1]).unwrap_err(),
+      RefreshError::Recovery(KeyRecoveryError::NotEnoughChunks)
+    );
+  }
+}
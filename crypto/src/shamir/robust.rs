@@ -0,0 +1,279 @@
+//! Recovery tolerating corrupted chunks.
+//!
+//! [super::recover_key] passes every supplied chunk to the reconstruction, so a single
+//! mistyped or bit-rotted chunk makes the recovery fail, even when enough good
+//! chunks are present.
+//!
+//! [recover_key_robust] uses the redundancy instead: chunks with invalid
+//! signatures or metadata not matching the majority are set aside, and subsets
+//! of the remaining chunks are searched for one that reconstructs the key
+//! matching the `key_hash`. All of the chunks are then checked against
+//! the recovered polynomial to report the inconsistent ones.
+
+use super::{issue::interpolate_at, recover_key, Chunk, KeyRecoveryError};
+use crate::blake2b512;
+use crate::encryption::MessageEncryptionKey;
+use crate::identity::IdentityPublicKey;
+
+/// Maximal number of subsets tried before giving up.
+pub const MAX_ATTEMPTS: usize = 100_000;
+
+/// The key recovered with [recover_key_robust].
+pub struct RobustRecovery {
+  /// The recovered key.
+  pub key: MessageEncryptionKey,
+  /// Sorted indices of chunks which are not consistent with the recovered key.
+  pub invalid_chunks: Vec<u8>,
+}
+
+/// Recover the key ignoring corrupted chunks.
+///
+/// The function requires at least `required` valid chunks (or share points) and
+/// returns the key along with a list of indices of the chunks that were found
+/// inconsistent.
+///
+/// Verifiable chunks (see [super::vss]) and chunks split according to a policy
+/// (see [super::policy]) are recovered using [super::recover_key].
+pub fn recover_key_robust(chunks: &[Chunk]) -> Result<RobustRecovery, KeyRecoveryError> {
+  if chunks.iter().any(Chunk::is_protected) {
+    return Err(KeyRecoveryError::PassphraseProtected);
+  }
+  if chunks
+    .iter()
+    .any(|c| c.is_verifiable() || c.policy_path().is_some())
+  {
+    return recover_key(chunks).map(|key| RobustRecovery {
+      key,
+      invalid_chunks: vec![],
+    });
+  }
+
+  let mut invalid_chunks = vec![];
+  let mut candidates = vec![];
+  for chunk in chunks {
+    match (chunk.signer(), chunk.data().len()) {
+      (Err(_), _) | (_, 0) => invalid_chunks.push(chunk.index()),
+      (Ok(signer), _) => {
+        let group = Group::of(chunk, signer);
+        if candidates.iter().all(|(g, c): &(Group, &Chunk)| {
+          g != &group || c.index() != chunk.index() || c.data() != chunk.data()
+        }) {
+          candidates.push((group, chunk));
+        }
+      }
+    }
+  }
+
+  // Chunks which metadata doesn't match the majority are considered corrupted.
+  let majority = candidates
+    .iter()
+    .max_by_key(|(group, _)| candidates.iter().filter(|(g, _)| g == group).count())
+    .map(|(group, _)| group.clone())
+    .ok_or(KeyRecoveryError::NotEnoughChunks)?;
+  let (group, other): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|(g, _)| g == &majority);
+  invalid_chunks.extend(other.iter().map(|(_, c)| c.index()));
+  let group: Vec<&Chunk> = group.into_iter().map(|(_, c)| c).collect();
+
+  let weight: usize = group.iter().map(|c| c.weight() as usize).sum();
+  if weight < majority.required {
+    return Err(KeyRecoveryError::NotEnoughChunks);
+  }
+
+  // Prefer the full `key_hash` over the set identifier of compact chunks.
+  let reference = group
+    .iter()
+    .find(|c| c.key_hash().is_some())
+    .unwrap_or(&group[0]);
+  let mut search = Search {
+    group: &group,
+    required: majority.required,
+    reference,
+    attempts: 0,
+  };
+  let (key, subset) = search
+    .run(0, &mut vec![], 0)
+    .ok_or(KeyRecoveryError::UnexpectedKey)?;
+
+  let points: Vec<&[u8]> = subset.iter().flat_map(|c| c.points()).collect();
+  for chunk in &group {
+    if chunk.points().any(|p| interpolate_at(&points, p[0]) != p) {
+      invalid_chunks.push(chunk.index());
+    }
+  }
+  invalid_chunks.sort_unstable();
+  invalid_chunks.dedup();
+
+  Ok(RobustRecovery {
+    key: MessageEncryptionKey::decode(&key)?,
+    invalid_chunks,
+  })
+}
+
+/// Metadata that has to be shared by all chunks of one set.
+#[derive(Clone, PartialEq, Eq)]
+struct Group {
+  set_id: Vec<u8>,
+  required: usize,
+  generation: u16,
+  signer: Option<IdentityPublicKey>,
+  point_len: usize,
+}
+
+impl Group {
+  fn of(chunk: &Chunk, signer: Option<IdentityPublicKey>) -> Self {
+    Self {
+      set_id: chunk.set_id().to_vec(),
+      required: chunk.configuration().required(),
+      generation: chunk.generation(),
+      signer,
+      point_len: chunk.data().len() / chunk.weight() as usize,
+    }
+  }
+}
+
+struct Search<'a> {
+  group: &'a [&'a Chunk],
+  required: usize,
+  reference: &'a Chunk,
+  attempts: usize,
+}
+
+impl<'a> Search<'a> {
+  /// Find a subset of chunks with distinct share points and sufficient weight,
+  /// which reconstructs the key matching `key_hash` of the `reference` chunk.
+  fn run(
+    &mut self,
+    start: usize,
+    subset: &mut Vec<&'a Chunk>,
+    weight: usize,
+  ) -> Option<(Vec<u8>, Vec<&'a Chunk>)> {
+    if weight >= self.required {
+      self.attempts += 1;
+      let points: Vec<&[u8]> = subset.iter().flat_map(|c| c.points()).collect();
+      let key = gf256::shamir::shamir::reconstruct(&points);
+      if self.reference.matches_key_hash(&blake2b512(&key)) {
+        return Some((key, subset.clone()));
+      }
+      return None;
+    }
+
+    for (idx, chunk) in self.group.iter().enumerate().skip(start) {
+      if self.attempts >= MAX_ATTEMPTS {
+        return None;
+      }
+      // Shares at the same x-coordinate can't be interpolated.
+      let mut xs: Vec<u8> = subset
+        .iter()
+        .chain(Some(chunk))
+        .flat_map(|c| c.points().map(|p| p[0]))
+        .collect();
+      let count = xs.len();
+      xs.sort_unstable();
+      xs.dedup();
+      if xs.len() != count {
+        continue;
+      }
+      subset.push(chunk);
+      let found = self.run(idx + 1, subset, weight + chunk.weight() as usize);
+      subset.pop();
+      if found.is_some() {
+        return found;
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use crate::shamir::WeightedConfiguration;
+  use crate::shamir::{split_into_chunks, split_into_weighted_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  fn corrupt(chunk: &Chunk, byte: usize) -> Chunk {
+    let mut data = chunk.data().to_vec();
+    data[byte] ^= 1;
+    Chunk::new(
+      chunk.index(),
+      data,
+      chunk.configuration(),
+      chunk.key_id().clone(),
+    )
+  }
+
+  #[test]
+  fn should_recover_key_despite_corrupted_chunk() {
+    // given
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 2).unwrap());
+    let corrupted = [
+      corrupt(&chunks[0], 5),
+      Chunk::decode(&chunks[1].encode()).unwrap(),
+      corrupt(&chunks[2], 0),
+      Chunk::decode(&chunks[3].encode()).unwrap(),
+    ];
+
+    // when
+    let regular = recover_key(&corrupted[0..2]);
+    let robust = recover_key_robust(&corrupted).unwrap();
+
+    // then
+    assert_eq!(regular.unwrap_err(), KeyRecoveryError::UnexpectedKey);
+    assert_eq!(robust.key.encode(), key().encode());
+    assert_eq!(robust.invalid_chunks, vec![0, 2]);
+  }
+
+  #[test]
+  fn should_report_chunks_with_inconsistent_metadata() {
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+    let other = split_into_chunks(
+      MessageEncryptionKey::new([2u8; KEY_SIZE]),
+      ChunksConfiguration::new(2, 1).unwrap(),
+    );
+    let mixed = [
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&other[1].encode()).unwrap(),
+      Chunk::decode(&chunks[2].encode()).unwrap(),
+    ];
+
+    let robust = recover_key_robust(&mixed).unwrap();
+
+    assert_eq!(robust.key.encode(), key().encode());
+    assert_eq!(robust.invalid_chunks, vec![1]);
+  }
+
+  #[test]
+  fn should_recover_weighted_chunks() {
+    let configuration = WeightedConfiguration::new(3, vec![2, 1, 1, 1]).unwrap();
+    let chunks = split_into_weighted_chunks(key(), &configuration);
+    let corrupted = [
+      corrupt(&chunks[1], 3),
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&chunks[2].encode()).unwrap(),
+    ];
+
+    let robust = recover_key_robust(&corrupted).unwrap();
+
+    assert_eq!(robust.key.encode(), key().encode());
+    assert_eq!(robust.invalid_chunks, vec![1]);
+  }
+
+  #[test]
+  fn should_fail_without_enough_valid_chunks() {
+    let chunks = split_into_chunks(key(), ChunksConfiguration::new(2, 1).unwrap());
+    let corrupted = [
+      corrupt(&chunks[0], 5),
+      Chunk::decode(&chunks[1].encode()).unwrap(),
+    ];
+
+    assert!(matches!(
+      recover_key_robust(&corrupted),
+      Err(KeyRecoveryError::UnexpectedKey)
+    ));
+  }
+}
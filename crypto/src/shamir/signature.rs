@@ -0,0 +1,132 @@
+//! Owner-signed chunks.
+//!
+//! The chunk may be signed with the owner's [IdentityKey] to allow the
+//! recipients to tell genuine chunks from spoofed ones. The signature covers
+//! all of the chunk fields (including other extensions) except the version.
+//!
+//! Note that changing the chunk data (i.e. [Chunk::protect] or [Chunk::unprotect])
+//! removes the signature, hence the chunks should be signed after they are
+//! protected with a passphrase.
+
+use super::{Chunk, ChunkExtension, SIGNATURE_TAG};
+use crate::identity::{IdentityKey, IdentityPublicKey, Signature, SignatureError};
+
+/// Domain separation string for chunk signatures.
+const DOMAIN: &[u8] = b"ICOD-Crypto signed chunk.";
+
+impl Chunk {
+  /// Sign the chunk with given identity key, replacing the previous signature.
+  pub fn sign(mut self, identity: &IdentityKey) -> Self {
+    let signature = identity.sign(DOMAIN, &self.signing_payload());
+    self.set_extension(ChunkExtension::Signature(signature));
+    self
+  }
+
+  /// Return the signature of the chunk (if any) without verifying it.
+  pub fn signature(&self) -> Option<&Signature> {
+    self.extensions.iter().find_map(|e| match e {
+      ChunkExtension::Signature(signature) => Some(signature),
+      _ => None,
+    })
+  }
+
+  /// Verify the signature and return the signer of the chunk.
+  ///
+  /// Returns `Ok(None)` for chunks that are not signed.
+  pub fn signer(&self) -> Result<Option<IdentityPublicKey>, SignatureError> {
+    self
+      .signature()
+      .map(|signature| {
+        signature.verify(DOMAIN, &self.signing_payload())?;
+        Ok(*signature.signer())
+      })
+      .transpose()
+  }
+
+  /// Remove the signature of the chunk.
+  pub(crate) fn remove_signature(&mut self) {
+    self.remove_extension(SIGNATURE_TAG);
+  }
+
+  fn signing_payload(&self) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(self.key_id.as_bytes());
+    out.push(self.chunks_configuration.required);
+    out.push(self.chunks_configuration.spare);
+    out.push(self.chunk_index);
+    for extension in &self.extensions {
+      if extension.tag() != SIGNATURE_TAG {
+        extension.encode_to(&mut out);
+      }
+    }
+    out.extend_from_slice(&self.chunk_data);
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+  use crate::identity::IDENTITY_KEY_SIZE;
+  use crate::shamir::{recover_key, split_into_chunks, ChunksConfiguration, KeyRecoveryError};
+  use crate::Bytes;
+  use pretty_assertions::assert_eq;
+
+  fn chunks() -> Vec<Chunk> {
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap())
+  }
+
+  fn identity(byte: u8) -> IdentityKey {
+    IdentityKey::from_bytes(&[byte; IDENTITY_KEY_SIZE])
+  }
+
+  #[test]
+  fn should_sign_and_verify_chunk() {
+    // given
+    let identity = identity(1);
+    let chunk = chunks().remove(0);
+
+    // when
+    let unsigned = chunk.signer();
+    let signed = chunk.sign(&identity);
+    let decoded = Chunk::decode(&signed.encode()).unwrap();
+
+    // then
+    assert_eq!(unsigned, Ok(None));
+    assert_eq!(decoded.version(), 1);
+    assert_eq!(decoded.signer(), Ok(Some(identity.public_key())));
+  }
+
+  #[test]
+  fn should_detect_spoofed_chunk() {
+    // given
+    let mut chunk = chunks().remove(0).sign(&identity(1));
+
+    // when
+    chunk.chunk_index = 2;
+
+    // then
+    assert_eq!(chunk.signer(), Err(SignatureError::InvalidSignature));
+  }
+
+  #[test]
+  fn should_verify_signatures_during_recovery() {
+    // given
+    let mut chunks: Vec<_> = chunks().into_iter().map(|c| c.sign(&identity(1))).collect();
+    let foreign = chunks.pop().unwrap().sign(&identity(2));
+    let mut tampered = chunks.pop().unwrap();
+    let mut data = tampered.chunk_data.to_vec();
+    data[1] ^= 1;
+    tampered.chunk_data = Bytes::from(data);
+
+    // when
+    let mismatch = recover_key(&[chunks.remove(0), foreign]).unwrap_err();
+    let invalid = recover_key(&[tampered]).unwrap_err();
+
+    // then
+    assert_eq!(mismatch, KeyRecoveryError::SignerMismatch);
+    assert_eq!(invalid, KeyRecoveryError::InvalidSignature(1));
+  }
+}
@@ -0,0 +1,667 @@
+//! SLIP-39 import and export.
+//!
+//! [SLIP-39](https://github.com/satoshilabs/slips/blob/master/slip-0039.md) is a widely
+//! implemented standard of Shamir Secret Sharing using mnemonic words, supporting
+//! two levels of thresholds: `group_threshold` of groups is required, where every group
+//! is recovered from `required` of it's member shares.
+//!
+//! SLIP-39 shares are not compatible with [Chunk]s on their own (different field,
+//! checksums and encryption of the secret), hence the conversion always goes through
+//! the [MessageEncryptionKey]: the chunks are recovered first and the raw 32-bytes key
+//! is split into SLIP-39 shares (and vice versa). The recipients are then able to
+//! restore the key with any SLIP-39 implementation (e.g. a hardware wallet).
+//!
+//! Note the version of the key is not preserved, which is fine since the decryption
+//! scheme is picked based on the version of the [crate::encryption::EncryptedMessage].
+
+use aes_gcm_siv::aead::{rand_core::RngCore, OsRng};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{recover_key, split_into_chunks, Chunk, ChunksConfiguration, KeyRecoveryError};
+use crate::encryption::{MessageEncryptionKey, KEY_SIZE};
+
+mod wordlist;
+
+use wordlist::WORDLIST;
+
+/// Iteration exponent used for newly created shares.
+///
+/// The passphrase encryption uses `10000 * 2^e` PBKDF2 iterations.
+pub const DEFAULT_ITERATION_EXPONENT: u8 = 1;
+
+/// Maximal number of groups and members within a group.
+const MAX_SHARE_COUNT: usize = 16;
+
+const RADIX_BITS: usize = 10;
+const ID_EXP_WORDS: usize = 2;
+const HEADER_WORDS: usize = ID_EXP_WORDS + 2;
+const CHECKSUM_WORDS: usize = 3;
+const MIN_MNEMONIC_WORDS: usize = HEADER_WORDS + CHECKSUM_WORDS + 13;
+const DIGEST_SIZE: usize = 4;
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+
+const CUSTOMIZATION: &[u8] = b"shamir";
+const CUSTOMIZATION_EXTENDABLE: &[u8] = b"shamir_extendable";
+
+/// An error which may occur during SLIP-39 conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Slip39Error {
+  /// The group threshold or member thresholds are out of range.
+  #[error("Invalid group configuration.")]
+  InvalidConfiguration,
+  /// The passphrase may contain printable ASCII characters only.
+  #[error("The passphrase contains unsupported characters.")]
+  InvalidPassphrase,
+  /// The word at given position (0-based) of the share is not on the wordlist.
+  #[error("Unknown word at position {0}.")]
+  UnknownWord(usize),
+  /// The share has invalid number of words.
+  #[error("Invalid number of words.")]
+  InvalidLength,
+  /// The checksum of the share does not match.
+  #[error("Invalid checksum. Check the order and spelling of the words.")]
+  InvalidChecksum,
+  /// The share has invalid padding or header.
+  #[error("Invalid share.")]
+  InvalidShare,
+  /// The shares are not part of the same set.
+  #[error("The shares are not part of the same set.")]
+  InconsistentShares,
+  /// There is not enough shares to recover the secret.
+  #[error("Not enough shares.")]
+  NotEnoughShares,
+  /// The recovered secret does not match it's digest.
+  #[error("Invalid digest of the shared secret.")]
+  InvalidDigest,
+  /// The shared secret is not a 32-bytes key.
+  #[error("The shared secret is not a 32-bytes key.")]
+  UnsupportedSecret,
+  /// The key could not be recovered from the chunks.
+  #[error("{0}")]
+  KeyRecovery(#[from] KeyRecoveryError),
+}
+
+/// Split the key into SLIP-39 mnemonic shares.
+///
+/// Every group is described by [ChunksConfiguration] (`required` of `required + spare`
+/// members are needed) and `group_threshold` of the groups are required to recover the key.
+/// The shares are returned per group, every share being a list of words separated by spaces.
+pub fn split_into_slip39_shares(
+  key: MessageEncryptionKey,
+  group_threshold: u8,
+  groups: &[ChunksConfiguration],
+  passphrase: &str,
+) -> Result<Vec<Vec<String>>, Slip39Error> {
+  let invalid_group =
+    |g: &ChunksConfiguration| g.total() > MAX_SHARE_COUNT || (g.required() == 1 && g.total() > 1);
+  if groups.is_empty()
+    || groups.len() > MAX_SHARE_COUNT
+    || group_threshold == 0
+    || group_threshold as usize > groups.len()
+    || groups.iter().any(invalid_group)
+  {
+    return Err(Slip39Error::InvalidConfiguration);
+  }
+  check_passphrase(passphrase)?;
+
+  let mut encoded = key.encode();
+  let mut identifier = [0u8; 2];
+  OsRng.fill_bytes(&mut identifier);
+  let params = Params {
+    identifier: u16::from_be_bytes(identifier) >> 1,
+    extendable: true,
+    iteration_exponent: DEFAULT_ITERATION_EXPONENT,
+    group_threshold,
+    group_count: groups.len() as u8,
+  };
+  let mut encrypted = feistel(
+    &encoded[encoded.len() - KEY_SIZE..],
+    passphrase,
+    &params,
+    false,
+  );
+  encoded.wipe();
+
+  let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted);
+  crate::wipe(&mut encrypted);
+
+  Ok(
+    group_secrets
+      .into_iter()
+      .zip(groups)
+      .map(|((group_index, group_secret), group)| {
+        let member_threshold = group.required() as u8;
+        split_secret(member_threshold, group.total() as u8, &group_secret)
+          .into_iter()
+          .map(|(member_index, value)| {
+            Share {
+              params,
+              group_index,
+              member_index,
+              member_threshold,
+              value,
+            }
+            .to_words()
+          })
+          .collect()
+      })
+      .collect(),
+  )
+}
+
+/// Recover the key from the chunks and split it into SLIP-39 mnemonic shares.
+///
+/// See [split_into_slip39_shares].
+pub fn chunks_to_slip39(
+  chunks: &[Chunk],
+  group_threshold: u8,
+  groups: &[ChunksConfiguration],
+  passphrase: &str,
+) -> Result<Vec<Vec<String>>, Slip39Error> {
+  let key = recover_key(chunks)?;
+  split_into_slip39_shares(key, group_threshold, groups, passphrase)
+}
+
+/// Recover the key from SLIP-39 mnemonic shares.
+///
+/// The shares may come from any SLIP-39 implementation, as long as the shared
+/// secret is 32-bytes long. Note a wrong passphrase is not detected and simply
+/// results in a different key.
+pub fn recover_key_from_slip39(
+  mnemonics: &[&str],
+  passphrase: &str,
+) -> Result<MessageEncryptionKey, Slip39Error> {
+  let mut secret = combine_mnemonics(mnemonics, passphrase)?;
+  let key = <[u8; KEY_SIZE]>::try_from(&secret[..]).map_err(|_| Slip39Error::UnsupportedSecret);
+  crate::wipe(&mut secret);
+  key.map(MessageEncryptionKey::new)
+}
+
+/// Import existing SLIP-39 shares as a new set of chunks.
+///
+/// See [recover_key_from_slip39].
+pub fn slip39_to_chunks(
+  mnemonics: &[&str],
+  passphrase: &str,
+  configuration: ChunksConfiguration,
+) -> Result<Vec<Chunk>, Slip39Error> {
+  let key = recover_key_from_slip39(mnemonics, passphrase)?;
+  Ok(split_into_chunks(key, configuration))
+}
+
+/// Parameters common to all shares of the set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Params {
+  identifier: u16,
+  extendable: bool,
+  iteration_exponent: u8,
+  group_threshold: u8,
+  group_count: u8,
+}
+
+struct Share {
+  params: Params,
+  group_index: u8,
+  member_index: u8,
+  member_threshold: u8,
+  value: Vec<u8>,
+}
+
+impl Params {
+  fn customization(&self) -> &'static [u8] {
+    if self.extendable {
+      CUSTOMIZATION_EXTENDABLE
+    } else {
+      CUSTOMIZATION
+    }
+  }
+}
+
+impl Share {
+  fn to_words(&self) -> String {
+    let p = &self.params;
+    let id_exp =
+      (p.identifier as u32) << 5 | (p.extendable as u32) << 4 | p.iteration_exponent as u32;
+    let header = (self.group_index as u32) << 16
+      | ((p.group_threshold - 1) as u32) << 12
+      | ((p.group_count - 1) as u32) << 8
+      | (self.member_index as u32) << 4
+      | (self.member_threshold - 1) as u32;
+
+    let value_words = (self.value.len() * 8).div_ceil(RADIX_BITS);
+    let padding = value_words * RADIX_BITS - self.value.len() * 8;
+    let bits: Vec<bool> = std::iter::repeat_n(false, padding)
+      .chain(self.value.iter().flat_map(|b| bits(*b as u32, 8)))
+      .collect();
+
+    let mut indices = vec![];
+    indices.extend([id_exp >> 10, id_exp & 0x3ff, header >> 10, header & 0x3ff]);
+    indices.extend(bits.chunks(RADIX_BITS).map(from_bits));
+    indices.extend(create_checksum(self.params.customization(), &indices));
+    indices
+      .iter()
+      .map(|i| WORDLIST[*i as usize])
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  fn parse(mnemonic: &str) -> Result<Self, Slip39Error> {
+    let indices = mnemonic
+      .split_whitespace()
+      .enumerate()
+      .map(|(position, word)| {
+        let word = word.to_lowercase();
+        // the first 4 letters of every word on the list are unique.
+        WORDLIST
+          .iter()
+          .position(|w| *w == word || (word.len() >= 4 && w.starts_with(&word)))
+          .map(|index| index as u32)
+          .ok_or(Slip39Error::UnknownWord(position))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    if indices.len() < MIN_MNEMONIC_WORDS {
+      return Err(Slip39Error::InvalidLength);
+    }
+
+    let id_exp = indices[0] << 10 | indices[1];
+    let header = indices[2] << 10 | indices[3];
+    let nibble = |shift: u32| (header >> shift & 0xf) as u8;
+    let params = Params {
+      identifier: (id_exp >> 5) as u16,
+      extendable: id_exp >> 4 & 1 == 1,
+      iteration_exponent: (id_exp & 0xf) as u8,
+      group_threshold: nibble(12) + 1,
+      group_count: nibble(8) + 1,
+    };
+    if polymod(params.customization(), &indices) != 1 {
+      return Err(Slip39Error::InvalidChecksum);
+    }
+
+    let value_words = &indices[HEADER_WORDS..indices.len() - CHECKSUM_WORDS];
+    let padding = value_words.len() * RADIX_BITS % 16;
+    if padding > 8 {
+      return Err(Slip39Error::InvalidLength);
+    }
+    let bits: Vec<bool> = value_words
+      .iter()
+      .flat_map(|w| bits(*w, RADIX_BITS))
+      .collect();
+    let (padding, value) = bits.split_at(padding);
+    if padding.iter().any(|b| *b) || params.group_threshold > params.group_count {
+      return Err(Slip39Error::InvalidShare);
+    }
+
+    Ok(Self {
+      params,
+      group_index: nibble(16),
+      member_index: nibble(4),
+      member_threshold: nibble(0) + 1,
+      value: value.chunks(8).map(|b| from_bits(b) as u8).collect(),
+    })
+  }
+}
+
+/// Combine the shares and decrypt the shared secret.
+fn combine_mnemonics(mnemonics: &[&str], passphrase: &str) -> Result<Vec<u8>, Slip39Error> {
+  check_passphrase(passphrase)?;
+  let shares = mnemonics
+    .iter()
+    .map(|m| Share::parse(m))
+    .collect::<Result<Vec<_>, _>>()?;
+  let first = shares.first().ok_or(Slip39Error::NotEnoughShares)?;
+  let params = first.params;
+  if shares
+    .iter()
+    .any(|s| s.params != params || s.value.len() != first.value.len())
+  {
+    return Err(Slip39Error::InconsistentShares);
+  }
+
+  let mut group_secrets = vec![];
+  for group_index in 0..params.group_count {
+    let mut members: Vec<&Share> = vec![];
+    for share in shares.iter().filter(|s| s.group_index == group_index) {
+      if members
+        .first()
+        .is_some_and(|m| m.member_threshold != share.member_threshold)
+      {
+        return Err(Slip39Error::InconsistentShares);
+      }
+      if members.iter().all(|m| m.member_index != share.member_index) {
+        members.push(share);
+      }
+    }
+    let Some(threshold) = members.first().map(|m| m.member_threshold) else {
+      continue;
+    };
+    if members.len() >= threshold as usize {
+      let points: Vec<_> = members
+        .iter()
+        .take(threshold as usize)
+        .map(|m| (m.member_index, m.value.clone()))
+        .collect();
+      group_secrets.push((group_index, recover_secret(threshold, &points)?));
+    }
+  }
+
+  if group_secrets.len() < params.group_threshold as usize {
+    return Err(Slip39Error::NotEnoughShares);
+  }
+  group_secrets.truncate(params.group_threshold as usize);
+  let mut encrypted = recover_secret(params.group_threshold, &group_secrets)?;
+  let secret = feistel(&encrypted, passphrase, &params, true);
+  crate::wipe(&mut encrypted);
+  Ok(secret)
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), Slip39Error> {
+  if passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+    Ok(())
+  } else {
+    Err(Slip39Error::InvalidPassphrase)
+  }
+}
+
+/// Encrypt (or decrypt) the secret with a 4-round Feistel network.
+fn feistel(secret: &[u8], passphrase: &str, params: &Params, decrypt: bool) -> Vec<u8> {
+  let half = secret.len() / 2;
+  let mut left = secret[..half].to_vec();
+  let mut right = secret[half..].to_vec();
+  let salt = if params.extendable {
+    vec![]
+  } else {
+    [CUSTOMIZATION, &params.identifier.to_be_bytes()].concat()
+  };
+  let iterations = (BASE_ITERATION_COUNT << params.iteration_exponent) / ROUND_COUNT as u32;
+
+  for round in 0..ROUND_COUNT {
+    let round = if decrypt {
+      ROUND_COUNT - 1 - round
+    } else {
+      round
+    };
+    let password = [&[round], passphrase.as_bytes()].concat();
+    let mut f = vec![0u8; right.len()];
+    pbkdf2::pbkdf2_hmac::<Sha256>(&password, &[&salt[..], &right].concat(), iterations, &mut f);
+    for (l, f) in left.iter_mut().zip(&f) {
+      *l ^= f;
+    }
+    std::mem::swap(&mut left, &mut right);
+  }
+
+  right.extend_from_slice(&left);
+  crate::wipe(&mut left);
+  right
+}
+
+/// Split the secret into `count` shares, `threshold` of which are required.
+///
+/// Note the secret is stored at `x = 255`, while the digest at `x = 254`.
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+  if threshold == 1 {
+    return (0..count).map(|i| (i, secret.to_vec())).collect();
+  }
+
+  let random = |len: usize| {
+    let mut out = vec![0u8; len];
+    OsRng.fill_bytes(&mut out);
+    out
+  };
+  let mut shares: Vec<_> = (0..threshold - 2)
+    .map(|i| (i, random(secret.len())))
+    .collect();
+  let random_part = random(secret.len() - DIGEST_SIZE);
+  let mut digest_share = digest(&random_part, secret).to_vec();
+  digest_share.extend_from_slice(&random_part);
+
+  let mut base = shares.clone();
+  base.push((DIGEST_INDEX, digest_share));
+  base.push((SECRET_INDEX, secret.to_vec()));
+  for i in threshold - 2..count {
+    shares.push((i, interpolate(&base, i)));
+  }
+  for (_, value) in &mut base {
+    crate::wipe(value);
+  }
+  shares
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Slip39Error> {
+  if threshold == 1 {
+    return Ok(shares[0].1.clone());
+  }
+  let secret = interpolate(shares, SECRET_INDEX);
+  let digest_share = interpolate(shares, DIGEST_INDEX);
+  let (expected, random_part) = digest_share.split_at(DIGEST_SIZE);
+  if digest(random_part, &secret)[..] != *expected {
+    return Err(Slip39Error::InvalidDigest);
+  }
+  Ok(secret)
+}
+
+fn digest(random_part: &[u8], secret: &[u8]) -> [u8; DIGEST_SIZE] {
+  let mut mac =
+    <Hmac<Sha256> as Mac>::new_from_slice(random_part).expect("HMAC accepts keys of any size");
+  mac.update(secret);
+  let mut out = [0u8; DIGEST_SIZE];
+  out.copy_from_slice(&mac.finalize().into_bytes()[..DIGEST_SIZE]);
+  out
+}
+
+/// Lagrange interpolation at `x` over GF(256) with the Rijndael polynomial.
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+  if let Some((_, value)) = shares.iter().find(|(i, _)| *i == x) {
+    return value.clone();
+  }
+  let (exp, log) = gf256_tables();
+  let log_prod: usize = shares.iter().map(|(i, _)| log[(i ^ x) as usize]).sum();
+
+  let mut result = vec![0u8; shares[0].1.len()];
+  for (i, value) in shares {
+    let others: usize = shares
+      .iter()
+      .filter(|(j, _)| j != i)
+      .map(|(j, _)| log[(i ^ j) as usize])
+      .sum();
+    // all the sums are kept non-negative modulo 255.
+    let basis = (log_prod + 255 * shares.len() - log[(i ^ x) as usize] - others) % 255;
+    for (r, v) in result.iter_mut().zip(value) {
+      if *v != 0 {
+        *r ^= exp[(log[*v as usize] + basis) % 255];
+      }
+    }
+  }
+  result
+}
+
+fn gf256_tables() -> ([u8; 255], [usize; 256]) {
+  let mut exp = [0u8; 255];
+  let mut log = [0usize; 256];
+  let mut poly = 1u16;
+  for (i, e) in exp.iter_mut().enumerate() {
+    *e = poly as u8;
+    log[poly as usize] = i;
+    // multiply by the generator `x + 1`.
+    poly = (poly << 1) ^ poly;
+    if poly & 0x100 != 0 {
+      poly ^= 0x11b;
+    }
+  }
+  (exp, log)
+}
+
+fn polymod(customization: &[u8], values: &[u32]) -> u32 {
+  const GEN: [u32; 10] = [
+    0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009, 0x1c0c2412, 0x38086c24, 0x3090fc48,
+    0x21b1f890, 0x3f3f120,
+  ];
+  let mut chk = 1u32;
+  for v in customization
+    .iter()
+    .map(|c| *c as u32)
+    .chain(values.iter().copied())
+  {
+    let b = chk >> 20;
+    chk = (chk & 0xfffff) << 10 ^ v;
+    for (i, g) in GEN.iter().enumerate() {
+      if b >> i & 1 == 1 {
+        chk ^= g;
+      }
+    }
+  }
+  chk
+}
+
+fn create_checksum(customization: &[u8], indices: &[u32]) -> [u32; CHECKSUM_WORDS] {
+  let values = [indices, &[0; CHECKSUM_WORDS]].concat();
+  let polymod = polymod(customization, &values) ^ 1;
+  [
+    polymod >> 20 & 0x3ff,
+    polymod >> 10 & 0x3ff,
+    polymod & 0x3ff,
+  ]
+}
+
+fn bits(value: u32, count: usize) -> impl Iterator<Item = bool> {
+  (0..count).rev().map(move |i| value >> i & 1 == 1)
+}
+
+fn from_bits(bits: &[bool]) -> u32 {
+  bits.iter().fold(0, |acc, bit| acc << 1 | *bit as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn split(key: [u8; KEY_SIZE], group_threshold: u8, groups: &[(u8, u8)]) -> Vec<Vec<String>> {
+    let groups: Vec<_> = groups
+      .iter()
+      .map(|(required, spare)| ChunksConfiguration::new(*required, *spare).unwrap())
+      .collect();
+    split_into_slip39_shares(
+      MessageEncryptionKey::new(key),
+      group_threshold,
+      &groups,
+      "TREZOR",
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn should_recover_reference_vectors() {
+    // given
+    let single = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard";
+    let shares = [
+      "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+      "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+    ];
+
+    // when
+    let single = combine_mnemonics(&[single], "TREZOR").unwrap();
+    let shared = combine_mnemonics(&shares, "TREZOR").unwrap();
+
+    // then
+    assert_eq!(hex::encode(single), "bb54aac4b89dc868ba37d9cc21b2cece");
+    assert_eq!(hex::encode(shared), "b43ceb7e57a0ea8766221624d01b0864");
+    assert_eq!(
+      combine_mnemonics(&shares[..1], "TREZOR"),
+      Err(Slip39Error::NotEnoughShares)
+    );
+  }
+
+  #[test]
+  fn should_split_and_recover_groups() {
+    // given
+    let key = [7u8; KEY_SIZE];
+    let groups = split(key, 2, &[(1, 0), (2, 1), (3, 2)]);
+    let mnemonics = [
+      groups[1][2].as_str(),
+      groups[2][4].as_str(),
+      groups[1][0].as_str(),
+      groups[2][1].as_str(),
+      groups[2][0].as_str(),
+    ];
+
+    // when
+    let recovered = recover_key_from_slip39(&mnemonics, "TREZOR").unwrap();
+    let with_single =
+      recover_key_from_slip39(&[&groups[0][0], &groups[1][1], &groups[1][2]], "TREZOR");
+    let one_group = recover_key_from_slip39(&mnemonics[..2], "TREZOR");
+    let wrong_passphrase = recover_key_from_slip39(&mnemonics, "").unwrap();
+
+    // then
+    assert_eq!(
+      groups.iter().map(Vec::len).collect::<Vec<_>>(),
+      vec![1, 3, 5]
+    );
+    assert_eq!(groups[0][0].split(' ').count(), 33);
+    assert_eq!(recovered.encode(), MessageEncryptionKey::new(key).encode());
+    assert!(with_single.is_ok());
+    assert_eq!(one_group.unwrap_err(), Slip39Error::NotEnoughShares);
+    assert!(wrong_passphrase.encode() != MessageEncryptionKey::new(key).encode());
+  }
+
+  #[test]
+  fn should_convert_between_chunks_and_shares() {
+    // given
+    let key = MessageEncryptionKey::new([3u8; KEY_SIZE]);
+    let chunks = split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap());
+    let groups = [ChunksConfiguration::new(2, 0).unwrap()];
+
+    // when
+    let shares = chunks_to_slip39(&chunks[1..], 1, &groups, "").unwrap();
+    let shares: Vec<&str> = shares[0].iter().map(String::as_str).collect();
+    let imported = slip39_to_chunks(&shares, "", ChunksConfiguration::new(3, 0).unwrap()).unwrap();
+
+    // then
+    assert_eq!(imported.len(), 3);
+    assert_eq!(
+      recover_key(&imported).unwrap().encode(),
+      recover_key(&chunks).unwrap().encode()
+    );
+    assert_eq!(
+      chunks_to_slip39(&chunks[..1], 1, &groups, ""),
+      Err(Slip39Error::KeyRecovery(KeyRecoveryError::NotEnoughChunks))
+    );
+  }
+
+  #[test]
+  fn should_detect_invalid_shares() {
+    // given
+    let groups = split([1u8; KEY_SIZE], 1, &[(2, 1)]);
+    let mut words: Vec<&str> = groups[0][0].split(' ').collect();
+    words[10] = if words[10] == "academic" {
+      "acid"
+    } else {
+      "academic"
+    };
+    let invalid = words.join(" ");
+    let abbreviated: Vec<String> = groups[0][1]
+      .split(' ')
+      .map(|w| w[..w.len().min(4)].to_uppercase())
+      .collect();
+
+    // when
+    let checksum = Share::parse(&invalid).err();
+    let unknown = Share::parse(&groups[0][0].replacen(' ', " xyz ", 1)).err();
+    let short = Share::parse("academic acid acne").err();
+    let passphrase = recover_key_from_slip39(&[&groups[0][0]], "zażółć").err();
+    let recovered = recover_key_from_slip39(&[&groups[0][0], &abbreviated.join(" ")], "TREZOR");
+
+    // then
+    assert_eq!(checksum, Some(Slip39Error::InvalidChecksum));
+    assert_eq!(unknown, Some(Slip39Error::UnknownWord(1)));
+    assert_eq!(short, Some(Slip39Error::InvalidLength));
+    assert_eq!(passphrase, Some(Slip39Error::InvalidPassphrase));
+    assert_eq!(
+      recovered.unwrap().encode(),
+      MessageEncryptionKey::new([1u8; KEY_SIZE]).encode()
+    );
+  }
+}
@@ -0,0 +1,448 @@
+//! Verifiable Secret Sharing.
+//!
+//! Regular [Chunk]s produced by [super::split_into_chunks] can only be checked
+//! at the very end of the recovery (via `key_hash`) and a single corrupted chunk
+//! makes the whole recovery fail without saying which one is bad.
+//!
+//! Verifiable chunks use Pedersen VSS over the `Ristretto255` group instead of
+//! `GF(256)` sharing. The encoded key is split into 31-byte limbs and every limb
+//! is shared using a separate random polynomial `f(x)` together with a blinding
+//! polynomial `r(x)`. For every coefficient pair `(a_j, b_j)` the dealer publishes
+//! a commitment `C_j = a_j * G + b_j * H`, so that the holder of the shares
+//! `(f(i), r(i))` can check that:
+//!
+//! ```markdown
+//! f(i) * G + r(i) * H == sum(C_j * i^j)
+//! ```
+//!
+//! The commitments are perfectly hiding, so publishing them does not leak
+//! any information about the key.
+//!
+//! The [Commitments] are meant to be published alongside the chunks, and the
+//! chunks only carry the [ChunkExtension::Vss] digest of them.
+
+use aes_gcm_siv::aead::OsRng;
+use curve25519_dalek::{
+  constants::RISTRETTO_BASEPOINT_POINT as G, ristretto::CompressedRistretto, RistrettoPoint, Scalar,
+};
+
+use super::{Chunk, ChunkDecodingError, ChunkExtension, ChunksConfiguration, KeyRecoveryError};
+use crate::encryption::MessageEncryptionKey;
+use crate::{blake2b512, Bytes, Hash, HASH_SIZE};
+
+/// Number of key bytes stored in a single scalar.
+const LIMB_SIZE: usize = 31;
+
+/// Byte size of an encoded scalar or group element.
+const ELEMENT_SIZE: usize = 32;
+
+/// A specific byte sequence used to identify the encoding of [Commitments].
+pub const COMMITMENTS_ENCODING_MAGIC_SEQUENCE: &[u8] = b"icodv";
+
+/// Domain separation string for the second generator.
+const H_DOMAIN: &[u8] = b"ICOD-Crypto Pedersen VSS generator H.";
+
+/// The second generator `H` with unknown discrete logarithm with respect to `G`.
+fn h() -> RistrettoPoint {
+  RistrettoPoint::hash_from_bytes::<blake2::Blake2b512>(H_DOMAIN)
+}
+
+/// An error which may occur when verifying a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum VssError {
+  /// The chunk was not created using [split_into_verifiable_chunks].
+  #[error("The chunk is not verifiable.")]
+  NotVerifiable,
+  /// The chunk is bound to different commitments.
+  #[error("The chunk does not belong to given commitments.")]
+  CommitmentsMismatch,
+  /// The chunk data is malformed.
+  #[error("The chunk data is malformed.")]
+  MalformedData,
+  /// The chunk share does not match the commitments.
+  #[error("The chunk is invalid.")]
+  InvalidShare,
+}
+
+/// Public commitments to the polynomials used to produce verifiable chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitments {
+  /// `required` commitments for every limb.
+  limbs: Vec<Vec<CompressedRistretto>>,
+}
+
+impl Commitments {
+  /// Return the number of chunks required to recover the key.
+  pub fn required(&self) -> usize {
+    self.limbs.first().map(Vec::len).unwrap_or_default()
+  }
+
+  /// Return the digest stored in every chunk bound to these commitments.
+  pub fn digest(&self) -> Hash {
+    blake2b512(&self.encode())
+  }
+
+  /// Encode the commitments as: magic sequence, version (1 byte), number of
+  /// limbs (1 byte), number of required chunks (1 byte) followed by all of
+  /// the compressed group elements (32 bytes each).
+  pub fn encode(&self) -> Bytes {
+    let mut out = vec![];
+    out.extend_from_slice(COMMITMENTS_ENCODING_MAGIC_SEQUENCE);
+    out.push(0);
+    out.push(self.limbs.len() as u8);
+    out.push(self.required() as u8);
+    for point in self.limbs.iter().flatten() {
+      out.extend_from_slice(point.as_bytes());
+    }
+    Bytes::from(out)
+  }
+
+  /// Attempt to decode [Commitments] from given bytes slice.
+  pub fn decode(data: &[u8]) -> Result<Self, ChunkDecodingError> {
+    let data = data
+      .strip_prefix(COMMITMENTS_ENCODING_MAGIC_SEQUENCE)
+      .ok_or(ChunkDecodingError::MissingMagicBytes)?;
+    let (header, data) = match data {
+      [0, limbs, required, data @ ..] => ((*limbs as usize, *required as usize), data),
+      [_, _, _, ..] => return Err(ChunkDecodingError::InvalidVersion),
+      _ => return Err(ChunkDecodingError::NotEnoughData),
+    };
+    let (limbs, required) = header;
+    if limbs == 0 || required == 0 {
+      return Err(ChunkDecodingError::InvalidConfiguration);
+    }
+    if data.len() != limbs * required * ELEMENT_SIZE {
+      return Err(ChunkDecodingError::NotEnoughData);
+    }
+
+    let points: Vec<_> = data
+      .chunks(ELEMENT_SIZE)
+      .map(|p| CompressedRistretto::from_slice(p).expect("Chunks have exact size; qed"))
+      .collect();
+    Ok(Self {
+      limbs: points.chunks(required).map(<[_]>::to_vec).collect(),
+    })
+  }
+
+  /// Verify that the chunk was produced by the dealer who published the commitments.
+  pub fn verify(&self, chunk: &Chunk) -> Result<(), VssError> {
+    if chunk.vss_digest().ok_or(VssError::NotVerifiable)? != &self.digest() {
+      return Err(VssError::CommitmentsMismatch);
+    }
+    let shares = decode_shares(chunk.data()).ok_or(VssError::MalformedData)?;
+    if shares.len() != self.limbs.len() {
+      return Err(VssError::MalformedData);
+    }
+
+    let x = x_coordinate(chunk.index());
+    let h = h();
+    for ((s, t), commitments) in shares.iter().zip(&self.limbs) {
+      let mut expected = RistrettoPoint::default();
+      let mut power = Scalar::ONE;
+      for c in commitments {
+        let c = c.decompress().ok_or(VssError::InvalidShare)?;
+        expected += c * power;
+        power *= x;
+      }
+      if s * G + t * h != expected {
+        return Err(VssError::InvalidShare);
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Split given key into a series of verifiable chunks according to given [ChunksConfiguration].
+///
+/// Along the chunks the function returns [Commitments] which should be published
+/// (or handed out together with every chunk) to allow the recipients to check
+/// their chunks using [Commitments::verify].
+///
+/// The chunks can be used with [super::recover_key] or with [recover_key],
+/// which also identifies invalid chunks.
+pub fn split_into_verifiable_chunks(
+  key: MessageEncryptionKey,
+  chunks_configuration: ChunksConfiguration,
+) -> (Vec<Chunk>, Commitments) {
+  let required = chunks_configuration.required();
+  let total = chunks_configuration.total();
+
+  let encoded = key.encode();
+  let key_hash = blake2b512(&encoded);
+  let mut payload = Vec::with_capacity(encoded.len() + 1);
+  payload.push(encoded.len() as u8);
+  payload.extend_from_slice(&encoded);
+
+  let h = h();
+  let mut limbs = vec![];
+  let mut shares = vec![vec![]; total];
+  for limb in payload.chunks(LIMB_SIZE) {
+    let mut secret = [0u8; ELEMENT_SIZE];
+    secret[..limb.len()].copy_from_slice(limb);
+    let mut f = vec![Scalar::from_bytes_mod_order(secret)];
+    crate::wipe(&mut secret);
+    f.extend((1..required).map(|_| Scalar::random(&mut OsRng)));
+    let mut r: Vec<_> = (0..required).map(|_| Scalar::random(&mut OsRng)).collect();
+
+    limbs.push(
+      f.iter()
+        .zip(&r)
+        .map(|(a, b)| (a * G + b * h).compress())
+        .collect(),
+    );
+    for (idx, share) in shares.iter_mut().enumerate() {
+      let x = x_coordinate(idx as u8);
+      share.extend_from_slice(evaluate(&f, x).as_bytes());
+      share.extend_from_slice(evaluate(&r, x).as_bytes());
+    }
+
+    f.fill(Scalar::ZERO);
+    r.fill(Scalar::ZERO);
+  }
+  crate::wipe(&mut payload);
+
+  let commitments = Commitments { limbs };
+  let digest = commitments.digest();
+  let chunks = shares
+    .into_iter()
+    .enumerate()
+    .map(|(idx, share)| {
+      let mut chunk = Chunk::new(idx as u8, share, chunks_configuration, key_hash.clone());
+      chunk.set_extension(ChunkExtension::Vss(digest.clone()));
+      chunk
+    })
+    .collect();
+
+  (chunks, commitments)
+}
+
+/// Given a slice of verifiable [Chunk]s recover the original key.
+///
+/// Contrary to [super::recover_key] every chunk is first checked against
+/// the [Commitments] and the first invalid chunk is reported
+/// via [KeyRecoveryError::InvalidChunk].
+pub fn recover_key(
+  chunks: &[Chunk],
+  commitments: &Commitments,
+) -> Result<MessageEncryptionKey, KeyRecoveryError> {
+  if chunks.iter().any(Chunk::is_protected) {
+    return Err(KeyRecoveryError::PassphraseProtected);
+  }
+  for chunk in chunks {
+    commitments
+      .verify(chunk)
+      .map_err(|_| KeyRecoveryError::InvalidChunk(chunk.index()))?;
+  }
+
+  super::recover_key(chunks)
+}
+
+/// Reconstruct the encoded key from verifiable chunks.
+///
+/// The chunks must have distinct indices. Returns `None` in case the chunks
+/// data is malformed.
+pub(crate) fn reconstruct(chunks: &[&Chunk]) -> Option<Bytes> {
+  let shares = chunks
+    .iter()
+    .map(|c| Some((x_coordinate(c.index()), decode_shares(c.data())?)))
+    .collect::<Option<Vec<_>>>()?;
+  let limbs = shares.first()?.1.len();
+  if shares.iter().any(|(_, s)| s.len() != limbs) {
+    return None;
+  }
+
+  let mut payload = vec![];
+  for limb in 0..limbs {
+    // Lagrange interpolation at `x = 0`.
+    let mut secret = Scalar::ZERO;
+    for (i, (xi, si)) in shares.iter().enumerate() {
+      let mut num = Scalar::ONE;
+      let mut den = Scalar::ONE;
+      for (j, (xj, _)) in shares.iter().enumerate() {
+        if i != j {
+          num *= xj;
+          den *= xj - xi;
+        }
+      }
+      secret += si[limb].0 * num * den.invert();
+    }
+    let bytes = secret.to_bytes();
+    if bytes[LIMB_SIZE] != 0 {
+      return None;
+    }
+    payload.extend_from_slice(&bytes[..LIMB_SIZE]);
+  }
+
+  let (&len, rest) = payload.split_first()?;
+  let key = Bytes::from_slice(rest.get(..len as usize)?);
+  crate::wipe(&mut payload);
+  Some(key)
+}
+
+fn x_coordinate(chunk_index: u8) -> Scalar {
+  Scalar::from(chunk_index as u64 + 1)
+}
+
+fn evaluate(coefficients: &[Scalar], x: Scalar) -> Scalar {
+  coefficients
+    .iter()
+    .rev()
+    .fold(Scalar::ZERO, |acc, c| acc * x + c)
+}
+
+/// Decode `(f(i), r(i))` pairs of every limb.
+fn decode_shares(data: &[u8]) -> Option<Vec<(Scalar, Scalar)>> {
+  if data.is_empty() || !data.len().is_multiple_of(2 * ELEMENT_SIZE) {
+    return None;
+  }
+  let scalar = |bytes: &[u8]| {
+    let mut out = [0u8; ELEMENT_SIZE];
+    out.copy_from_slice(bytes);
+    Option::from(Scalar::from_canonical_bytes(out))
+  };
+  data
+    .chunks(2 * ELEMENT_SIZE)
+    .map(|pair| {
+      Some((
+        scalar(&pair[..ELEMENT_SIZE])?,
+        scalar(&pair[ELEMENT_SIZE..])?,
+      ))
+    })
+    .collect()
+}
+
+/// Decode the value of [ChunkExtension::Vss].
+pub(crate) fn decode_digest(value: &[u8]) -> Result<Hash, ChunkDecodingError> {
+  if value.len() != HASH_SIZE {
+    return Err(ChunkDecodingError::InvalidExtension);
+  }
+  Hash::from_slice(value).map_err(|_| ChunkDecodingError::InvalidExtension)
+}
+
+impl Chunk {
+  /// Returns `true` if the chunk was produced by [split_into_verifiable_chunks].
+  pub fn is_verifiable(&self) -> bool {
+    self.vss_digest().is_some()
+  }
+
+  /// Return the digest of [Commitments] the chunk is bound to.
+  pub fn vss_digest(&self) -> Option<&Hash> {
+    self.extensions.iter().find_map(|e| match e {
+      ChunkExtension::Vss(digest) => Some(digest),
+      _ => None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  fn split(required: u8, spare: u8) -> (Vec<Chunk>, Commitments) {
+    split_into_verifiable_chunks(key(), ChunksConfiguration::new(required, spare).unwrap())
+  }
+
+  #[test]
+  fn should_split_and_recover_verifiable_chunks() {
+    // given
+    let (chunks, commitments) = split(3, 2);
+
+    // when
+    let recovered = recover_key(&chunks[1..4], &commitments).unwrap();
+    let recovered_unverified = super::super::recover_key(&chunks[2..]).unwrap();
+
+    // then
+    assert_eq!(chunks.len(), 5);
+    assert!(chunks.iter().all(Chunk::is_verifiable));
+    assert_eq!(commitments.required(), 3);
+    assert_eq!(recovered.encode(), key().encode());
+    assert_eq!(recovered_unverified.encode(), key().encode());
+  }
+
+  #[test]
+  fn should_not_count_the_same_verifiable_chunk_twice() {
+    // given
+    let (chunks, commitments) = split(2, 1);
+    let duplicated = [
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&chunks[1].encode()).unwrap(),
+    ];
+
+    // when
+    let recovered = recover_key(&duplicated, &commitments).unwrap();
+    let robust = super::super::recover_key_robust(&duplicated).unwrap();
+    let not_enough = super::super::recover_key(&duplicated[..2]);
+
+    // then
+    assert_eq!(recovered.encode(), key().encode());
+    assert_eq!(robust.key.encode(), key().encode());
+    assert_eq!(not_enough.unwrap_err(), KeyRecoveryError::NotEnoughChunks);
+  }
+
+  #[test]
+  fn should_verify_chunks() {
+    let (chunks, commitments) = split(2, 1);
+
+    for chunk in &chunks {
+      assert_eq!(commitments.verify(chunk), Ok(()));
+    }
+  }
+
+  #[test]
+  fn should_encode_and_decode_verifiable_chunks() {
+    // given
+    let (chunks, commitments) = split(2, 1);
+
+    // when
+    let decoded_commitments = Commitments::decode(&commitments.encode()).unwrap();
+    let decoded_chunk = Chunk::decode(&chunks[0].encode()).unwrap();
+
+    // then
+    assert_eq!(decoded_commitments, commitments);
+    assert_eq!(decoded_chunk.version(), 1);
+    assert_eq!(decoded_commitments.verify(&decoded_chunk), Ok(()));
+  }
+
+  #[test]
+  fn should_detect_corrupted_chunk() {
+    // given
+    let (mut chunks, commitments) = split(2, 2);
+    let mut data = chunks[1].chunk_data.to_vec();
+    data[0] ^= 1;
+    chunks[1].chunk_data = Bytes::from(data);
+
+    // when
+    let verification = commitments.verify(&chunks[1]);
+    let recovered = recover_key(&chunks, &commitments);
+
+    // then
+    assert_eq!(verification, Err(VssError::InvalidShare));
+    assert_eq!(recovered.unwrap_err(), KeyRecoveryError::InvalidChunk(1));
+  }
+
+  #[test]
+  fn should_detect_forged_chunk() {
+    // given
+    let configuration = ChunksConfiguration::new(2, 1).unwrap();
+    let (mut chunks, commitments) = split(2, 1);
+    let (mut forged, _) = split(2, 1);
+    let regular = super::super::split_into_chunks(key(), configuration);
+
+    // when
+    let verification = commitments.verify(&forged[2]);
+    let not_verifiable = commitments.verify(&regular[0]);
+    let recovered = recover_key(&[chunks.remove(0), forged.remove(2)], &commitments);
+
+    // then
+    assert_eq!(verification, Err(VssError::CommitmentsMismatch));
+    assert_eq!(not_verifiable, Err(VssError::NotVerifiable));
+    assert_eq!(recovered.unwrap_err(), KeyRecoveryError::InvalidChunk(2));
+  }
+}
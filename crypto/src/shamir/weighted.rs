@@ -0,0 +1,200 @@
+//! Weighted chunks.
+//!
+//! Not every recipient has to be trusted equally. With weighted chunks a single
+//! recipient may receive several share points bundled into one [Chunk], for
+//! instance with weights `[2, 1, 1]` and `3` required points the key can be
+//! recovered by the first recipient together with any other one, while both
+//! recipients with weight `1` together hold only `2` of the required points.
+//!
+//! The [ChunksConfiguration] of weighted chunks describes share points, i.e.
+//! `required` is the threshold and `required + spare` is the sum of all weights.
+
+use super::{Chunk, ChunkExtension, ChunksConfiguration};
+use crate::encryption::MessageEncryptionKey;
+
+/// A [ChunksConfiguration] with a weight assigned to every recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedConfiguration {
+  configuration: ChunksConfiguration,
+  weights: Vec<u8>,
+}
+
+impl WeightedConfiguration {
+  /// Create a new [WeightedConfiguration].
+  ///
+  /// The method will return a `()` error in case any of the weights is `0`,
+  /// the sum of weights is lower than `required` or the configuration
+  /// is invalid (see [ChunksConfiguration::new]).
+  #[allow(clippy::result_unit_err)]
+  pub fn new(required: u8, weights: Vec<u8>) -> Result<Self, ()> {
+    if weights.is_empty() || weights.contains(&0) {
+      return Err(());
+    }
+    let total: usize = weights.iter().map(|w| *w as usize).sum();
+    if total >= 255 {
+      return Err(());
+    }
+    let spare = total.checked_sub(required as usize).ok_or(())?;
+    let configuration = ChunksConfiguration::new(required, u8::try_from(spare).map_err(|_| ())?)?;
+    Ok(Self {
+      configuration,
+      weights,
+    })
+  }
+
+  /// Return the configuration of share points.
+  pub fn configuration(&self) -> ChunksConfiguration {
+    self.configuration
+  }
+
+  /// Return the weights of consecutive chunks.
+  pub fn weights(&self) -> &[u8] {
+    &self.weights
+  }
+}
+
+impl From<ChunksConfiguration> for WeightedConfiguration {
+  fn from(configuration: ChunksConfiguration) -> Self {
+    Self {
+      configuration,
+      weights: vec![1; configuration.total()],
+    }
+  }
+}
+
+/// Split given key into a series of weighted SSS chunks.
+///
+/// The function returns one [Chunk] per weight. The chunks with weight higher
+/// than `1` carry multiple share points and are encoded with [ChunkExtension::Weight].
+///
+/// The chunks can later be used to recover the original key
+/// using [super::recover_key] function.
+pub fn split_into_weighted_chunks(
+  key: MessageEncryptionKey,
+  configuration: &WeightedConfiguration,
+) -> Vec<Chunk> {
+  let mut points = super::split_into_chunks(key, configuration.configuration).into_iter();
+
+  configuration
+    .weights
+    .iter()
+    .enumerate()
+    .map(|(idx, weight)| {
+      let bundle: Vec<Chunk> = points.by_ref().take(*weight as usize).collect();
+      let mut data = Vec::with_capacity(bundle.iter().map(|c| c.data().len()).sum());
+      for point in &bundle {
+        data.extend_from_slice(point.data());
+      }
+      let first = &bundle[0];
+      let mut chunk = Chunk::new(
+        idx as u8,
+        data,
+        first.configuration(),
+        first.key_id().clone(),
+      );
+      if *weight > 1 {
+        chunk.set_extension(ChunkExtension::Weight(*weight));
+      }
+      chunk
+    })
+    .collect()
+}
+
+impl Chunk {
+  /// Return the number of share points this chunk carries.
+  pub fn weight(&self) -> u8 {
+    self
+      .extensions
+      .iter()
+      .find_map(|e| match e {
+        ChunkExtension::Weight(weight) => Some(*weight),
+        _ => None,
+      })
+      .unwrap_or(1)
+  }
+
+  /// Check that the data can be divided into `weight` share points of the same size.
+  ///
+  /// Note the data of protected chunks is encrypted, so it needs to be unlocked first.
+  pub(crate) fn has_valid_points(&self) -> bool {
+    let len = self.chunk_data.len();
+    len > 0 && len.is_multiple_of(self.weight() as usize)
+  }
+
+  /// Return the raw share points of the chunk.
+  ///
+  /// Protected chunks are rejected by the recovery functions before
+  /// the points are accessed, so the data must be valid here.
+  pub(crate) fn points(&self) -> impl Iterator<Item = &[u8]> {
+    debug_assert!(!self.is_protected() && self.has_valid_points());
+    let weight = self.weight() as usize;
+    self
+      .chunk_data
+      .chunks((self.chunk_data.len() / weight).max(1))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::KEY_SIZE;
+  use crate::shamir::{recover_key, KeyRecoveryError};
+  use pretty_assertions::assert_eq;
+
+  fn key() -> MessageEncryptionKey {
+    MessageEncryptionKey::new([1u8; KEY_SIZE])
+  }
+
+  #[test]
+  fn should_validate_weights() {
+    assert!(WeightedConfiguration::new(3, vec![2, 1, 1]).is_ok());
+    assert_eq!(WeightedConfiguration::new(3, vec![1, 1]), Err(()));
+    assert_eq!(WeightedConfiguration::new(1, vec![1, 0]), Err(()));
+    assert_eq!(WeightedConfiguration::new(1, vec![]), Err(()));
+    assert_eq!(WeightedConfiguration::new(1, vec![200, 200]), Err(()));
+  }
+
+  #[test]
+  fn should_split_and_recover_weighted_chunks() {
+    // given
+    let configuration = WeightedConfiguration::new(3, vec![2, 1, 1]).unwrap();
+
+    // when
+    let chunks = split_into_weighted_chunks(key(), &configuration);
+    let decoded: Vec<_> = chunks
+      .iter()
+      .map(|c| Chunk::decode(&c.encode()).unwrap())
+      .collect();
+
+    // then
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(decoded[0].version(), 1);
+    assert_eq!(decoded[0].weight(), 2);
+    assert_eq!(decoded[1].version(), 0);
+    assert_eq!(decoded[1].weight(), 1);
+    assert_eq!(
+      decoded[0].configuration(),
+      ChunksConfiguration::new(3, 1).unwrap()
+    );
+
+    let spouse_and_sibling = recover_key(&decoded[0..2]).unwrap();
+    let siblings = recover_key(&decoded[1..3]);
+    assert_eq!(spouse_and_sibling.encode(), key().encode());
+    assert_eq!(siblings.unwrap_err(), KeyRecoveryError::NotEnoughChunks);
+  }
+
+  #[test]
+  fn should_not_count_the_same_chunk_twice() {
+    let configuration = WeightedConfiguration::new(3, vec![2, 1, 1]).unwrap();
+    let chunks = split_into_weighted_chunks(key(), &configuration);
+    let duplicated = [
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+      Chunk::decode(&chunks[0].encode()).unwrap(),
+    ];
+
+    assert_eq!(
+      recover_key(&duplicated).unwrap_err(),
+      KeyRecoveryError::NotEnoughChunks
+    );
+  }
+}
@@ -0,0 +1,799 @@
+//! Uniform Resources (UR) encoding.
+//!
+//! [UR](https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md)
+//! is a standard of the airgapped wallet tooling to transfer CBOR-encoded values using
+//! (animated) QR codes. The values are encoded as `ur:<type>/<bytewords>` strings,
+//! where the bytewords carry the CBOR payload followed by a `CRC-32` checksum.
+//!
+//! Larger values are split into multiple parts (`ur:<type>/<seq>-<count>/<bytewords>`)
+//! using fountain codes: the first `count` parts carry the fragments as-is, every next
+//! part is a XOR of a pseudo-random subset of fragments, so the receiver can pick up
+//! any sufficient subset of the endless stream of parts (see [UrEncoder] and [UrDecoder]).
+//!
+//! The ICOD-specific types are described by [UrPayload].
+
+use sha2::{Digest, Sha256};
+
+use crate::encryption::fountain::{xor, PeelingDecoder, MAX_FRAGMENTS_COUNT, MAX_PAYLOAD_LEN};
+use crate::encryption::EncryptedMessage;
+use crate::shamir::compact::crc32;
+use crate::shamir::Chunk;
+use crate::Bytes;
+
+/// UR type of a single [Chunk].
+pub const CHUNK_UR_TYPE: &str = "icod-chunk";
+/// UR type of the encrypted message parts.
+pub const MESSAGE_UR_TYPE: &str = "icod-msg";
+/// UR type of the encrypted message parts together with the chunks.
+pub const SECURED_UR_TYPE: &str = "icod-secured";
+
+/// Minimal length of a single fragment of multi-part UR.
+const MIN_FRAGMENT_LEN: usize = 10;
+
+const BYTEWORDS: [&str; 256] = [
+  "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+  "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+  "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+  "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+  "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+  "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+  "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+  "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+  "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+  "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+  "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+  "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+  "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+  "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+  "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+  "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+  "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+  "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+  "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+  "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+  "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+  "zest", "zinc", "zone", "zoom",
+];
+
+/// An error which may occur when decoding the UR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum UrError {
+  /// The string does not start with `ur:` or is malformed.
+  #[error("The string is not a valid UR.")]
+  InvalidUr,
+  /// The bytewords could not be decoded.
+  #[error("Invalid bytewords.")]
+  InvalidBytewords,
+  /// The checksum of the part or the whole value does not match.
+  #[error("Invalid checksum.")]
+  InvalidChecksum,
+  /// The payload is not a valid CBOR value of expected type.
+  #[error("Invalid CBOR payload.")]
+  InvalidCbor,
+  /// The part belongs to a different value than the previously received ones.
+  #[error("The part belongs to a different value.")]
+  ForeignPart,
+  /// The type of the UR is not supported.
+  #[error("Unsupported UR type.")]
+  UnsupportedType,
+  /// The chunk or message carried by the UR could not be decoded.
+  #[error("Invalid chunk or message.")]
+  InvalidPayload,
+}
+
+/// The ICOD-specific values transferred as UR.
+///
+/// The CBOR encoding of the values is:
+/// - [CHUNK_UR_TYPE]: byte string of the encoded chunk (see [Chunk::encode]).
+/// - [MESSAGE_UR_TYPE]: array of byte strings of the encoded message parts
+///   (see [EncryptedMessage::split_and_encode]).
+/// - [SECURED_UR_TYPE]: array of two arrays, the message parts and the encoded chunks.
+#[derive(Debug)]
+pub enum UrPayload {
+  /// A single chunk.
+  Chunk(Chunk),
+  /// Encoded parts of the encrypted message.
+  Message(Vec<Bytes>),
+  /// Encoded parts of the encrypted message together with the chunks.
+  Secured {
+    /// Encoded parts of the encrypted message.
+    message: Vec<Bytes>,
+    /// The chunks of the key.
+    chunks: Vec<Chunk>,
+  },
+}
+
+impl UrPayload {
+  /// Return the UR type of the value.
+  pub fn ur_type(&self) -> &'static str {
+    match self {
+      Self::Chunk(_) => CHUNK_UR_TYPE,
+      Self::Message(_) => MESSAGE_UR_TYPE,
+      Self::Secured { .. } => SECURED_UR_TYPE,
+    }
+  }
+
+  /// Encode the value as CBOR.
+  pub fn to_cbor(&self) -> Vec<u8> {
+    let mut out = vec![];
+    let write_parts = |out: &mut Vec<u8>, parts: &[Bytes]| {
+      cbor::write_head(out, cbor::ARRAY, parts.len() as u64);
+      for part in parts {
+        cbor::write_bytes(out, part);
+      }
+    };
+    let write_chunks = |out: &mut Vec<u8>, chunks: &[Chunk]| {
+      cbor::write_head(out, cbor::ARRAY, chunks.len() as u64);
+      for chunk in chunks {
+        cbor::write_bytes(out, &chunk.encode());
+      }
+    };
+    match self {
+      Self::Chunk(chunk) => cbor::write_bytes(&mut out, &chunk.encode()),
+      Self::Message(parts) => write_parts(&mut out, parts),
+      Self::Secured { message, chunks } => {
+        cbor::write_head(&mut out, cbor::ARRAY, 2);
+        write_parts(&mut out, message);
+        write_chunks(&mut out, chunks);
+      }
+    }
+    out
+  }
+
+  /// Decode the value of given UR type from CBOR.
+  ///
+  /// The chunks and the message parts are validated.
+  pub fn from_cbor(ur_type: &str, data: &[u8]) -> Result<Self, UrError> {
+    let mut data = data;
+    let payload = match ur_type {
+      CHUNK_UR_TYPE => Self::Chunk(read_chunk(&mut data)?),
+      MESSAGE_UR_TYPE => Self::Message(read_parts(&mut data)?),
+      SECURED_UR_TYPE => {
+        if cbor::read_head(&mut data, cbor::ARRAY)? != 2 {
+          return Err(UrError::InvalidCbor);
+        }
+        let message = read_parts(&mut data)?;
+        let count = cbor::read_head(&mut data, cbor::ARRAY)?;
+        let chunks = (0..count)
+          .map(|_| read_chunk(&mut data))
+          .collect::<Result<_, _>>()?;
+        Self::Secured { message, chunks }
+      }
+      _ => return Err(UrError::UnsupportedType),
+    };
+    if !data.is_empty() {
+      return Err(UrError::InvalidCbor);
+    }
+    Ok(payload)
+  }
+}
+
+fn read_chunk(data: &mut &[u8]) -> Result<Chunk, UrError> {
+  Chunk::decode(cbor::read_bytes(data)?).map_err(|_| UrError::InvalidPayload)
+}
+
+fn read_parts(data: &mut &[u8]) -> Result<Vec<Bytes>, UrError> {
+  let count = cbor::read_head(data, cbor::ARRAY)?;
+  let parts: Vec<Bytes> = (0..count)
+    .map(|_| cbor::read_bytes(data).map(Bytes::from_slice))
+    .collect::<Result<_, _>>()?;
+  EncryptedMessage::collate_from_parts(parts.clone()).map_err(|_| UrError::InvalidPayload)?;
+  Ok(parts)
+}
+
+/// Produces the parts of the UR.
+pub struct UrEncoder {
+  ur_type: String,
+  message_len: usize,
+  checksum: u32,
+  fragment_len: usize,
+  fragments: Vec<Vec<u8>>,
+  next_seq: u32,
+}
+
+impl UrEncoder {
+  /// Prepare the CBOR-encoded value of given UR type to be split into parts
+  /// carrying at most `max_fragment_len` bytes of data.
+  ///
+  /// See [MAX_FRAGMENTS_COUNT] for the bounds of the fragments.
+  pub fn new(ur_type: &str, cbor: Vec<u8>, max_fragment_len: usize) -> Self {
+    let message_len = cbor.len();
+    let max_fragment_len = max_fragment_len
+      .max(MIN_FRAGMENT_LEN)
+      .max(message_len.div_ceil(MAX_FRAGMENTS_COUNT as usize));
+    // find the smallest number of fragments of equal length.
+    let fragment_len = (1..=(message_len / MIN_FRAGMENT_LEN).max(1))
+      .map(|count| message_len.div_ceil(count))
+      .find(|len| *len <= max_fragment_len)
+      .unwrap_or(max_fragment_len)
+      .max(1);
+    let mut padded = cbor.clone();
+    padded.resize(message_len.div_ceil(fragment_len).max(1) * fragment_len, 0);
+    Self {
+      ur_type: ur_type.into(),
+      message_len,
+      checksum: crc32(&cbor),
+      fragment_len,
+      fragments: padded.chunks(fragment_len).map(<[u8]>::to_vec).collect(),
+      next_seq: 1,
+    }
+  }
+
+  /// Encode the ICOD-specific value.
+  pub fn from_payload(payload: &UrPayload, max_fragment_len: usize) -> Self {
+    Self::new(payload.ur_type(), payload.to_cbor(), max_fragment_len)
+  }
+
+  /// Number of fragments the value is split into.
+  ///
+  /// At least this many parts are required to decode the value.
+  pub fn fragments_count(&self) -> u32 {
+    self.fragments.len() as u32
+  }
+
+  /// Return `true` if the value fits into a single part.
+  pub fn is_single_part(&self) -> bool {
+    self.fragments.len() == 1
+  }
+
+  /// Return the next part of the stream.
+  pub fn next_part(&mut self) -> String {
+    let part = self.part(self.next_seq);
+    self.next_seq = self.next_seq.wrapping_add(1).max(1);
+    part
+  }
+
+  /// Return the part with given sequence number (starting at `1`).
+  ///
+  /// A single-part value is always encoded without the sequence.
+  pub fn part(&self, seq: u32) -> String {
+    if self.is_single_part() {
+      let mut cbor = self.fragments[0].clone();
+      cbor.truncate(self.message_len);
+      return format!("ur:{}/{}", self.ur_type, bytewords::encode(&cbor));
+    }
+
+    let count = self.fragments_count();
+    let mut data = vec![0u8; self.fragment_len];
+    for index in fragment_indices(seq, count, self.checksum) {
+      xor(&mut data, &self.fragments[index]);
+    }
+    let mut part = vec![];
+    cbor::write_head(&mut part, cbor::ARRAY, 5);
+    for value in [seq, count, self.message_len as u32, self.checksum] {
+      cbor::write_head(&mut part, cbor::UINT, value as u64);
+    }
+    cbor::write_bytes(&mut part, &data);
+    format!(
+      "ur:{}/{}-{}/{}",
+      self.ur_type,
+      seq,
+      count,
+      bytewords::encode(&part)
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PartHeader {
+  count: u32,
+  message_len: usize,
+  checksum: u32,
+  fragment_len: usize,
+}
+
+/// Incrementally decodes the UR from it's parts.
+#[derive(Default)]
+pub struct UrDecoder {
+  ur_type: Option<String>,
+  header: Option<PartHeader>,
+  fragments: PeelingDecoder,
+  message: Option<Vec<u8>>,
+}
+
+impl UrDecoder {
+  /// Create a new decoder.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feed the decoder with a single part (case-insensitive).
+  ///
+  /// Returns `true` if the value is fully recovered.
+  /// Duplicated parts are ignored.
+  pub fn receive(&mut self, part: &str) -> Result<bool, UrError> {
+    let (ur_type, seq, data) = parse(part)?;
+    if self.ur_type.as_ref().is_some_and(|t| *t != ur_type) {
+      return Err(UrError::ForeignPart);
+    }
+
+    let Some((seq, count)) = seq else {
+      if self.header.is_some() {
+        return Err(UrError::ForeignPart);
+      }
+      self.ur_type = Some(ur_type);
+      self.message = Some(data);
+      return Ok(true);
+    };
+
+    let mut data = &data[..];
+    if cbor::read_head(&mut data, cbor::ARRAY)? != 5 {
+      return Err(UrError::InvalidCbor);
+    }
+    let mut uint = || cbor::read_head(&mut data, cbor::UINT);
+    let (part_seq, part_count, message_len, checksum) = (uint()?, uint()?, uint()?, uint()?);
+    let fragment = cbor::read_bytes(&mut data)?.to_vec();
+    let header = PartHeader {
+      count,
+      message_len: message_len as usize,
+      checksum: u32::try_from(checksum).map_err(|_| UrError::InvalidCbor)?,
+      fragment_len: fragment.len(),
+    };
+    if part_seq != seq as u64
+      || part_count != count as u64
+      || !data.is_empty()
+      || count > MAX_FRAGMENTS_COUNT
+      || header.message_len > MAX_PAYLOAD_LEN
+      || header.message_len > count as usize * header.fragment_len
+      || header.message_len <= (count as usize - 1) * header.fragment_len
+    {
+      return Err(UrError::InvalidCbor);
+    }
+
+    match self.header {
+      None if self.message.is_some() => return Err(UrError::ForeignPart),
+      None => {
+        self.ur_type = Some(ur_type);
+        self.fragments = PeelingDecoder::new(count);
+        self.header = Some(header);
+      }
+      Some(expected) if expected != header => return Err(UrError::ForeignPart),
+      Some(_) => {}
+    }
+    if self.is_complete() {
+      return Ok(true);
+    }
+
+    self.fragments.receive(seq, fragment, || {
+      fragment_indices(seq, count, header.checksum)
+    });
+    let Some(message) = self.fragments.payload(header.message_len) else {
+      return Ok(false);
+    };
+    if crc32(&message) != header.checksum {
+      return Err(UrError::InvalidChecksum);
+    }
+    self.message = Some(message);
+    Ok(true)
+  }
+
+  /// Return `true` if the value is fully recovered.
+  pub fn is_complete(&self) -> bool {
+    self.message.is_some()
+  }
+
+  /// Number of recovered fragments.
+  pub fn decoded_fragments(&self) -> u32 {
+    match self.header {
+      None => self.message.is_some() as u32,
+      Some(_) => self.fragments.decoded_fragments(),
+    }
+  }
+
+  /// Number of fragments of the value (`0` before the first part is received).
+  pub fn fragments_count(&self) -> u32 {
+    match self.header {
+      None => self.message.is_some() as u32,
+      Some(header) => header.count,
+    }
+  }
+
+  /// Return the UR type and the CBOR-encoded value, if it's complete.
+  pub fn message(&self) -> Option<(&str, &[u8])> {
+    Some((self.ur_type.as_deref()?, self.message.as_deref()?))
+  }
+
+  /// Return the ICOD-specific value, if it's complete.
+  pub fn payload(&self) -> Option<Result<UrPayload, UrError>> {
+    self
+      .message()
+      .map(|(ur_type, cbor)| UrPayload::from_cbor(ur_type, cbor))
+  }
+}
+
+/// Check if given string looks like a UR.
+pub fn is_ur(item: &str) -> bool {
+  item.get(..3).is_some_and(|s| s.eq_ignore_ascii_case("ur:"))
+}
+
+/// Type, sequence (if multi-part) and the decoded bytewords of a UR string.
+type ParsedUr = (String, Option<(u32, u32)>, Vec<u8>);
+
+/// Parse the UR string into it's type, sequence (if multi-part) and the decoded bytewords.
+fn parse(item: &str) -> Result<ParsedUr, UrError> {
+  if !is_ur(item) {
+    return Err(UrError::InvalidUr);
+  }
+  let item = item[3..].trim().to_ascii_lowercase();
+  let components: Vec<&str> = item.split('/').collect();
+  let (ur_type, seq, words) = match components[..] {
+    [ur_type, words] => (ur_type, None, words),
+    [ur_type, seq, words] => {
+      let (seq, count) = seq.split_once('-').ok_or(UrError::InvalidUr)?;
+      let seq: u32 = seq.parse().map_err(|_| UrError::InvalidUr)?;
+      let count: u32 = count.parse().map_err(|_| UrError::InvalidUr)?;
+      if seq == 0 || count == 0 {
+        return Err(UrError::InvalidUr);
+      }
+      (ur_type, Some((seq, count)), words)
+    }
+    _ => return Err(UrError::InvalidUr),
+  };
+  let valid_type = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+  if ur_type.is_empty() || !ur_type.chars().all(valid_type) {
+    return Err(UrError::InvalidUr);
+  }
+  Ok((ur_type.into(), seq, bytewords::decode(words)?))
+}
+
+/// Return the indices of fragments mixed into the part with given sequence number.
+///
+/// The first `count` parts carry single fragments.
+fn fragment_indices(seq: u32, count: u32, checksum: u32) -> Vec<usize> {
+  if seq <= count {
+    return vec![seq as usize - 1];
+  }
+  let mut rng = Xoshiro256::new(&[seq.to_be_bytes(), checksum.to_be_bytes()].concat());
+  let weights: Vec<f64> = (1..=count).map(|i| 1.0 / i as f64).collect();
+  let degree = AliasSampler::new(&weights).next(&mut rng) + 1;
+  let mut remaining: Vec<usize> = (0..count as usize).collect();
+  // only the first `degree` items of the shuffle are used.
+  let mut shuffled = Vec::with_capacity(degree);
+  while shuffled.len() < degree {
+    let index = rng.next_int(0, remaining.len() as u64 - 1) as usize;
+    shuffled.push(remaining.remove(index));
+  }
+  shuffled.sort_unstable();
+  shuffled
+}
+
+/// `xoshiro256**` generator seeded with `SHA-256` of the seed.
+struct Xoshiro256 {
+  s: [u64; 4],
+}
+
+impl Xoshiro256 {
+  fn new(seed: &[u8]) -> Self {
+    let hash = Sha256::digest(seed);
+    let mut s = [0u64; 4];
+    for (s, bytes) in s.iter_mut().zip(hash.chunks(8)) {
+      *s = u64::from_be_bytes(bytes.try_into().expect("SHA-256 has 32 bytes; qed"));
+    }
+    Self { s }
+  }
+
+  fn next(&mut self) -> u64 {
+    let s = &mut self.s;
+    let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = s[1] << 17;
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = s[3].rotate_left(45);
+    result
+  }
+
+  fn next_double(&mut self) -> f64 {
+    self.next() as f64 / (u64::MAX as f64 + 1.0)
+  }
+
+  fn next_int(&mut self, low: u64, high: u64) -> u64 {
+    (self.next_double() * (high - low + 1) as f64) as u64 + low
+  }
+}
+
+/// Vose's alias method of sampling from a discrete distribution.
+struct AliasSampler {
+  probs: Vec<f64>,
+  aliases: Vec<usize>,
+}
+
+impl AliasSampler {
+  fn new(weights: &[f64]) -> Self {
+    let n = weights.len();
+    let sum: f64 = weights.iter().sum();
+    let mut p: Vec<f64> = weights.iter().map(|w| w * n as f64 / sum).collect();
+    let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).rev().partition(|i| p[*i] < 1.0);
+    let mut probs = vec![0.0; n];
+    let mut aliases = vec![0; n];
+    while !small.is_empty() && !large.is_empty() {
+      let (a, g) = (small.pop().unwrap(), large.pop().unwrap());
+      probs[a] = p[a];
+      aliases[a] = g;
+      p[g] += p[a] - 1.0;
+      if p[g] < 1.0 {
+        small.push(g);
+      } else {
+        large.push(g);
+      }
+    }
+    for i in large.into_iter().chain(small) {
+      probs[i] = 1.0;
+    }
+    Self { probs, aliases }
+  }
+
+  fn next(&self, rng: &mut Xoshiro256) -> usize {
+    let (r1, r2) = (rng.next_double(), rng.next_double());
+    let i = (self.probs.len() as f64 * r1) as usize;
+    if r2 < self.probs[i] {
+      i
+    } else {
+      self.aliases[i]
+    }
+  }
+}
+
+/// Minimal `bytewords` encoding (first and last letter of every word) with a checksum.
+mod bytewords {
+  use super::{crc32, UrError, BYTEWORDS};
+
+  pub fn encode(data: &[u8]) -> String {
+    data
+      .iter()
+      .chain(&crc32(data).to_be_bytes())
+      .flat_map(|b| {
+        let word = BYTEWORDS[*b as usize].as_bytes();
+        [word[0] as char, word[3] as char]
+      })
+      .collect()
+  }
+
+  pub fn decode(words: &str) -> Result<Vec<u8>, UrError> {
+    let letters = words.as_bytes();
+    if !letters.len().is_multiple_of(2) || letters.len() < 10 {
+      return Err(UrError::InvalidBytewords);
+    }
+    let mut data = letters
+      .chunks(2)
+      .map(|pair| {
+        BYTEWORDS
+          .iter()
+          .position(|w| w.as_bytes()[0] == pair[0] && w.as_bytes()[3] == pair[1])
+          .map(|b| b as u8)
+          .ok_or(UrError::InvalidBytewords)
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let checksum = data.split_off(data.len() - 4);
+    if crc32(&data).to_be_bytes()[..] != checksum[..] {
+      return Err(UrError::InvalidChecksum);
+    }
+    Ok(data)
+  }
+}
+
+/// The subset of CBOR used by the UR parts and ICOD types.
+mod cbor {
+  use super::UrError;
+
+  pub const UINT: u8 = 0;
+  pub const BYTES: u8 = 2;
+  pub const ARRAY: u8 = 4;
+
+  pub fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+      0..=23 => out.push(major | value as u8),
+      24..=0xff => out.extend([major | 24, value as u8]),
+      0x100..=0xffff => {
+        out.push(major | 25);
+        out.extend((value as u16).to_be_bytes());
+      }
+      0x1_0000..=0xffff_ffff => {
+        out.push(major | 26);
+        out.extend((value as u32).to_be_bytes());
+      }
+      _ => {
+        out.push(major | 27);
+        out.extend(value.to_be_bytes());
+      }
+    }
+  }
+
+  pub fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_head(out, BYTES, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+  }
+
+  pub fn read_head(data: &mut &[u8], major: u8) -> Result<u64, UrError> {
+    let (&first, rest) = data.split_first().ok_or(UrError::InvalidCbor)?;
+    if first >> 5 != major {
+      return Err(UrError::InvalidCbor);
+    }
+    let len = match first & 0x1f {
+      value @ 0..=23 => {
+        *data = rest;
+        return Ok(value as u64);
+      }
+      24 => 1,
+      25 => 2,
+      26 => 4,
+      27 => 8,
+      _ => return Err(UrError::InvalidCbor),
+    };
+    if rest.len() < len {
+      return Err(UrError::InvalidCbor);
+    }
+    let (value, rest) = rest.split_at(len);
+    *data = rest;
+    Ok(value.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+  }
+
+  pub fn read_bytes<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], UrError> {
+    let len = read_head(data, BYTES)? as usize;
+    if data.len() < len {
+      return Err(UrError::InvalidCbor);
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::encryption::{
+    FountainDecoder, FountainEncoder, FountainError, MessageEncryptionKey, KEY_SIZE,
+    MESSAGE_ID_SIZE,
+  };
+  use crate::shamir::{split_into_chunks, ChunksConfiguration};
+  use pretty_assertions::assert_eq;
+
+  fn make_message(len: usize) -> Vec<u8> {
+    let mut rng = Xoshiro256::new(b"Wolf");
+    (0..len).map(|_| rng.next_int(0, 255) as u8).collect()
+  }
+
+  #[test]
+  fn should_match_reference_vectors() {
+    // given
+    let mut rng = Xoshiro256::new(b"Wolf");
+    let mut cbor = vec![];
+    cbor::write_bytes(&mut cbor, &make_message(256));
+    let encoder = UrEncoder::new("bytes", cbor, 30);
+
+    // when
+    let numbers: Vec<u64> = (0..10).map(|_| rng.next() % 100).collect();
+    let indices: Vec<_> = (12..=15)
+      .map(|seq| fragment_indices(seq, 11, 0x2f19f3bb))
+      .collect();
+
+    // then
+    assert_eq!(
+      bytewords::encode(&[0, 1, 2, 128, 255]),
+      "aeadaolazmjendeoti"
+    );
+    assert_eq!(numbers, vec![42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+    assert_eq!(
+      indices,
+      vec![vec![9], vec![2, 5, 6, 8, 9, 10], vec![8], vec![1, 5]]
+    );
+    assert_eq!(
+      encoder.part(1),
+      "ur:bytes/1-9/lpadascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtdkgslpgh"
+    );
+    assert_eq!(
+      encoder.part(2),
+      "ur:bytes/2-9/lpaoascfadaxcywenbpljkhdcagwdpfnsboxgwlbaawzuefywkdplrsrjynbvygabwjldapfcsgmghhkhstlrdcxaefz"
+    );
+  }
+
+  #[test]
+  fn should_decode_from_mixed_parts() {
+    // given
+    let mut cbor = vec![];
+    cbor::write_bytes(&mut cbor, &make_message(1024));
+    let encoder = UrEncoder::new("bytes", cbor.clone(), 100);
+    let mut decoder = UrDecoder::new();
+
+    // when
+    // only the parts mixing multiple fragments, the parts are case-insensitive.
+    let mut seq = encoder.fragments_count() + 1;
+    while !decoder.receive(&encoder.part(seq).to_uppercase()).unwrap() {
+      seq += 1;
+      assert!(seq < 1_000, "Decoding does not converge.");
+    }
+
+    // then
+    assert_eq!(decoder.message(), Some(("bytes", &cbor[..])));
+    assert_eq!(decoder.fragments_count(), 11);
+    assert_eq!(
+      decoder.receive(&UrEncoder::new("bytes", vec![0x40], 100).part(1)),
+      Err(UrError::ForeignPart)
+    );
+  }
+
+  #[test]
+  fn should_encode_icod_payloads() {
+    // given
+    let key = MessageEncryptionKey::new([1u8; KEY_SIZE]);
+    let message = crate::encryption::encrypt_message(
+      &key,
+      &crate::encryption::Message::from_str("Hello World!"),
+    )
+    .unwrap();
+    let mut chunks = split_into_chunks(key, ChunksConfiguration::new(2, 1).unwrap());
+    let chunk = UrPayload::Chunk(chunks.remove(0));
+    let secured = UrPayload::Secured {
+      message: message.split_and_encode(Some(40)),
+      chunks,
+    };
+
+    // when
+    let part = UrEncoder::from_payload(&chunk, 1000).next_part();
+    let mut decoder = UrDecoder::new();
+    decoder.receive(&part).unwrap();
+    let mut encoder = UrEncoder::from_payload(&secured, 50);
+    let mut secured_decoder = UrDecoder::new();
+    while !secured_decoder.receive(&encoder.next_part()).unwrap() {}
+    let mut tampered = part.clone();
+    let typo = if &part[20..22] == "ae" { "ad" } else { "ae" };
+    tampered.replace_range(20..22, typo);
+
+    // then
+    assert!(part.starts_with("ur:icod-chunk/"));
+    assert_eq!(
+      decoder.payload().unwrap().unwrap().to_cbor(),
+      chunk.to_cbor()
+    );
+    assert_eq!(
+      secured_decoder.payload().unwrap().unwrap().to_cbor(),
+      secured.to_cbor()
+    );
+    assert!(!encoder.is_single_part());
+    assert_eq!(
+      UrDecoder::new().receive(&tampered),
+      Err(UrError::InvalidChecksum)
+    );
+    assert!(matches!(
+      UrPayload::from_cbor("bytes", &[0x40]),
+      Err(UrError::UnsupportedType)
+    ));
+  }
+
+  #[test]
+  fn should_bound_fragments_count() {
+    // given
+    let count = MAX_FRAGMENTS_COUNT + 1;
+    let mut frame = vec![];
+    frame.extend_from_slice(&0u32.to_be_bytes());
+    frame.extend_from_slice(&count.to_be_bytes());
+    frame.extend_from_slice(&u32::MAX.to_be_bytes());
+    frame.extend_from_slice(&[0u8; MESSAGE_ID_SIZE]);
+    frame.push(1);
+    let mut part = vec![];
+    cbor::write_head(&mut part, cbor::ARRAY, 5);
+    for value in [1, count, count, 0] {
+      cbor::write_head(&mut part, cbor::UINT, value as u64);
+    }
+    cbor::write_bytes(&mut part, &[0]);
+    let part = format!("ur:bytes/1-{}/{}", count, bytewords::encode(&part));
+    let message = make_message(MAX_FRAGMENTS_COUNT as usize * 20);
+    let encoder = UrEncoder::new("bytes", message.clone(), 10);
+    let frame_encoder =
+      FountainEncoder::new(EncryptedMessage::new(message, *b"test nonce x").unwrap(), 1);
+
+    // when
+    let result = UrDecoder::new().receive(&part);
+    let frame_result = FountainDecoder::new().receive(&frame);
+
+    // then
+    assert_eq!(result, Err(UrError::InvalidCbor));
+    assert_eq!(frame_result, Err(FountainError::MalformedFrame));
+    assert!(encoder.fragments_count() <= MAX_FRAGMENTS_COUNT);
+    assert!(frame_encoder.fragments_count() <= MAX_FRAGMENTS_COUNT);
+  }
+}